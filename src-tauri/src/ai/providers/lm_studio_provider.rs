@@ -38,6 +38,7 @@ pub struct LMStudioProvider {
     base_url: String,
     api_key: Option<String>,
     preferred_model_name: Option<String>,
+    preferred_embedding_model_name: Option<String>,
 }
 
 impl LMStudioProvider {
@@ -62,9 +63,28 @@ impl LMStudioProvider {
             base_url: base_url.trim_end_matches('/').to_string(),
             api_key,
             preferred_model_name: None,
+            preferred_embedding_model_name: None,
         }
     }
 
+    /// LM Studio's own REST API, served beside the OpenAI-compatible one under /v1
+    fn native_api_url(&self) -> String {
+        let root = self.base_url.strip_suffix("/v1").unwrap_or(&self.base_url);
+        format!("{}/api/v0", root)
+    }
+
+    /// GETs from LM Studio's own API, which reports each model's type
+    async fn get_native(&self, path: &str) -> Result<Value, AIProviderError> {
+        let url = format!("{}/{}", self.native_api_url(), path);
+        let response = self.client.get(&url).send().await
+            .map_err(|e| AIProviderError::APIError(format!("Network error: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(AIProviderError::APIError(format!("API returned error {}", response.status())));
+        }
+        response.json().await
+            .map_err(|e| AIProviderError::APIError(format!("Failed to parse response: {}", e)))
+    }
+
     /// Helper method to add authorization header if API key is set
     fn add_auth_header(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
         match &self.api_key {
@@ -79,6 +99,17 @@ impl LMStudioProvider {
 #[async_trait]
 impl ModelProvider for LMStudioProvider {
     async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError> {
+        // Older LM Studio releases only have the OpenAI-compatible list, which doesn't
+        // say what a model is for
+        match self.get_native("models").await {
+            Ok(list) => {
+                if let Some(data) = list["data"].as_array() {
+                    return Ok(data.iter().filter_map(native_model).collect());
+                }
+            }
+            Err(e) => log::debug!("LM Studio's native model list is unavailable, guessing model types from their ids: {}", e),
+        }
+
         let url = format!("{}/models", self.base_url);
         
         let request = self.client.get(&url);
@@ -118,18 +149,18 @@ impl ModelProvider for LMStudioProvider {
             .map_err(|e| AIProviderError::APIError(format!("Failed to parse response: {}", e)))?;
             
         Ok(model_list.data.into_iter()
-            .map(|m| AIModel {
-                id: m.id.clone(),
-                name: m.id.clone(),
-                provider: "lm_studio".to_string(),
-                capabilities: vec![ModelCapability::ChatCompletion], // Most LM Studio models support chat
-                context_length: None, // Not provided by the API
-                additional_info: serde_json::to_value(m).unwrap_or_default(),
+            .map(|m| {
+                let id = m.id.clone();
+                guessed_model(&id, serde_json::to_value(m).unwrap_or_default())
             })
             .collect())
     }
     
     async fn get_model(&self, model_id: &str) -> Result<AIModel, AIProviderError> {
+        if let Some(model) = self.get_native(&format!("models/{}", model_id)).await.ok().as_ref().and_then(native_model) {
+            return Ok(model);
+        }
+
         let url = format!("{}/models/{}", self.base_url, model_id);
         
         let request = self.client.get(&url);
@@ -158,14 +189,8 @@ impl ModelProvider for LMStudioProvider {
         let model_data: Value = response.json().await
             .map_err(|e| AIProviderError::APIError(format!("Failed to parse response: {}", e)))?;
             
-        Ok(AIModel {
-            id: model_data["id"].as_str().unwrap_or(model_id).to_string(),
-            name: model_data["id"].as_str().unwrap_or(model_id).to_string(),
-            provider: "lm_studio".to_string(),
-            capabilities: vec![ModelCapability::ChatCompletion], // Most LM Studio models support chat
-            context_length: None, // Not provided by the API
-            additional_info: model_data,
-        })
+        let id = model_data["id"].as_str().unwrap_or(model_id).to_string();
+        Ok(guessed_model(&id, model_data))
     }
 
     async fn get_preferred_inference_model(&self, preference_model: &str) -> Result<AIModel, AIProviderError> {
@@ -433,6 +458,9 @@ impl EmbeddingProvider for LMStudioProvider {
                     format!("Embeddings not supported by this LM Studio instance")
                 ));
             }
+            if response.status().as_u16() == 429 {
+                return Err(AIProviderError::RateLimitExceeded);
+            }
             
             let status = response.status();
             let text = response.text().await
@@ -467,11 +495,66 @@ impl EmbeddingProvider for LMStudioProvider {
     }
 }
 
+impl LMStudioProvider {
+    pub const DEFAULT_EMBEDDING_MODEL: &'static str = "text-embedding-nomic-embed-text-v1.5";
+}
+
 impl PreferredEmbeddingModel for LMStudioProvider {
     fn get_preferred_embedding_model(&self) -> String {
-        "text-embedding-nomic-embed-text-v1.5".to_string()
-        // LM Studio doesn't have a preferred embedding model
-        //unimplemented!("LM Studio does not yet support a preferred embedding model");
+        self.preferred_embedding_model_name
+            .clone()
+            .unwrap_or_else(|| Self::DEFAULT_EMBEDDING_MODEL.to_string())
+    }
+
+    fn set_preferred_embedding_model(&mut self, model_name: String) {
+        self.preferred_embedding_model_name = Some(model_name);
+    }
+}
+
+/// Families of embedding models whose ids don't say "embed"
+const EMBEDDING_MODEL_FAMILIES: [&str; 6] = ["bge-", "gte-", "e5-", "minilm", "mpnet", "sentence-t5"];
+
+/// A model from LM Studio's own list, whose `type` is "llm", "vlm" or "embeddings"
+fn native_model(data: &Value) -> Option<AIModel> {
+    let id = data["id"].as_str()?;
+    let capabilities = match data["type"].as_str() {
+        Some("embeddings") => vec![ModelCapability::Embedding],
+        Some("llm") | Some("vlm") => vec![ModelCapability::ChatCompletion],
+        _ => return Some(guessed_model(id, data.clone())),
+    };
+    Some(AIModel {
+        id: id.to_string(),
+        name: id.to_string(),
+        provider: "lm_studio".to_string(),
+        capabilities,
+        context_length: data["max_context_length"].as_u64().map(|length| length as usize),
+        additional_info: data.clone(),
+    })
+}
+
+/// A model LM Studio didn't give a type for, its capabilities guessed from its id.
+/// `additional_info.capabilities_inferred` is set so the guess isn't taken as final.
+fn guessed_model(id: &str, mut additional_info: Value) -> AIModel {
+    if let Some(fields) = additional_info.as_object_mut() {
+        fields.insert("capabilities_inferred".to_string(), Value::Bool(true));
+    }
+    AIModel {
+        id: id.to_string(),
+        name: id.to_string(),
+        provider: "lm_studio".to_string(),
+        capabilities: infer_model_capabilities(id),
+        context_length: None, // Not provided by the API
+        additional_info,
+    }
+}
+
+/// Embedding models are mostly published with "embed" in their id (nomic-embed-text,
+/// mxbai-embed-large, ...) or belong to a few well-known families
+fn infer_model_capabilities(model_id: &str) -> Vec<ModelCapability> {
+    let id = model_id.to_lowercase();
+    if id.contains("embed") || EMBEDDING_MODEL_FAMILIES.iter().any(|family| id.contains(family)) {
+        vec![ModelCapability::Embedding]
+    } else {
+        vec![ModelCapability::ChatCompletion] // Most LM Studio models support chat
     }
-    
 }
\ No newline at end of file
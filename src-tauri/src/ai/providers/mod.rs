@@ -81,6 +81,49 @@ pub fn create_provider(provider_type: ProviderType, config: &str) -> Provider {
     }
 }
 
+/// The provider's models that can produce embeddings
+pub async fn list_embedding_models(provider: &Provider) -> Result<Vec<AIModel>, AIProviderError> {
+    Ok(provider
+        .list_models()
+        .await?
+        .into_iter()
+        .filter(|model| model.capabilities.contains(&ModelCapability::Embedding))
+        .collect())
+}
+
+/// Checks that `model_name` is served by the provider and can produce embeddings.
+/// Ollama lists models with their tag, so "nomic-embed-text" matches "nomic-embed-text:latest".
+pub async fn validate_embedding_model(provider: &Provider, model_name: &str) -> Result<AIModel, AIProviderError> {
+    let models = provider.list_models().await?;
+    let model = models
+        .into_iter()
+        .find(|model| model_matches_name(model, model_name))
+        .ok_or_else(|| AIProviderError::ModelNotFound(format!(
+            "{} is not offered by {}", model_name, provider.get_provider_name()
+        )))?;
+
+    if !model.capabilities.contains(&ModelCapability::Embedding) {
+        // A provider that only guessed what the model does can't rule it out
+        if model.additional_info["capabilities_inferred"].as_bool() == Some(true) {
+            log::warn!(
+                "{} doesn't say whether {} can create embeddings; using it anyway",
+                provider.get_provider_name(), model_name
+            );
+            return Ok(model);
+        }
+        return Err(AIProviderError::ModelNotAvailable(format!(
+            "{} can't create embeddings", model_name
+        )));
+    }
+    Ok(model)
+}
+
+fn model_matches_name(model: &AIModel, model_name: &str) -> bool {
+    model.id == model_name
+        || model.name == model_name
+        || model.id.strip_suffix(":latest") == Some(model_name)
+}
+
 #[async_trait]
 impl ChatCompletionProvider for Provider {
     async fn create_chat_completion(
@@ -171,4 +214,12 @@ impl PreferredEmbeddingModel for Provider {
             Provider::Ollama(provider) => provider.get_preferred_embedding_model(),
         }
     }
+
+    fn set_preferred_embedding_model(&mut self, model_name: String) {
+        match self {
+            Provider::OpenAI(provider) => provider.set_preferred_embedding_model(model_name),
+            Provider::LMStudio(provider) => provider.set_preferred_embedding_model(model_name),
+            Provider::Ollama(provider) => provider.set_preferred_embedding_model(model_name),
+        }
+    }
 }
\ No newline at end of file
@@ -26,6 +26,7 @@ pub struct OllamaProvider {
     #[serde(skip)]
     client: Ollama,
     preferred_model_name: Option<String>,
+    preferred_embedding_model_name: Option<String>,
 }
 
 impl OllamaProvider {
@@ -57,6 +58,7 @@ impl OllamaProvider {
         OllamaProvider {
             client: ollama,
             preferred_model_name: None,
+            preferred_embedding_model_name: None,
         }
     }
 }
@@ -362,9 +364,18 @@ impl EmbeddingProvider for OllamaProvider {
     }
 }
 
+impl OllamaProvider {
+    pub const DEFAULT_EMBEDDING_MODEL: &'static str = "nomic-embed-text";
+}
+
 impl PreferredEmbeddingModel for OllamaProvider {
     fn get_preferred_embedding_model(&self) -> String {
-        "nomic-embed-text".to_string()
-        //unimplemented!("Ollama does not yet support a preferred embedding model");
+        self.preferred_embedding_model_name
+            .clone()
+            .unwrap_or_else(|| Self::DEFAULT_EMBEDDING_MODEL.to_string())
+    }
+
+    fn set_preferred_embedding_model(&mut self, model_name: String) {
+        self.preferred_embedding_model_name = Some(model_name);
     }
 }
\ No newline at end of file
@@ -27,6 +27,7 @@ pub struct OpenAIProvider {
     #[serde(skip)]
     last_request: Option<CreateChatCompletionRequest>,
     preferred_model_name: Option<String>,
+    preferred_embedding_model_name: Option<String>,
 }
 
 impl OpenAIProvider {
@@ -37,6 +38,7 @@ impl OpenAIProvider {
             client: Client::with_config(config),
             last_request: None,
             preferred_model_name: None,
+            preferred_embedding_model_name: None,
         }
     }
     
@@ -52,7 +54,7 @@ impl OpenAIProvider {
 
     /// Create with an existing OpenAI client
     pub fn with_client(client: Client<OpenAIConfig>) -> Self {
-        OpenAIProvider { client, last_request: None, preferred_model_name: None }
+        OpenAIProvider { client, last_request: None, preferred_model_name: None, preferred_embedding_model_name: None }
     }
     
     /// Get a reference to the underlying OpenAI client
@@ -298,7 +300,12 @@ impl EmbeddingProvider for OpenAIProvider {
         };
         
         let response = self.client.embeddings().create(request).await
-            .map_err(|e| AIProviderError::APIError(e.to_string()))?;
+            .map_err(|e| match &e {
+                // Surface 429s distinctly so callers can back off and retry
+                async_openai::error::OpenAIError::ApiError(api_err)
+                    if api_err.code.as_deref() == Some("rate_limit_exceeded") => AIProviderError::RateLimitExceeded,
+                _ => AIProviderError::APIError(e.to_string()),
+            })?;
             
         let embedding_model_name = PreferredEmbeddingModel::get_preferred_embedding_model(self);
 
@@ -312,9 +319,19 @@ impl EmbeddingProvider for OpenAIProvider {
     }
 }
 
+impl OpenAIProvider {
+    pub const DEFAULT_EMBEDDING_MODEL: &'static str = "text-embedding-ada-002";
+}
+
 impl PreferredEmbeddingModel for OpenAIProvider {
     fn get_preferred_embedding_model(&self) -> String {
-        "text-embedding-ada-002".to_string()
+        self.preferred_embedding_model_name
+            .clone()
+            .unwrap_or_else(|| Self::DEFAULT_EMBEDDING_MODEL.to_string())
+    }
+
+    fn set_preferred_embedding_model(&mut self, model_name: String) {
+        self.preferred_embedding_model_name = Some(model_name);
     }
 }
 
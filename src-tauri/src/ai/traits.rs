@@ -87,6 +87,9 @@ pub trait EmbeddingProvider {
 /// Trait to get the preferred embedding model
 pub trait PreferredEmbeddingModel {
     fn get_preferred_embedding_model(&self) -> String;
+
+    /// Overrides the provider's default embedding model
+    fn set_preferred_embedding_model(&mut self, model_name: String);
 }
 
 #[async_trait]
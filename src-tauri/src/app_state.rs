@@ -5,7 +5,10 @@ use chrono::format;
 use tauri::AppHandle;
 use crate::conversations::Conversation;
 //use crate::document_store::{self, DocumentStore};
-use crate::document_store::DocumentStore;
+use crate::document_store::{DocumentStore, SearchHit};
+use crate::ingestion_queue::IngestionQueue;
+use crate::linked_folders::LinkedFolderSync;
+use crate::attached_canons::AttachedCanons;
 use crate::embeddings::EmbeddingGenerator;
 use crate::logger::Logger;
 use std::sync::{Arc};
@@ -52,13 +55,17 @@ pub struct AppState {
     pub preferences: Mutex<Preferences>, 
     pub app_handle: Option<AppHandle>,
     pub rag_cache: Arc<Mutex<RagCache>>,
+    pub ingestion_queue: IngestionQueue,
+    pub linked_folder_sync: LinkedFolderSync,
+    /// Canons searched read-only alongside `doc_store`; lock after `doc_store`
+    pub attached_canons: Mutex<AttachedCanons>,
 }
 
 // Define a new struct for caching
 #[derive(Debug)]
 pub struct RagCache {
     pub last_context: String,
-    pub similarity_documents: Vec<(i64, String, usize, String, f32)>,
+    pub similarity_documents: Vec<SearchHit>,
     pub last_updated: chrono::DateTime<Utc>,
 }
 
@@ -92,6 +99,9 @@ impl AppState {
             preferences: Mutex::new(Preferences::default()), // Start with default preferences
             app_handle: Some(app_handle),
             rag_cache: Arc::new(Mutex::new(RagCache::new())),
+            ingestion_queue: IngestionQueue::new(),
+            linked_folder_sync: LinkedFolderSync::new(),
+            attached_canons: Mutex::new(AttachedCanons::new()),
         };
         Ok(app_state)
     }
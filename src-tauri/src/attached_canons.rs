@@ -0,0 +1,343 @@
+#![allow(dead_code)]
+// src/attached_canons.rs
+//
+// Canons attached read-only next to the primary canon, say a personal style canon
+// alongside a project's research. RAG searches fan out across the primary and every
+// attached canon, scale each canon's scores by its weight, and label every hit with the
+// canon it came from. Attachments are remembered in preferences by path and weight.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ai::providers::Provider;
+use crate::ai::traits::PreferredEmbeddingModel;
+use crate::ai::{self, AIProviderError};
+use crate::document_store::{DocumentStore, SearchHit, SearchOptions};
+
+/// An attachment as remembered in preferences
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttachedCanonPreference {
+    pub path: String,
+    pub weight: f32,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AttachedCanonInfo {
+    pub label: String,
+    pub path: String,
+    pub weight: f32,
+    pub embedding_model_name: Option<String>,
+}
+
+#[derive(Debug)]
+struct AttachedCanon {
+    label: String,
+    path: PathBuf,
+    weight: f32,
+    store: DocumentStore,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AttachError {
+    #[error("Canon weights must be greater than zero, not {0}")]
+    InvalidWeight(f32),
+    #[error("{0} is already attached")]
+    AlreadyAttached(String),
+    #[error("{0} is the primary canon")]
+    IsPrimary(String),
+    #[error("{0} isn't attached")]
+    NotAttached(String),
+}
+
+/// The label a canon is shown with: its file name, or its folder's name for canons
+/// created as a folder holding `ghostwriter.canon`
+fn default_label(db_path: &Path) -> String {
+    let stem = db_path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    if stem == "ghostwriter" {
+        if let Some(folder) = db_path.parent().and_then(|parent| parent.file_name()) {
+            return folder.to_string_lossy().to_string();
+        }
+    }
+    stem
+}
+
+fn check_weight(weight: f32) -> Result<(), AttachError> {
+    if weight.is_finite() && weight > 0.0 {
+        Ok(())
+    } else {
+        Err(AttachError::InvalidWeight(weight))
+    }
+}
+
+/// The canons attached alongside the primary one, kept in `AppState`
+#[derive(Debug, Default)]
+pub struct AttachedCanons {
+    canons: Vec<AttachedCanon>,
+}
+
+impl AttachedCanons {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.canons.is_empty()
+    }
+
+    /// Opens a canon read-only and adds it to searches with the given weight
+    pub fn attach(
+        &mut self,
+        primary: &DocumentStore,
+        path: &Path,
+        weight: f32,
+        label: Option<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        check_weight(weight)?;
+        let store = DocumentStore::open_read_only(path.to_path_buf())?;
+        let db_path = PathBuf::from(store.get_database_path());
+        let same_file = |other: &str| match (std::fs::canonicalize(&db_path), std::fs::canonicalize(other)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => db_path == Path::new(other),
+        };
+        if same_file(primary.get_database_path()) {
+            return Err(Box::new(AttachError::IsPrimary(db_path.display().to_string())));
+        }
+        if self.canons.iter().any(|canon| same_file(&canon.path.to_string_lossy())) {
+            return Err(Box::new(AttachError::AlreadyAttached(db_path.display().to_string())));
+        }
+
+        let label = label
+        .map(|label| label.trim().to_string())
+        .filter(|label| !label.is_empty())
+        .unwrap_or_else(|| default_label(&db_path));
+        log::info!("Attached {} ({}) read-only with weight {}", label, db_path.display(), weight);
+        self.canons.push(AttachedCanon { label, path: db_path, weight, store });
+        Ok(())
+    }
+
+    fn position(&self, path: &str) -> Option<usize> {
+        let wanted = DocumentStore::resolve_database_path(&PathBuf::from(path));
+        self.canons.iter().position(|canon| canon.path == wanted || canon.path == Path::new(path))
+    }
+
+    pub fn detach(&mut self, path: &str) -> Result<(), AttachError> {
+        let position = self.position(path).ok_or_else(|| AttachError::NotAttached(path.to_string()))?;
+        let canon = self.canons.remove(position);
+        log::info!("Detached {} ({})", canon.label, canon.path.display());
+        Ok(())
+    }
+
+    pub fn set_weight(&mut self, path: &str, weight: f32) -> Result<(), AttachError> {
+        check_weight(weight)?;
+        let position = self.position(path).ok_or_else(|| AttachError::NotAttached(path.to_string()))?;
+        self.canons[position].weight = weight;
+        Ok(())
+    }
+
+    pub async fn list(&self) -> Vec<AttachedCanonInfo> {
+        let mut infos = Vec::with_capacity(self.canons.len());
+        for canon in &self.canons {
+            infos.push(AttachedCanonInfo {
+                label: canon.label.clone(),
+                path: canon.path.to_string_lossy().to_string(),
+                weight: canon.weight,
+                embedding_model_name: canon.store.canon_embedding_model().await.ok().flatten(),
+            });
+        }
+        infos
+    }
+
+    /// The attachments to remember in preferences
+    pub fn preferences(&self) -> Vec<AttachedCanonPreference> {
+        self.canons
+        .iter()
+        .map(|canon| AttachedCanonPreference {
+            path: canon.path.to_string_lossy().to_string(),
+            weight: canon.weight,
+            label: Some(canon.label.clone()),
+        })
+        .collect()
+    }
+
+    /// Re-attaches canons remembered in preferences that aren't attached yet. Canons that
+    /// can't be opened any more are logged and left out.
+    pub fn restore(&mut self, primary: &DocumentStore, remembered: &[AttachedCanonPreference]) {
+        for attachment in remembered {
+            if self.position(&attachment.path).is_some() {
+                continue;
+            }
+            if let Err(e) = self.attach(primary, Path::new(&attachment.path), attachment.weight, attachment.label.clone()) {
+                log::warn!("Couldn't re-attach canon {}: {}", attachment.path, e);
+            }
+        }
+    }
+
+    /// Searches the primary canon and every attached canon, ranking all hits together
+    /// by score times canon weight. Attached canons that fail to search (a different
+    /// embedding dimension, say) are logged and skipped; the primary's errors are returned.
+    pub async fn search(
+        &self,
+        primary: &DocumentStore,
+        query_text: &str,
+        query_embedding_result: &Result<Vec<ai::models::Embedding>, AIProviderError>,
+        provider: &Provider,
+        similar_docs_count: usize,
+        similarity_threshold: f32,
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchHit>, Box<dyn std::error::Error>> {
+        let mut hits = primary
+        .search(query_text, query_embedding_result, provider, similar_docs_count, similarity_threshold, options)
+        .await?;
+        if self.canons.is_empty() {
+            return Ok(hits);
+        }
+
+        for canon in &self.canons {
+            match canon
+            .store
+            .search(query_text, query_embedding_result, provider, similar_docs_count, similarity_threshold, options)
+            .await
+            {
+                Ok(canon_hits) => hits.extend(canon_hits.into_iter().map(|hit| SearchHit {
+                    canon_name: canon.label.clone(),
+                    canon_weight: canon.weight,
+                    ..hit
+                })),
+                Err(e) => log::warn!(
+                    "Skipping attached canon {} for this search ({}): {}",
+                    canon.label,
+                    provider.get_preferred_embedding_model(),
+                    e
+                ),
+            }
+        }
+
+        Ok(merge_ranked(hits, similar_docs_count))
+    }
+}
+
+/// Best hits first, pinned ones ahead of the rest and never cut; chunk ids are only
+/// unique within a canon, so duplicates are keyed on both
+fn merge_ranked(mut hits: Vec<SearchHit>, count: usize) -> Vec<SearchHit> {
+    hits.sort_by(|a, b| {
+        b.pinned.cmp(&a.pinned)
+        .then(b.rank_score().partial_cmp(&a.rank_score()).unwrap_or(std::cmp::Ordering::Equal))
+    });
+    let mut seen = std::collections::HashSet::new();
+    let mut merged = Vec::with_capacity(count);
+    for hit in hits {
+        if seen.insert((hit.canon_name.clone(), hit.chunk_id)) && (hit.pinned || merged.len() < count) {
+            merged.push(hit);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document_store::insert_test_document;
+    use crate::chunking::ChunkProvenance;
+
+    fn hit(canon_name: &str, chunk_id: usize, similarity: f32, canon_weight: f32) -> SearchHit {
+        SearchHit {
+            doc_id: 1,
+            doc_name: "doc".to_string(),
+            chunk_id,
+            chunk: String::new(),
+            similarity,
+            lexical_score: None,
+            fused_score: None,
+            canon_name: canon_name.to_string(),
+            canon_weight,
+            doc_weight: 1.0,
+            pinned: false,
+            provenance: ChunkProvenance::default(),
+            citation: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_weights_decide_the_ranking_across_canons() {
+        let hits = vec![
+            hit("project", 1, 0.90, 1.0),
+            hit("style", 1, 0.85, 1.5),
+            hit("style", 2, 0.80, 0.5),
+            hit("project", 1, 0.70, 1.0),
+        ];
+        let merged = merge_ranked(hits, 3);
+        let labels: Vec<(&str, usize)> = merged.iter().map(|hit| (hit.canon_name.as_str(), hit.chunk_id)).collect();
+        // The same chunk id in two canons is two chunks; one chunk appears once
+        assert_eq!(labels, vec![("style", 1), ("project", 1), ("style", 2)]);
+    }
+
+    #[test]
+    fn test_attach_is_read_only_and_refuses_the_primary() {
+        let dir = tempfile::tempdir().unwrap();
+        let primary = DocumentStore::new(dir.path().join("primary")).unwrap();
+        let style_dir = dir.path().join("style");
+        drop(DocumentStore::new(style_dir.clone()).unwrap());
+
+        let mut attached = AttachedCanons::new();
+        assert!(attached.attach(&primary, &dir.path().join("primary"), 1.0, None).is_err());
+        assert!(attached.attach(&primary, &style_dir, 0.0, None).is_err());
+        attached.attach(&primary, &style_dir, 0.5, None).unwrap();
+        assert!(attached.attach(&primary, &style_dir, 1.0, None).is_err());
+
+        let remembered = attached.preferences();
+        assert_eq!(remembered[0].label.as_deref(), Some("style"));
+        assert_eq!(remembered[0].weight, 0.5);
+
+        {
+            let conn = attached.canons[0].store.conn.blocking_lock();
+            assert!(conn.execute("DELETE FROM documents", []).is_err(), "attached canons are read-only");
+        }
+
+        attached.set_weight(&style_dir.to_string_lossy(), 2.0).unwrap();
+        attached.detach(&style_dir.to_string_lossy()).unwrap();
+        assert!(attached.is_empty());
+    }
+
+    #[test]
+    fn test_search_fans_out_across_a_canon_with_an_older_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        // Opening the primary registers sqlite-vec for the connections below
+        let primary = DocumentStore::new(dir.path().join("primary")).unwrap();
+        {
+            let conn = primary.conn.blocking_lock();
+            insert_test_document(&conn, 1, "/tmp/style.md", "test-model");
+            DocumentStore::insert_chunk_embedding(&conn, 1, "short sentences", &[0.8, 0.6, 0.0], "test-model").unwrap();
+        }
+        // Last written at version 10: no tags, weights, chunk positions or source metadata
+        let older_path = dir.path().join("older.canon");
+        {
+            let mut conn = rusqlite::Connection::open(&older_path).unwrap();
+            crate::migrations::migrate_to(&mut conn, 10).unwrap();
+            insert_test_document(&conn, 1, "/tmp/noir.md", "test-model");
+            DocumentStore::insert_chunk_embedding(&conn, 1, "rain on the window", &[1.0, 0.0, 0.0], "test-model").unwrap();
+        }
+
+        let mut attached = AttachedCanons::new();
+        attached.attach(&primary, &older_path, 2.0, Some("older".to_string())).unwrap();
+        let mut provider = Provider::Ollama(crate::ai::providers::OllamaProvider::new(""));
+        provider.set_preferred_embedding_model("test-model".to_string());
+        let query = Ok(vec![ai::models::Embedding { vector: vec![1.0, 0.0, 0.0], index: 0, model_name: None }]);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let hybrid = SearchOptions { mode: crate::document_store::SearchMode::Hybrid, ..Default::default() };
+        let hits = runtime.block_on(attached.search(&primary, "rain", &query, &provider, 5, 0.0, &hybrid)).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].canon_name, "older");
+        assert_eq!(hits[0].chunk, "rain on the window");
+        assert_eq!(hits[0].provenance, ChunkProvenance::default());
+        assert_eq!(hits[1].chunk, "short sentences");
+
+        // A tag filter finds nothing in a canon from before tags, rather than failing it
+        let tagged = SearchOptions { tags: vec!["noir".to_string()], ..Default::default() };
+        let hits = runtime.block_on(attached.search(&primary, "rain", &query, &provider, 5, 0.0, &tagged)).unwrap();
+        assert!(hits.is_empty());
+    }
+}
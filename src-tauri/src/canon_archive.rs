@@ -0,0 +1,795 @@
+#![allow(dead_code)]
+// src/canon_archive.rs
+//
+// Canons hold absolute source paths, so the SQLite file alone doesn't travel between
+// machines. Export writes a zip archive instead:
+//
+//   manifest.json          what's inside, the canon's details and each embedding model
+//   documents.json         documents, with paths relative to the folder they share
+//   chunks.json            chunk text and positions
+//   vectors/<model>.bin    per model: records of chunk id (i64) + vector (f32 × dimension), little-endian
+//   sources/<doc id>.txt   optionally, each document's original source text
+//
+// Import merges an archive into the open canon. Paths can be re-rooted on the new
+// machine, documents already present (same content hash and model) are skipped, and
+// vectors are checked against the canon's indexes like any other insert.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use chrono::Local;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::collections;
+use crate::document_metadata::{self, SourceMetadata};
+use crate::document_store::{blob_to_vector, DocumentStore};
+use crate::migrations;
+
+pub const ARCHIVE_FORMAT: &str = "ghostwriter-canon-archive";
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DOCUMENTS_ENTRY: &str = "documents.json";
+const CHUNKS_ENTRY: &str = "chunks.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format: String,
+    pub format_version: u32,
+    /// Schema version of the canon the archive was exported from
+    pub schema_version: i64,
+    pub exported_at: String,
+    pub canon: ArchivedCanon,
+    /// The folder document paths were made relative to, on the exporting machine
+    pub source_root: Option<String>,
+    pub embedding_models: Vec<ArchivedVectorIndex>,
+    pub documents: usize,
+    pub chunks: usize,
+    pub includes_source_text: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedCanon {
+    pub name: Option<String>,
+    pub owner: Option<String>,
+    pub notes: Option<String>,
+    pub embedding_model_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedVectorIndex {
+    pub embedding_model_name: String,
+    pub dimension: usize,
+    pub normalized: Option<bool>,
+    pub provider: Option<String>,
+    /// Archive entry holding this model's vectors
+    pub vectors: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedDocument {
+    pub id: i64,
+    pub name: String,
+    pub title: Option<String>,
+    pub authors: Option<serde_json::Value>,
+    pub created_at: String,
+    /// The path on the exporting machine
+    pub original_path: String,
+    /// The path relative to the manifest's source_root, when the document lives under it
+    pub relative_path: Option<String>,
+    pub paused: bool,
+    pub embedding_model_name: String,
+    pub notes: Option<String>,
+    pub chunking_strategy: Option<String>,
+    pub chunking_params: Option<serde_json::Value>,
+    pub content_hash: Option<String>,
+    pub source_modified_at: Option<String>,
+    /// Archive entry holding the original source text, if it was included
+    pub source_text: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default = "default_retrieval_weight")]
+    pub retrieval_weight: f32,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub source_metadata: Option<SourceMetadata>,
+}
+
+fn default_retrieval_weight() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedChunk {
+    pub id: i64,
+    pub doc_id: i64,
+    pub chunk: String,
+    pub embedding_model_name: String,
+    pub content_hash: Option<String>,
+    pub chunk_index: Option<i64>,
+    #[serde(default)]
+    pub start_offset: Option<i64>,
+    #[serde(default)]
+    pub end_offset: Option<i64>,
+    #[serde(default)]
+    pub page: Option<u32>,
+    #[serde(default)]
+    pub section: Option<String>,
+}
+
+/// What importing an archive did
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ArchiveImportReport {
+    pub documents_imported: usize,
+    /// Documents skipped because the canon already holds the same content for the same model
+    pub duplicates_skipped: usize,
+    /// Documents skipped because the canon holds different content at the same path
+    pub conflicts_skipped: usize,
+    pub chunks_imported: usize,
+    pub vectors_imported: usize,
+    pub sources_written: usize,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("Not a canon archive (format {0})")]
+    UnknownFormat(String),
+    #[error("Archive format version {found} is newer than this version of Ghostwriter supports ({supported})")]
+    NewerFormat { found: u32, supported: u32 },
+    #[error("Archive is damaged: {0}")]
+    Corrupt(String),
+    #[error("Archive path {0} would leave the folder it is imported into")]
+    UnsafePath(String),
+}
+
+fn file_options() -> FileOptions {
+    FileOptions::default().compression_method(CompressionMethod::Deflated)
+}
+
+fn json_column(value: Option<String>) -> Option<serde_json::Value> {
+    value.and_then(|value| serde_json::from_str(&value).ok())
+}
+
+/// The deepest folder containing every local path, if there is one
+fn common_root<'a>(paths: impl Iterator<Item = &'a str>) -> Option<PathBuf> {
+    let mut root: Option<Vec<Component>> = None;
+    for path in paths.map(Path::new).filter(|path| path.is_absolute()) {
+        let parent: Vec<Component> = path.parent().map(|parent| parent.components().collect()).unwrap_or_default();
+        root = Some(match root {
+            None => parent,
+            Some(root) => root.into_iter().zip(parent).take_while(|(a, b)| a == b).map(|(a, _)| a).collect(),
+        });
+    }
+    root.filter(|components| components.len() > 1)
+    .map(|components| components.into_iter().collect())
+}
+
+/// A path relative to `root` with forward slashes, so archives read the same on every platform
+fn relative_to(path: &str, root: &Path) -> Option<String> {
+    let relative = Path::new(path).strip_prefix(root).ok()?;
+    let parts: Vec<String> = relative.components().map(|part| part.as_os_str().to_string_lossy().to_string()).collect();
+    Some(parts.join("/"))
+}
+
+/// A document path from an archive, if it stays inside the folder it's joined onto:
+/// relative, and without `..`, `.` or a drive prefix
+fn archive_relative_path(path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    let safe = path.components().next().is_some() && path.components().all(|component| matches!(component, Component::Normal(_)));
+    Some(path.to_path_buf()).filter(|_| safe)
+}
+
+/// `path`, or "name (2).ext", "name (3).ext" and so on when a file is already there or
+/// another document in the same import has claimed it
+fn unclaimed_path(path: PathBuf, claimed: &HashSet<PathBuf>) -> PathBuf {
+    if !path.exists() && !claimed.contains(&path) {
+        return path;
+    }
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+    (2..)
+    .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+    .find(|candidate| !candidate.exists() && !claimed.contains(candidate))
+    .unwrap()
+}
+
+/// Source files written during an import. Unless the import commits, they and any
+/// folders made for them are removed again when this is dropped.
+#[derive(Default)]
+struct SourceWrites {
+    files: Vec<PathBuf>,
+    dirs: Vec<PathBuf>,
+    committed: bool,
+}
+
+impl SourceWrites {
+    fn write(&mut self, path: &Path, text: &str) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            let mut missing: Vec<PathBuf> = parent.ancestors().take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists()).map(Path::to_path_buf).collect();
+            std::fs::create_dir_all(parent)?;
+            // Outermost first, so they can be removed innermost first
+            missing.reverse();
+            self.dirs.extend(missing);
+        }
+        // create_new: never replace a file, even one that appeared since the path was chosen
+        std::fs::OpenOptions::new().write(true).create_new(true).open(path)?.write_all(text.as_bytes())?;
+        self.files.push(path.to_path_buf());
+        Ok(())
+    }
+}
+
+impl Drop for SourceWrites {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        for file in &self.files {
+            if let Err(e) = std::fs::remove_file(file) {
+                log::warn!("Couldn't remove {} after a failed import: {}", file.display(), e);
+            }
+        }
+        for dir in self.dirs.iter().rev() {
+            // Only empty folders go; anything else put there since is left alone
+            let _ = std::fs::remove_dir(dir);
+        }
+    }
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut entry = archive
+    .by_name(name)
+    .map_err(|_| ArchiveError::Corrupt(format!("missing {}", name)))?;
+    let mut bytes = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_documents(conn: &Connection) -> Result<Vec<ArchivedDocument>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, title, authors, created_at, file_path, paused, embedding_model_name, notes,
+        chunking_strategy, chunking_params, content_hash, source_modified_at, retrieval_weight, pinned
+        FROM documents ORDER BY id",
+    )?;
+    let documents = stmt.query_map([], |row| {
+        Ok(ArchivedDocument {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            title: row.get(2)?,
+            authors: json_column(row.get(3)?),
+            created_at: row.get(4)?,
+            original_path: row.get(5)?,
+            relative_path: None,
+            paused: row.get::<_, Option<bool>>(6)?.unwrap_or(false),
+            embedding_model_name: row.get::<_, Option<String>>(7)?.unwrap_or_else(|| "unknown".to_string()),
+            notes: row.get(8)?,
+            chunking_strategy: row.get(9)?,
+            chunking_params: json_column(row.get(10)?),
+            content_hash: row.get(11)?,
+            source_modified_at: row.get(12)?,
+            source_text: None,
+            tags: Vec::new(),
+            retrieval_weight: row.get::<_, Option<f64>>(13)?.map_or(1.0, |weight| weight as f32),
+            pinned: row.get::<_, Option<bool>>(14)?.unwrap_or(false),
+            source_metadata: None,
+        })
+    })?;
+    let mut documents = documents.collect::<Result<Vec<_>, _>>()?;
+    let mut tags = collections::tags_by_document(conn)?;
+    let mut metadata = document_metadata::metadata_by_document(conn)?;
+    for document in documents.iter_mut() {
+        document.tags = tags.remove(&document.id).unwrap_or_default();
+        document.source_metadata = metadata.remove(&document.id);
+    }
+    Ok(documents)
+}
+
+fn read_chunks(conn: &Connection) -> Result<Vec<ArchivedChunk>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT id, doc_id, chunk, embedding_model_name, content_hash, chunk_index, start_offset, end_offset, page, section
+        FROM embeddings ORDER BY doc_id, chunk_index, id",
+    )?;
+    let chunks = stmt.query_map([], |row| {
+        Ok(ArchivedChunk {
+            id: row.get(0)?,
+            doc_id: row.get(1)?,
+            chunk: row.get(2)?,
+            embedding_model_name: row.get::<_, Option<String>>(3)?.unwrap_or_else(|| "unknown".to_string()),
+            content_hash: row.get(4)?,
+            chunk_index: row.get(5)?,
+            start_offset: row.get(6)?,
+            end_offset: row.get(7)?,
+            page: row.get(8)?,
+            section: row.get(9)?,
+        })
+    })?;
+    chunks.collect()
+}
+
+impl DocumentStore {
+    /// Writes the canon to a portable archive at `archive_path`. With `include_sources`,
+    /// each document's source file is read and stored alongside; sources that can't be
+    /// read (moved files, URLs) are left out and noted in the log.
+    pub async fn export_archive(
+        &self,
+        archive_path: &Path,
+        include_sources: bool,
+    ) -> Result<ArchiveManifest, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+
+        let canon = conn
+        .query_row(
+            "SELECT name, owner, notes, embedding_model_name FROM canon ORDER BY id LIMIT 1",
+            [],
+            |row| Ok(ArchivedCanon {
+                name: row.get(0)?,
+                owner: row.get(1)?,
+                notes: row.get(2)?,
+                embedding_model_name: row.get(3)?,
+            }),
+        )
+        .optional()?
+        .unwrap_or(ArchivedCanon {
+            name: Some(self.get_database_name().to_string()),
+            owner: None,
+            notes: None,
+            embedding_model_name: None,
+        });
+
+        let mut documents = read_documents(&conn)?;
+        let chunks = read_chunks(&conn)?;
+        let source_root = common_root(documents.iter().map(|document| document.original_path.as_str()));
+        if let Some(root) = &source_root {
+            for document in documents.iter_mut() {
+                document.relative_path = relative_to(&document.original_path, root);
+            }
+        }
+
+        let mut writer = ZipWriter::new(File::create(archive_path)?);
+
+        let mut embedding_models = Vec::new();
+        for index in Self::vector_indexes(&conn)? {
+            let entry = format!("vectors/{}.bin", index.table_name);
+            let mut stmt = conn.prepare(&format!(
+                "SELECT v.rowid, v.embedding FROM {} v JOIN embeddings e ON e.id = v.rowid ORDER BY v.rowid",
+                index.table_name
+            ))?;
+            let mut rows = stmt.query([])?;
+            let mut bytes = Vec::new();
+            let mut count = 0;
+            while let Some(row) = rows.next()? {
+                let chunk_id: i64 = row.get(0)?;
+                let blob: Vec<u8> = row.get(1)?;
+                bytes.extend_from_slice(&chunk_id.to_le_bytes());
+                bytes.extend_from_slice(&blob);
+                count += 1;
+            }
+            writer.start_file(entry.as_str(), file_options())?;
+            writer.write_all(&bytes)?;
+            embedding_models.push(ArchivedVectorIndex {
+                embedding_model_name: index.embedding_model_name,
+                dimension: index.dimension,
+                normalized: index.normalized,
+                provider: index.provider,
+                vectors: entry,
+                count,
+            });
+        }
+
+        if include_sources {
+            for document in documents.iter_mut() {
+                match std::fs::read_to_string(&document.original_path) {
+                    Ok(text) => {
+                        let entry = format!("sources/{}.txt", document.id);
+                        writer.start_file(entry.as_str(), file_options())?;
+                        writer.write_all(text.as_bytes())?;
+                        document.source_text = Some(entry);
+                    }
+                    Err(e) => log::warn!("Leaving the source of {} out of the archive: {}", document.original_path, e),
+                }
+            }
+        }
+
+        let manifest = ArchiveManifest {
+            format: ARCHIVE_FORMAT.to_string(),
+            format_version: ARCHIVE_FORMAT_VERSION,
+            schema_version: migrations::current_version(&conn)?,
+            exported_at: Local::now().to_rfc3339(),
+            canon,
+            source_root: source_root.map(|root| root.to_string_lossy().to_string()),
+            embedding_models,
+            documents: documents.len(),
+            chunks: chunks.len(),
+            includes_source_text: include_sources,
+        };
+
+        writer.start_file(DOCUMENTS_ENTRY, file_options())?;
+        writer.write_all(&serde_json::to_vec(&documents)?)?;
+        writer.start_file(CHUNKS_ENTRY, file_options())?;
+        writer.write_all(&serde_json::to_vec(&chunks)?)?;
+        writer.start_file(MANIFEST_ENTRY, file_options())?;
+        writer.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+        writer.finish()?;
+
+        log::info!(
+            "Exported {} documents and {} chunks to {}",
+            manifest.documents, manifest.chunks, archive_path.display()
+        );
+        Ok(manifest)
+    }
+
+    /// Reads an archive's manifest without importing anything
+    pub fn read_archive_manifest(archive_path: &Path) -> Result<ArchiveManifest, Box<dyn std::error::Error + Send + Sync>> {
+        let mut archive = ZipArchive::new(File::open(archive_path)?)?;
+        let manifest: ArchiveManifest = serde_json::from_slice(&read_entry(&mut archive, MANIFEST_ENTRY)?)?;
+        if manifest.format != ARCHIVE_FORMAT {
+            return Err(ArchiveError::UnknownFormat(manifest.format).into());
+        }
+        if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+            return Err(ArchiveError::NewerFormat {
+                found: manifest.format_version,
+                supported: ARCHIVE_FORMAT_VERSION,
+            }.into());
+        }
+        Ok(manifest)
+    }
+
+    /// Merges an archive into this canon in a single transaction.
+    ///
+    /// Document paths are rewritten in order of preference: into `sources_dir` when the
+    /// archive carries the document's source text (which is written there), under
+    /// `path_root` when the document sat under the exporter's source root, and otherwise
+    /// left as they were. Paths that would lead out of those folders are refused, and a
+    /// source file is never overwritten: one already at the path keeps it, and the
+    /// imported copy is written beside it as "name (2).ext". Sources written by an
+    /// import that then fails are removed again.
+    pub async fn import_archive(
+        &self,
+        archive_path: &Path,
+        path_root: Option<&Path>,
+        sources_dir: Option<&Path>,
+    ) -> Result<ArchiveImportReport, Box<dyn std::error::Error + Send + Sync>> {
+        let manifest = Self::read_archive_manifest(archive_path)?;
+        let mut archive = ZipArchive::new(File::open(archive_path)?)?;
+        let documents: Vec<ArchivedDocument> = serde_json::from_slice(&read_entry(&mut archive, DOCUMENTS_ENTRY)?)?;
+        let chunks: Vec<ArchivedChunk> = serde_json::from_slice(&read_entry(&mut archive, CHUNKS_ENTRY)?)?;
+
+        let mut report = ArchiveImportReport::default();
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        let mut written = SourceWrites::default();
+        let mut claimed = HashSet::new();
+
+        // Archive document id -> id in this canon
+        let mut doc_ids: HashMap<i64, i64> = HashMap::new();
+        for document in &documents {
+            if let Some(hash) = &document.content_hash {
+                let duplicate: bool = tx.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM documents WHERE content_hash = ?1 AND embedding_model_name = ?2)",
+                    params![hash, document.embedding_model_name],
+                    |row| row.get(0),
+                )?;
+                if duplicate {
+                    report.duplicates_skipped += 1;
+                    continue;
+                }
+            }
+
+            let source_text = match (&document.source_text, sources_dir) {
+                (Some(entry), Some(_)) => Some(String::from_utf8(read_entry(&mut archive, entry)?)?),
+                _ => None,
+            };
+            let relative_path = document.relative_path.clone().unwrap_or_else(|| format!("{}.txt", document.name));
+            let file_path = match (source_text.is_some(), sources_dir, path_root, &document.relative_path) {
+                (true, Some(dir), _, _) => {
+                    let relative_path = archive_relative_path(&relative_path).ok_or_else(|| ArchiveError::UnsafePath(relative_path.clone()))?;
+                    unclaimed_path(dir.join(relative_path), &claimed)
+                }
+                (_, _, Some(root), Some(relative_path)) => {
+                    root.join(archive_relative_path(relative_path).ok_or_else(|| ArchiveError::UnsafePath(relative_path.clone()))?)
+                }
+                _ => PathBuf::from(&document.original_path),
+            };
+            let file_path_str = file_path.to_string_lossy().to_string();
+
+            let taken: bool = tx.query_row(
+                "SELECT EXISTS(SELECT 1 FROM documents WHERE file_path = ?1 AND embedding_model_name = ?2)",
+                params![file_path_str, document.embedding_model_name],
+                |row| row.get(0),
+            )?;
+            if taken {
+                log::warn!("Not importing {}: the canon already has different content at that path", file_path_str);
+                report.conflicts_skipped += 1;
+                continue;
+            }
+
+            if let Some(text) = &source_text {
+                written.write(&file_path, text)?;
+                claimed.insert(file_path.clone());
+                report.sources_written += 1;
+            }
+
+            tx.execute(
+                "INSERT INTO documents (name, title, authors, created_at, file_path, paused, embedding_model_name, notes,
+                chunking_strategy, chunking_params, content_hash, source_modified_at, retrieval_weight, pinned)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                params![
+                    document.name,
+                    document.title,
+                    document.authors.as_ref().map(|authors| authors.to_string()),
+                    document.created_at,
+                    file_path_str,
+                    document.paused,
+                    document.embedding_model_name,
+                    document.notes.clone().unwrap_or_default(),
+                    document.chunking_strategy,
+                    document.chunking_params.as_ref().map(|chunking| chunking.to_string()),
+                    document.content_hash,
+                    // A written source is new on this machine, so let the next ingestion re-check it
+                    if source_text.is_some() { None } else { document.source_modified_at.clone() },
+                    f64::from(document.retrieval_weight),
+                    document.pinned,
+                ],
+            )?;
+            let doc_id = tx.last_insert_rowid();
+            collections::tag_document(&tx, doc_id, &document.tags)?;
+            if let Some(metadata) = &document.source_metadata {
+                document_metadata::set_metadata(&tx, doc_id, metadata)?;
+            }
+            doc_ids.insert(document.id, doc_id);
+            report.documents_imported += 1;
+        }
+
+        // Archive chunk id -> id in this canon
+        let mut chunk_ids: HashMap<i64, i64> = HashMap::new();
+        for chunk in &chunks {
+            let doc_id = match doc_ids.get(&chunk.doc_id) {
+                Some(doc_id) => *doc_id,
+                None => continue,
+            };
+            tx.execute(
+                "INSERT INTO embeddings (doc_id, chunk, embedding_model_name, content_hash, chunk_index, start_offset, end_offset, page, section)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    doc_id,
+                    chunk.chunk,
+                    chunk.embedding_model_name,
+                    chunk.content_hash,
+                    chunk.chunk_index,
+                    chunk.start_offset,
+                    chunk.end_offset,
+                    chunk.page,
+                    chunk.section
+                ],
+            )?;
+            chunk_ids.insert(chunk.id, tx.last_insert_rowid());
+            report.chunks_imported += 1;
+        }
+
+        for index in &manifest.embedding_models {
+            let bytes = read_entry(&mut archive, &index.vectors)?;
+            let record_size = 8 + 4 * index.dimension;
+            if index.dimension == 0 || bytes.len() % record_size != 0 {
+                return Err(ArchiveError::Corrupt(format!("{} doesn't hold {}-dimensional vectors", index.vectors, index.dimension)).into());
+            }
+            for record in bytes.chunks_exact(record_size) {
+                let mut id_bytes = [0u8; 8];
+                id_bytes.copy_from_slice(&record[..8]);
+                let chunk_id = match chunk_ids.get(&i64::from_le_bytes(id_bytes)) {
+                    Some(chunk_id) => *chunk_id,
+                    None => continue,
+                };
+                let vector = blob_to_vector(&record[8..]);
+                Self::insert_vector(&tx, chunk_id, &vector, &index.embedding_model_name).map_err(|e| e.to_string())?;
+                report.vectors_imported += 1;
+            }
+            if let Some(provider) = &index.provider {
+                Self::record_index_provider(&tx, &index.embedding_model_name, provider)?;
+            }
+        }
+
+        if let Some(model) = &manifest.canon.embedding_model_name {
+            Self::record_canon_embedding_model(&tx, self.get_database_name(), model)?;
+        }
+        tx.commit()?;
+        written.committed = true;
+
+        log::info!(
+            "Imported {} documents ({} duplicates and {} conflicts skipped) from {}",
+            report.documents_imported, report.duplicates_skipped, report.conflicts_skipped, archive_path.display()
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document_store::insert_test_document;
+
+    fn canon_with_documents(dir: &Path, source_dir: &Path) -> DocumentStore {
+        let store = DocumentStore::new(dir.join("team.canon")).unwrap();
+        let conn = store.conn.blocking_lock();
+        for (id, name, text, vector) in [
+            (1, "the-big-sleep.md", "The General's orchids", [1.0, 0.0, 0.0]),
+            (2, "notes/farewell-my-lovely.md", "A big man in a loud suit", [0.0, 1.0, 0.0]),
+        ] {
+            let path = source_dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, text).unwrap();
+            insert_test_document(&conn, id, &path.to_string_lossy(), "test-model");
+            conn.execute(
+                "UPDATE documents SET content_hash = ?2 WHERE id = ?1",
+                params![id, crate::document_store::content_hash(text)],
+            ).unwrap();
+            DocumentStore::insert_chunk_embedding(&conn, id, text, &vector, "test-model").unwrap();
+        }
+        drop(conn);
+        store
+    }
+
+    #[test]
+    fn test_archive_round_trip_rewrites_paths_and_dedupes() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let exporter = tempfile::tempdir().unwrap();
+        let importer = tempfile::tempdir().unwrap();
+        let source_dir = exporter.path().join("manuscripts");
+        let store = canon_with_documents(exporter.path(), &source_dir);
+
+        let archive_path = exporter.path().join("team.canonarchive");
+        let manifest = runtime.block_on(store.export_archive(&archive_path, true)).unwrap();
+        assert_eq!((manifest.documents, manifest.chunks), (2, 2));
+        assert_eq!(manifest.source_root.as_deref(), Some(source_dir.to_string_lossy().as_ref()));
+        assert_eq!(manifest.embedding_models[0].dimension, 3);
+        assert_eq!(manifest.embedding_models[0].count, 2);
+
+        let target = DocumentStore::new(importer.path().join("mine.canon")).unwrap();
+        let new_root = importer.path().join("shared");
+        let report = runtime.block_on(target.import_archive(&archive_path, None, Some(&new_root))).unwrap();
+        assert_eq!((report.documents_imported, report.chunks_imported, report.vectors_imported), (2, 2, 2));
+        assert_eq!(report.sources_written, 2);
+        assert_eq!(
+            std::fs::read_to_string(new_root.join("notes/farewell-my-lovely.md")).unwrap(),
+            "A big man in a loud suit"
+        );
+
+        {
+            let conn = target.conn.blocking_lock();
+            let path: String = conn.query_row(
+                "SELECT file_path FROM documents WHERE name = 'the-big-sleep.md'",
+                [],
+                |row| row.get(0),
+            ).unwrap();
+            assert_eq!(Path::new(&path), new_root.join("the-big-sleep.md"));
+            let results = DocumentStore::knn_search(&conn, "test-model", &[0.0, 1.0, 0.0], 1).unwrap();
+            assert_eq!(results[0].3, "A big man in a loud suit");
+        }
+
+        // Importing the same archive again finds everything already there
+        let report = runtime.block_on(target.import_archive(&archive_path, None, None)).unwrap();
+        assert_eq!((report.documents_imported, report.duplicates_skipped), (0, 2));
+    }
+
+    #[test]
+    fn test_archive_from_a_newer_format_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("future.canonarchive");
+        let mut writer = ZipWriter::new(File::create(&archive_path).unwrap());
+        writer.start_file(MANIFEST_ENTRY, file_options()).unwrap();
+        writer.write_all(serde_json::json!({
+            "format": ARCHIVE_FORMAT,
+            "format_version": ARCHIVE_FORMAT_VERSION + 1,
+            "schema_version": 99,
+            "exported_at": "later",
+            "canon": { "name": null, "owner": null, "notes": null, "embedding_model_name": null },
+            "source_root": null,
+            "embedding_models": [],
+            "documents": 0,
+            "chunks": 0,
+            "includes_source_text": false
+        }).to_string().as_bytes()).unwrap();
+        writer.finish().unwrap();
+
+        let error = DocumentStore::read_archive_manifest(&archive_path).unwrap_err();
+        assert!(matches!(error.downcast_ref::<ArchiveError>(), Some(ArchiveError::NewerFormat { .. })));
+    }
+
+    fn write_archive(path: &Path, documents: serde_json::Value, sources: &[(&str, &str)]) {
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        writer.start_file(MANIFEST_ENTRY, file_options()).unwrap();
+        writer.write_all(serde_json::json!({
+            "format": ARCHIVE_FORMAT,
+            "format_version": ARCHIVE_FORMAT_VERSION,
+            "schema_version": 14,
+            "exported_at": "now",
+            "canon": { "name": null, "owner": null, "notes": null, "embedding_model_name": null },
+            "source_root": "/exported",
+            "embedding_models": [],
+            "documents": documents.as_array().unwrap().len(),
+            "chunks": 0,
+            "includes_source_text": true
+        }).to_string().as_bytes()).unwrap();
+        writer.start_file(DOCUMENTS_ENTRY, file_options()).unwrap();
+        writer.write_all(documents.to_string().as_bytes()).unwrap();
+        writer.start_file(CHUNKS_ENTRY, file_options()).unwrap();
+        writer.write_all(b"[]").unwrap();
+        for (entry, text) in sources {
+            writer.start_file(*entry, file_options()).unwrap();
+            writer.write_all(text.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn archived_document(id: i64, relative_path: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "name": relative_path,
+            "created_at": "now",
+            "original_path": format!("/exported/{}", relative_path),
+            "relative_path": relative_path,
+            "paused": false,
+            "embedding_model_name": "test-model",
+            "content_hash": format!("hash-{}", id),
+            "source_text": format!("sources/{}.txt", id)
+        })
+    }
+
+    #[test]
+    fn test_archive_paths_cannot_escape_and_failed_imports_leave_nothing() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("crafted.canonarchive");
+        write_archive(
+            &archive_path,
+            serde_json::json!([archived_document(1, "notes/fine.md"), archived_document(2, "../escaped.md")]),
+            &[("sources/1.txt", "fine"), ("sources/2.txt", "gotcha")],
+        );
+
+        let target = DocumentStore::new(dir.path().join("mine.canon")).unwrap();
+        let sources_dir = dir.path().join("shared");
+        let error = runtime.block_on(target.import_archive(&archive_path, None, Some(&sources_dir))).unwrap_err();
+        assert!(matches!(error.downcast_ref::<ArchiveError>(), Some(ArchiveError::UnsafePath(_))));
+        assert!(!dir.path().join("escaped.md").exists());
+        // The first document's source was written, then removed with the rolled-back import
+        assert!(!sources_dir.exists());
+        let count: i64 = target.conn.blocking_lock().query_row("SELECT COUNT(*) FROM documents", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+
+        assert!(archive_relative_path("/etc/passwd").is_none());
+        assert!(archive_relative_path("notes/./fine.md").is_some());
+        assert!(archive_relative_path("").is_none());
+    }
+
+    #[test]
+    fn test_imported_sources_never_overwrite_files() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("team.canonarchive");
+        write_archive(&archive_path, serde_json::json!([archived_document(1, "fine.md")]), &[("sources/1.txt", "theirs")]);
+
+        let sources_dir = dir.path().join("shared");
+        std::fs::create_dir_all(&sources_dir).unwrap();
+        std::fs::write(sources_dir.join("fine.md"), "mine").unwrap();
+
+        let target = DocumentStore::new(dir.path().join("mine.canon")).unwrap();
+        let report = runtime.block_on(target.import_archive(&archive_path, None, Some(&sources_dir))).unwrap();
+        assert_eq!(report.sources_written, 1);
+        assert_eq!(std::fs::read_to_string(sources_dir.join("fine.md")).unwrap(), "mine");
+        assert_eq!(std::fs::read_to_string(sources_dir.join("fine (2).md")).unwrap(), "theirs");
+        let path: String = target.conn.blocking_lock().query_row("SELECT file_path FROM documents", [], |row| row.get(0)).unwrap();
+        assert_eq!(Path::new(&path), sources_dir.join("fine (2).md"));
+    }
+
+    #[test]
+    fn test_common_root_is_the_shared_folder() {
+        let root = common_root(["/a/b/c/one.md", "/a/b/two.md", "https://example.com/page"].into_iter());
+        assert_eq!(root, Some(PathBuf::from("/a/b")));
+        assert_eq!(common_root(["/one.md", "/two.md"].into_iter()), None);
+    }
+}
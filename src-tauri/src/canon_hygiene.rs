@@ -0,0 +1,697 @@
+#![allow(dead_code)]
+// src/canon_hygiene.rs
+//
+// Finds what crowds out good retrieval in a canon: clusters of near-identical chunks
+// (overlapping chunk windows, the same story in several newsletter digests), chunks
+// with nothing to say, boilerplate repeated across pages and documents, and documents
+// that never got any chunks. Near duplicates must agree on both measures: their word
+// shingles overlap and their vectors are close. Candidate pairs come from MinHash
+// signatures bucketed by band (locality-sensitive hashing), so only chunks that likely
+// share shingles are ever compared, and vectors are looked up for those alone. The
+// report can then be cleaned up in one go.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::chunk_editor;
+use crate::document_store::{blob_to_vector, DocumentStore};
+
+/// MinHash signatures are split into this many bands of `BAND_ROWS` values; two chunks
+/// are compared when any band matches. With 10 × 3, pairs sharing 70% of their shingles
+/// are found 98% of the time and pairs sharing 30% about a quarter of the time.
+const MINHASH_BANDS: usize = 10;
+const BAND_ROWS: usize = 3;
+/// Buckets larger than this pair each member with the first only, not with each other
+const MAX_BUCKET_PAIRS: usize = 50;
+const PREVIEW_CHARS: usize = 120;
+
+/// Thresholds for the analysis
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HygieneOptions {
+    /// Cosine similarity at or above which two chunks' vectors count as close
+    pub min_similarity: f32,
+    /// Share of word shingles two chunks must have in common
+    pub min_shingle_overlap: f32,
+    /// Chunks with fewer words than this are reported as empty
+    pub min_chunk_words: usize,
+    /// Short text repeated at least this many times is boilerplate
+    pub boilerplate_min_repeats: usize,
+    /// Longer chunks are never boilerplate; repeats of them are duplicates
+    pub boilerplate_max_words: usize,
+}
+
+impl Default for HygieneOptions {
+    fn default() -> Self {
+        Self {
+            min_similarity: 0.95,
+            min_shingle_overlap: 0.7,
+            min_chunk_words: 3,
+            boilerplate_min_repeats: 3,
+            boilerplate_max_words: 30,
+        }
+    }
+}
+
+/// What the cleanup removes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HygieneCleanup {
+    /// All but the first chunk of each duplicate cluster
+    pub duplicates: bool,
+    pub empty_chunks: bool,
+    /// All but the first occurrence of each boilerplate text
+    pub boilerplate: bool,
+    pub empty_documents: bool,
+}
+
+impl Default for HygieneCleanup {
+    fn default() -> Self {
+        Self { duplicates: true, empty_chunks: true, boilerplate: true, empty_documents: true }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HygieneChunk {
+    pub chunk_id: i64,
+    pub doc_id: i64,
+    pub doc_name: String,
+    pub preview: String,
+    /// Hash of the chunk's text when analysed, so cleanup leaves chunks edited since alone
+    #[serde(skip)]
+    fingerprint: u64,
+}
+
+/// Near-identical chunks; `keep` is the oldest one in an active document, or the
+/// oldest when every copy is paused
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateCluster {
+    pub keep: HygieneChunk,
+    pub duplicates: Vec<HygieneChunk>,
+}
+
+/// Chunks that are the same short text, numbers aside, such as running headers.
+/// `keep` is the first occurrence in an active document; cleanup removes only the repeats.
+#[derive(Debug, Clone, Serialize)]
+pub struct BoilerplateGroup {
+    pub text: String,
+    pub keep: HygieneChunk,
+    pub repeats: Vec<HygieneChunk>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EmptyDocument {
+    pub id: i64,
+    pub name: String,
+    pub file_path: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HygieneReport {
+    pub chunks_scanned: usize,
+    pub duplicate_clusters: Vec<DuplicateCluster>,
+    pub empty_chunks: Vec<HygieneChunk>,
+    pub boilerplate: Vec<BoilerplateGroup>,
+    /// Documents without a single chunk; documents still being ingested are left out
+    pub documents_without_embeddings: Vec<EmptyDocument>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HygieneCleanupReport {
+    pub duplicates_removed: usize,
+    pub empty_chunks_removed: usize,
+    pub boilerplate_removed: usize,
+    pub documents_removed: usize,
+}
+
+struct StoredChunk {
+    chunk: HygieneChunk,
+    text: String,
+    paused: bool,
+}
+
+/// Where the copy to keep sits among `members`, in the order given: the first in an
+/// active document, so cleanup never leaves the only copy where search can't see it
+fn survivor(members: &[&StoredChunk]) -> usize {
+    members.iter().position(|stored| !stored.paused).unwrap_or(0)
+}
+
+fn fingerprint(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn preview(text: &str) -> String {
+    let preview: String = text.chars().take(PREVIEW_CHARS).collect();
+    if preview.len() < text.len() { format!("{}…", preview.trim_end()) } else { preview }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|word| !word.is_empty())
+    .map(|word| word.to_lowercase())
+    .collect()
+}
+
+/// Lower-cased text with runs of digits replaced, so "Page 12" and "Page 13" match
+fn boilerplate_key(text: &str) -> String {
+    let mut key = String::new();
+    let mut in_digits = false;
+    for word in text.split_whitespace() {
+        if !key.is_empty() {
+            key.push(' ');
+        }
+        for c in word.chars() {
+            if c.is_ascii_digit() {
+                if !in_digits {
+                    key.push('#');
+                }
+                in_digits = true;
+            } else {
+                key.extend(c.to_lowercase());
+                in_digits = false;
+            }
+        }
+        in_digits = false;
+    }
+    key
+}
+
+/// Word 3-shingles, or the words themselves for chunks too short to have any
+fn shingles(text: &str) -> HashSet<String> {
+    let words = words(text);
+    if words.len() < 3 {
+        return words.into_iter().collect();
+    }
+    words.windows(3).map(|window| window.join(" ")).collect()
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f32 / union as f32
+}
+
+fn find_root(parents: &mut HashMap<i64, i64>, id: i64) -> i64 {
+    let parent = *parents.get(&id).unwrap_or(&id);
+    if parent == id {
+        return id;
+    }
+    let root = find_root(parents, parent);
+    parents.insert(id, root);
+    root
+}
+
+fn load_chunks(conn: &Connection) -> Result<Vec<StoredChunk>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT e.id, e.doc_id, d.name, e.chunk, COALESCE(d.paused, 0)
+        FROM embeddings e
+        JOIN documents d ON d.id = e.doc_id
+        ORDER BY e.id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let text: String = row.get(3)?;
+        Ok(StoredChunk {
+            chunk: HygieneChunk {
+                chunk_id: row.get(0)?,
+                doc_id: row.get(1)?,
+                doc_name: row.get(2)?,
+                preview: preview(&text),
+                fingerprint: fingerprint(&text),
+            },
+            text,
+            paused: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// One minimum per hash function over the chunk's shingles; chunks agree on each
+/// value with probability equal to their shingles' Jaccard similarity
+fn minhash(text: &str) -> Vec<u64> {
+    let hashes: Vec<u64> = shingles(text)
+    .iter()
+    .map(|shingle| {
+        let mut hasher = DefaultHasher::new();
+        shingle.hash(&mut hasher);
+        hasher.finish()
+    })
+    .collect();
+    (0..MINHASH_BANDS * BAND_ROWS)
+    .map(|i| {
+        let seed = splitmix64(i as u64);
+        hashes.iter().map(|hash| splitmix64(hash ^ seed)).min().unwrap_or(u64::MAX)
+    })
+    .collect()
+}
+
+/// Pairs of chunks, lower id first, that share at least one band of their signatures
+fn candidate_pairs(signatures: &[(i64, Vec<u64>)]) -> HashSet<(i64, i64)> {
+    let mut pairs = HashSet::new();
+    for band in 0..MINHASH_BANDS {
+        let mut buckets: HashMap<&[u64], Vec<i64>> = HashMap::new();
+        for (chunk_id, signature) in signatures {
+            buckets.entry(&signature[band * BAND_ROWS..(band + 1) * BAND_ROWS]).or_default().push(*chunk_id);
+        }
+        for members in buckets.values().filter(|members| members.len() > 1) {
+            if members.len() <= MAX_BUCKET_PAIRS {
+                for (i, a) in members.iter().enumerate() {
+                    pairs.extend(members[i + 1..].iter().map(|b| (*a, *b)));
+                }
+            } else {
+                // Chunks are in id order, so the first is the oldest, which is the one kept
+                pairs.extend(members[1..].iter().map(|b| (members[0], *b)));
+            }
+        }
+    }
+    pairs
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|y| y * y).sum::<f32>().sqrt();
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+/// Chunk vectors looked up by rowid in each of the canon's indexes, as pairs need them
+struct VectorLookup {
+    tables: Vec<String>,
+    cache: HashMap<(usize, i64), Option<Vec<f32>>>,
+}
+
+impl VectorLookup {
+    fn new(conn: &Connection) -> Result<Self, rusqlite::Error> {
+        let tables = DocumentStore::vector_indexes(conn)?.into_iter().map(|index| index.table_name).collect();
+        Ok(Self { tables, cache: HashMap::new() })
+    }
+
+    fn vector(&mut self, conn: &Connection, table: usize, chunk_id: i64) -> Result<Option<Vec<f32>>, rusqlite::Error> {
+        if let Some(vector) = self.cache.get(&(table, chunk_id)) {
+            return Ok(vector.clone());
+        }
+        let mut stmt = conn.prepare_cached(&format!("SELECT embedding FROM {} WHERE rowid = ?1", self.tables[table]))?;
+        let vector = stmt
+        .query_row(params![chunk_id], |row| row.get::<_, Vec<u8>>(0))
+        .optional()?
+        .map(|blob| blob_to_vector(&blob));
+        self.cache.insert((table, chunk_id), vector.clone());
+        Ok(vector)
+    }
+
+    /// Whether the two chunks' vectors are close in any index holding both
+    fn close(&mut self, conn: &Connection, a: i64, b: i64, min_similarity: f32) -> Result<bool, rusqlite::Error> {
+        for table in 0..self.tables.len() {
+            if let (Some(va), Some(vb)) = (self.vector(conn, table, a)?, self.vector(conn, table, b)?) {
+                if cosine_similarity(&va, &vb) >= min_similarity {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Analyses the whole canon, paused documents included. Their chunks are only ever
+/// removed as repeats of a copy that stays searchable.
+pub(crate) fn analyze(conn: &Connection, options: &HygieneOptions) -> Result<HygieneReport, rusqlite::Error> {
+    let chunks = load_chunks(conn)?;
+    let mut report = HygieneReport { chunks_scanned: chunks.len(), ..Default::default() };
+
+    // Empty chunks first, then boilerplate among what's left; neither is also
+    // reported as a duplicate
+    let mut flagged: HashSet<i64> = HashSet::new();
+    let mut boilerplate_groups: HashMap<String, Vec<&StoredChunk>> = HashMap::new();
+    for stored in &chunks {
+        let word_count = words(&stored.text).len();
+        if word_count < options.min_chunk_words {
+            report.empty_chunks.push(stored.chunk.clone());
+            flagged.insert(stored.chunk.chunk_id);
+        } else if word_count <= options.boilerplate_max_words {
+            boilerplate_groups.entry(boilerplate_key(&stored.text)).or_default().push(stored);
+        }
+    }
+    let mut boilerplate: Vec<BoilerplateGroup> = boilerplate_groups
+    .into_values()
+    .filter(|group| group.len() >= options.boilerplate_min_repeats.max(2))
+    .map(|group| {
+        flagged.extend(group.iter().map(|stored| stored.chunk.chunk_id));
+        let keep = survivor(&group);
+        BoilerplateGroup {
+            text: group[keep].chunk.preview.clone(),
+            keep: group[keep].chunk.clone(),
+            repeats: group
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != keep)
+            .map(|(_, stored)| stored.chunk.clone())
+            .collect(),
+        }
+    })
+    .collect();
+    boilerplate.sort_by(|a, b| b.repeats.len().cmp(&a.repeats.len()).then(a.text.cmp(&b.text)));
+    report.boilerplate = boilerplate;
+
+    let by_id: HashMap<i64, &StoredChunk> = chunks.iter().map(|stored| (stored.chunk.chunk_id, stored)).collect();
+    let signatures: Vec<(i64, Vec<u64>)> = chunks
+    .iter()
+    .filter(|stored| !flagged.contains(&stored.chunk.chunk_id))
+    .map(|stored| (stored.chunk.chunk_id, minhash(&stored.text)))
+    .collect();
+    let mut pairs: Vec<(i64, i64)> = candidate_pairs(&signatures).into_iter().collect();
+    pairs.sort_unstable();
+
+    let mut vectors = VectorLookup::new(conn)?;
+    let mut shingle_cache: HashMap<i64, HashSet<String>> = HashMap::new();
+    let mut parents: HashMap<i64, i64> = HashMap::new();
+    for (a, b) in pairs {
+        for chunk_id in [a, b] {
+            shingle_cache.entry(chunk_id).or_insert_with(|| shingles(&by_id[&chunk_id].text));
+        }
+        if jaccard(&shingle_cache[&a], &shingle_cache[&b]) < options.min_shingle_overlap {
+            continue;
+        }
+        if vectors.close(conn, a, b, options.min_similarity)? {
+            let (root_a, root_b) = (find_root(&mut parents, a), find_root(&mut parents, b));
+            if root_a != root_b {
+                // The oldest chunk is the root
+                parents.insert(root_a.max(root_b), root_a.min(root_b));
+            }
+        }
+    }
+    let mut clusters: HashMap<i64, Vec<i64>> = HashMap::new();
+    for chunk_id in parents.keys().copied().collect::<Vec<_>>() {
+        let root = find_root(&mut parents, chunk_id);
+        if root != chunk_id {
+            clusters.entry(root).or_default().push(chunk_id);
+        }
+    }
+    let mut clusters: Vec<(i64, Vec<i64>)> = clusters.into_iter().collect();
+    clusters.sort_by_key(|(root, _)| *root);
+    report.duplicate_clusters = clusters
+    .into_iter()
+    .map(|(root, duplicates)| {
+        let mut members: Vec<&StoredChunk> = std::iter::once(root).chain(duplicates).map(|chunk_id| by_id[&chunk_id]).collect();
+        members.sort_by_key(|stored| stored.chunk.chunk_id);
+        let keep = members.remove(survivor(&members));
+        DuplicateCluster {
+            keep: keep.chunk.clone(),
+            duplicates: members.iter().map(|stored| stored.chunk.clone()).collect(),
+        }
+    })
+    .collect();
+
+    let mut stmt = conn.prepare(
+        "SELECT d.id, d.name, d.file_path FROM documents d
+        WHERE NOT EXISTS (SELECT 1 FROM embeddings e WHERE e.doc_id = d.id)
+        AND NOT EXISTS (SELECT 1 FROM jobs j WHERE j.doc_id = d.id AND j.status IN ('queued', 'running'))
+        ORDER BY d.id",
+    )?;
+    let documents = stmt.query_map([], |row| {
+        Ok(EmptyDocument { id: row.get(0)?, name: row.get(1)?, file_path: row.get(2)? })
+    })?;
+    report.documents_without_embeddings = documents.collect::<Result<_, _>>()?;
+    Ok(report)
+}
+
+/// Whether a chunk still holds the text it was analysed with
+fn unchanged(conn: &Connection, chunk: &HygieneChunk) -> Result<bool, rusqlite::Error> {
+    let text: Option<String> = conn
+    .query_row("SELECT chunk FROM embeddings WHERE id = ?1", params![chunk.chunk_id], |row| row.get(0))
+    .optional()?;
+    Ok(text.map_or(false, |text| fingerprint(&text) == chunk.fingerprint))
+}
+
+/// The repeats whose kept copy is still there, leaving out any changed since analysis
+fn removable<'a>(conn: &Connection, keep: &HygieneChunk, repeats: &'a [HygieneChunk]) -> Result<Vec<&'a HygieneChunk>, rusqlite::Error> {
+    if !unchanged(conn, keep)? {
+        return Ok(Vec::new());
+    }
+    let mut removable = Vec::new();
+    for chunk in repeats {
+        if unchanged(conn, chunk)? {
+            removable.push(chunk);
+        }
+    }
+    Ok(removable)
+}
+
+/// Removes what `report` found, as chosen by `cleanup`. The report may be older than
+/// the canon: chunks edited or removed since, and documents that have gained chunks,
+/// are left alone.
+pub(crate) fn apply_cleanup(
+    conn: &Connection,
+    report: &HygieneReport,
+    cleanup: &HygieneCleanup,
+) -> Result<HygieneCleanupReport, Box<dyn std::error::Error + Send + Sync>> {
+    let mut removed = HygieneCleanupReport::default();
+    let mut chunk_ids: Vec<&HygieneChunk> = Vec::new();
+    if cleanup.duplicates {
+        for cluster in &report.duplicate_clusters {
+            let duplicates = removable(conn, &cluster.keep, &cluster.duplicates)?;
+            removed.duplicates_removed += duplicates.len();
+            chunk_ids.extend(duplicates);
+        }
+    }
+    if cleanup.empty_chunks {
+        for chunk in &report.empty_chunks {
+            if unchanged(conn, chunk)? {
+                removed.empty_chunks_removed += 1;
+                chunk_ids.push(chunk);
+            }
+        }
+    }
+    if cleanup.boilerplate {
+        for group in &report.boilerplate {
+            let repeats = removable(conn, &group.keep, &group.repeats)?;
+            removed.boilerplate_removed += repeats.len();
+            chunk_ids.extend(repeats);
+        }
+    }
+
+    let doc_ids: HashSet<i64> = chunk_ids.iter().map(|chunk| chunk.doc_id).collect();
+    let ids: Vec<i64> = chunk_ids.iter().map(|chunk| chunk.chunk_id).collect();
+    DocumentStore::delete_chunks(conn, &ids).map_err(|e| e.to_string())?;
+    for doc_id in doc_ids {
+        chunk_editor::renumber_chunks(conn, doc_id)?;
+    }
+
+    if cleanup.empty_documents {
+        for document in &report.documents_without_embeddings {
+            let still_empty: bool = conn.query_row(
+                "SELECT NOT EXISTS (SELECT 1 FROM embeddings WHERE doc_id = ?1)
+                AND NOT EXISTS (SELECT 1 FROM jobs WHERE doc_id = ?1 AND status IN ('queued', 'running'))",
+                params![document.id],
+                |row| row.get(0),
+            )?;
+            if still_empty {
+                DocumentStore::delete_document_rows(conn, document.id)?;
+                removed.documents_removed += 1;
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// Analyses the canon at `db_path` on a connection of its own, off the async runtime,
+/// so a long analysis doesn't hold up the store
+async fn analyze_in_background(db_path: PathBuf, options: HygieneOptions) -> Result<HygieneReport, Box<dyn std::error::Error + Send + Sync>> {
+    let report = tokio::task::spawn_blocking(move || -> Result<HygieneReport, rusqlite::Error> {
+        let conn = DocumentStore::open_side_connection(&db_path)?;
+        analyze(&conn, &options)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    Ok(report)
+}
+
+impl DocumentStore {
+    /// Reports duplicate clusters, empty and boilerplate chunks, and documents without chunks
+    pub async fn hygiene_report(&self, options: &HygieneOptions) -> Result<HygieneReport, Box<dyn std::error::Error + Send + Sync>> {
+        analyze_in_background(PathBuf::from(self.get_database_path()), options.clone()).await
+    }
+
+    /// Analyses the canon, then removes what was found in one transaction. Only the
+    /// removal holds the store.
+    pub async fn clean_canon(
+        &self,
+        options: &HygieneOptions,
+        cleanup: &HygieneCleanup,
+    ) -> Result<HygieneCleanupReport, Box<dyn std::error::Error + Send + Sync>> {
+        let report = analyze_in_background(PathBuf::from(self.get_database_path()), options.clone()).await?;
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        let removed = apply_cleanup(&tx, &report, cleanup)?;
+        tx.commit()?;
+        log::info!(
+            "Canon cleanup removed {} duplicate, {} empty and {} boilerplate chunks and {} empty documents",
+            removed.duplicates_removed, removed.empty_chunks_removed, removed.boilerplate_removed, removed.documents_removed
+        );
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document_store::insert_test_document;
+    use crate::chunking::ChunkProvenance;
+
+    fn add_chunk(conn: &Connection, doc_id: i64, index: usize, text: &str, vector: &[f32]) -> i64 {
+        let id = DocumentStore::insert_chunk_embedding(conn, doc_id, text, vector, "test-model").unwrap();
+        DocumentStore::set_chunk_position(conn, id, index, text, &ChunkProvenance::default()).unwrap();
+        id
+    }
+
+    #[test]
+    fn test_report_finds_duplicates_junk_and_empty_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DocumentStore::new(dir.path().join("hygiene.canon")).unwrap();
+        let mut conn = store.conn.blocking_lock();
+        for (id, name) in [(1, "digest-1"), (2, "digest-2"), (3, "stalled"), (4, "queued")] {
+            insert_test_document(&conn, id, name, "test-model");
+        }
+        conn.execute(
+            "INSERT INTO jobs (source, source_kind, doc_id, status, created_at, updated_at) VALUES ('queued', 'file', 4, 'queued', 'now', 'now')",
+            [],
+        ).unwrap();
+
+        let story = "The city council voted on Tuesday to close the harbour road for repairs through the winter.";
+        let reworded = "The city council voted on Tuesday to close the harbour road for repairs through the spring.";
+        let kept = add_chunk(&conn, 1, 0, story, &[1.0, 0.0, 0.0]);
+        let footer = add_chunk(&conn, 1, 1, "Unsubscribe | Page 1", &[0.0, 1.0, 0.0]);
+        let duplicate = add_chunk(&conn, 2, 0, reworded, &[0.99, 0.01, 0.0]);
+        // Close in vector space but different words
+        let different = add_chunk(&conn, 2, 1, "A gun in the drawer and rain on the window all night long.", &[0.98, 0.02, 0.0]);
+        add_chunk(&conn, 2, 2, "Unsubscribe | Page 2", &[0.0, 1.0, 0.0]);
+        add_chunk(&conn, 2, 3, "Unsubscribe | Page 3", &[0.0, 1.0, 0.0]);
+        let empty = add_chunk(&conn, 2, 4, "--", &[0.0, 0.0, 1.0]);
+
+        let report = analyze(&conn, &HygieneOptions::default()).unwrap();
+        assert_eq!(report.chunks_scanned, 7);
+        assert_eq!(report.duplicate_clusters.len(), 1);
+        assert_eq!(report.duplicate_clusters[0].keep.chunk_id, kept);
+        assert_eq!(report.duplicate_clusters[0].duplicates.iter().map(|chunk| chunk.chunk_id).collect::<Vec<_>>(), vec![duplicate]);
+        assert_eq!(report.empty_chunks.iter().map(|chunk| chunk.chunk_id).collect::<Vec<_>>(), vec![empty]);
+        assert_eq!(report.boilerplate.len(), 1);
+        assert_eq!(report.boilerplate[0].keep.chunk_id, footer);
+        assert_eq!(report.boilerplate[0].repeats.len(), 2);
+        assert_eq!(report.documents_without_embeddings.iter().map(|document| document.id).collect::<Vec<_>>(), vec![3]);
+
+        let tx = conn.transaction().unwrap();
+        let removed = apply_cleanup(&tx, &report, &HygieneCleanup::default()).unwrap();
+        tx.commit().unwrap();
+        assert_eq!((removed.duplicates_removed, removed.empty_chunks_removed, removed.boilerplate_removed, removed.documents_removed), (1, 1, 2, 1));
+        let remaining: Vec<i64> = conn
+        .prepare("SELECT id FROM embeddings ORDER BY id")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+        // One copy of the boilerplate survives
+        assert_eq!(remaining, vec![kept, footer, different]);
+        let different_index: i64 = conn.query_row("SELECT chunk_index FROM embeddings WHERE id = ?1", params![different], |row| row.get(0)).unwrap();
+        assert_eq!(different_index, 0);
+    }
+
+    #[test]
+    fn test_clean_canon_keeps_one_copy_and_skips_chunks_changed_since_analysis() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let store = DocumentStore::new(dir.path().join("hygiene.canon")).unwrap();
+        let (first, edited) = {
+            let conn = store.conn.blocking_lock();
+            insert_test_document(&conn, 1, "digest", "test-model");
+            let first = add_chunk(&conn, 1, 0, "Thanks for reading, see you next week", &[0.0, 1.0, 0.0]);
+            add_chunk(&conn, 1, 1, "Thanks for reading, see you next week", &[0.0, 1.0, 0.0]);
+            let edited = add_chunk(&conn, 1, 2, "Thanks for reading, see you next week", &[0.0, 1.0, 0.0]);
+            (first, edited)
+        };
+
+        let report = runtime.block_on(store.hygiene_report(&HygieneOptions::default())).unwrap();
+        assert_eq!(report.boilerplate[0].keep.chunk_id, first);
+        assert_eq!(report.boilerplate[0].repeats.len(), 2);
+
+        let conn = store.conn.blocking_lock();
+        conn.execute("UPDATE embeddings SET chunk = 'Thanks for reading! Reply with questions.' WHERE id = ?1", params![edited]).unwrap();
+        let removed = apply_cleanup(&conn, &report, &HygieneCleanup::default()).unwrap();
+        assert_eq!(removed.boilerplate_removed, 1);
+        let remaining: Vec<i64> = conn
+        .prepare("SELECT id FROM embeddings ORDER BY id")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+        assert_eq!(remaining, vec![first, edited]);
+        drop(conn);
+
+        // With the analysis off the store's connection, the whole cleanup runs end to end
+        let removed = runtime.block_on(store.clean_canon(&HygieneOptions::default(), &HygieneCleanup::default())).unwrap();
+        assert_eq!(removed.boilerplate_removed, 0);
+    }
+
+    #[test]
+    fn test_duplicates_keep_the_copy_in_an_active_document() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DocumentStore::new(dir.path().join("hygiene.canon")).unwrap();
+        let conn = store.conn.blocking_lock();
+        insert_test_document(&conn, 1, "digest-1", "test-model");
+        insert_test_document(&conn, 2, "digest-2", "test-model");
+        conn.execute("UPDATE documents SET paused = 1 WHERE id = 1", []).unwrap();
+
+        let story = "The city council voted on Tuesday to close the harbour road for repairs through the winter.";
+        let reworded = "The city council voted on Tuesday to close the harbour road for repairs through the spring.";
+        let paused_copy = add_chunk(&conn, 1, 0, story, &[1.0, 0.0, 0.0]);
+        let active_copy = add_chunk(&conn, 2, 0, reworded, &[0.99, 0.01, 0.0]);
+
+        let report = analyze(&conn, &HygieneOptions::default()).unwrap();
+        assert_eq!(report.duplicate_clusters.len(), 1);
+        assert_eq!(report.duplicate_clusters[0].keep.chunk_id, active_copy);
+        assert_eq!(report.duplicate_clusters[0].duplicates.iter().map(|chunk| chunk.chunk_id).collect::<Vec<_>>(), vec![paused_copy]);
+
+        let removed = apply_cleanup(&conn, &report, &HygieneCleanup::default()).unwrap();
+        assert_eq!(removed.duplicates_removed, 1);
+        let remaining: Vec<i64> = conn
+        .prepare("SELECT id FROM embeddings ORDER BY id")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+        assert_eq!(remaining, vec![active_copy]);
+    }
+
+    #[test]
+    fn test_minhash_candidates_pair_similar_text_only() {
+        let story = "The city council voted on Tuesday to close the harbour road for repairs through the winter.";
+        let reworded = "The city council voted on Tuesday to close the harbour road for repairs through the spring.";
+        let unrelated = "A gun in the drawer and rain on the window all night long in Laurel Canyon.";
+        let signatures = vec![(1, minhash(story)), (2, minhash(reworded)), (3, minhash(unrelated))];
+        let pairs = candidate_pairs(&signatures);
+        assert!(pairs.contains(&(1, 2)));
+        assert!(!pairs.contains(&(1, 3)) && !pairs.contains(&(2, 3)));
+    }
+
+    #[test]
+    fn test_boilerplate_key_ignores_numbers_and_case() {
+        assert_eq!(boilerplate_key("THE BIG SLEEP  12"), boilerplate_key("The Big Sleep 137"));
+        assert_ne!(boilerplate_key("Chapter one"), boilerplate_key("Chapter two"));
+    }
+}
@@ -0,0 +1,591 @@
+#![allow(dead_code)]
+// src/canon_ops.rs
+//
+// Operations across canon files: merging another canon into this one, splitting a
+// subset of documents out into a new canon, and copying documents between canons.
+// Documents travel with their chunks and every vector they have, so nothing is
+// re-embedded; documents whose model differs from the receiving canon's are reported
+// so they can be re-embedded there.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use chrono::DateTime;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::collections;
+use crate::document_metadata;
+use crate::document_store::{blob_to_vector, DocumentStore};
+use crate::migrations;
+
+/// Columns copied with a document, everything but its id
+const DOCUMENT_COLUMNS: [&str; 15] = [
+    "name", "title", "authors", "created_at", "file_path", "paused", "embedding_model_name",
+    "notes", "chunking_strategy", "chunking_params", "content_hash", "source_modified_at",
+    "retrieval_weight", "pinned", "extraction_version",
+];
+/// Columns copied with a chunk, everything but its id and document
+const CHUNK_COLUMNS: [&str; 8] = [
+    "chunk", "embedding_model_name", "content_hash", "chunk_index", "start_offset", "end_offset", "page", "section",
+];
+
+/// What to do when the receiving canon already has a document at the same path (for the same model)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathConflict {
+    #[default]
+    KeepExisting,
+    KeepIncoming,
+    /// Keep whichever was modified (or failing that, added) more recently
+    KeepNewer,
+}
+
+/// Documents picked by id, author or tag; a document matching any of them is selected
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DocumentSelection {
+    #[serde(default)]
+    pub doc_ids: Vec<i64>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CanonTransferReport {
+    pub documents_copied: usize,
+    pub chunks_copied: usize,
+    pub vectors_copied: usize,
+    /// Same content for the same model already in the receiving canon
+    pub duplicates_skipped: usize,
+    /// Path conflicts resolved in favour of the document already there
+    pub conflicts_kept_existing: usize,
+    /// Path conflicts resolved by replacing the document already there
+    pub conflicts_replaced: usize,
+    /// Copied documents (ids in the receiving canon) with no vectors for its embedding model
+    pub needs_reembedding: Vec<i64>,
+    /// Documents removed from the source canon after a split
+    pub removed_from_source: usize,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CanonTransferError {
+    #[error("Source and target are the same canon")]
+    SameCanon,
+    #[error("{0} already exists; split into a new canon file")]
+    TargetExists(String),
+    #[error("No documents match the selection")]
+    EmptySelection,
+}
+
+/// A document read from the source canon, ready to insert elsewhere
+struct DocumentRow {
+    values: Vec<Value>,
+    file_path: String,
+    embedding_model_name: String,
+    content_hash: Option<String>,
+    /// When the content last changed, for resolving conflicts by recency
+    changed_at: String,
+}
+
+fn read_document_row(conn: &Connection, doc_id: i64) -> rusqlite::Result<Option<DocumentRow>> {
+    conn.query_row(
+        &format!("SELECT {} FROM documents WHERE id = ?1", DOCUMENT_COLUMNS.join(", ")),
+        params![doc_id],
+        |row| {
+            let values = (0..DOCUMENT_COLUMNS.len())
+            .map(|i| row.get::<_, Value>(i))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+            let created_at: String = row.get(3)?;
+            let source_modified_at: Option<String> = row.get(11)?;
+            Ok(DocumentRow {
+                file_path: row.get(4)?,
+                embedding_model_name: row.get::<_, Option<String>>(6)?.unwrap_or_else(|| "unknown".to_string()),
+                content_hash: row.get(10)?,
+                changed_at: source_modified_at.unwrap_or(created_at),
+                values,
+            })
+        },
+    )
+    .optional()
+}
+
+fn changed_at_of(conn: &Connection, doc_id: i64) -> rusqlite::Result<String> {
+    conn.query_row(
+        "SELECT COALESCE(source_modified_at, created_at) FROM documents WHERE id = ?1",
+        params![doc_id],
+        |row| row.get(0),
+    )
+}
+
+fn is_newer(incoming: &str, existing: &str) -> bool {
+    match (DateTime::parse_from_rfc3339(incoming), DateTime::parse_from_rfc3339(existing)) {
+        (Ok(incoming), Ok(existing)) => incoming > existing,
+        _ => incoming > existing,
+    }
+}
+
+fn placeholders(count: usize) -> String {
+    (1..=count).map(|i| format!("?{}", i)).collect::<Vec<_>>().join(", ")
+}
+
+/// The embedding model a canon has recorded for itself, if any
+fn recorded_canon_model(conn: &Connection) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT embedding_model_name FROM canon WHERE embedding_model_name IS NOT NULL ORDER BY id LIMIT 1",
+        [],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Removes a canon a failed split created, folder or file with SQLite's side files
+fn remove_created_canon(path: &Path) {
+    let removed = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        for suffix in ["-journal", "-wal", "-shm"] {
+            let mut side_file = path.as_os_str().to_owned();
+            side_file.push(suffix);
+            let _ = std::fs::remove_file(side_file);
+        }
+        std::fs::remove_file(path)
+    };
+    if let Err(e) = removed {
+        log::warn!("Couldn't remove {} after the failed split: {}", path.display(), e);
+    }
+}
+
+impl DocumentStore {
+    /// Copies documents from `source` into `target` with their chunks and vectors.
+    /// Runs against a transaction on the target so a failed copy leaves it untouched.
+    fn transfer_documents(
+        source: &Connection,
+        target: &Connection,
+        target_canon_name: &str,
+        doc_ids: &[i64],
+        on_conflict: PathConflict,
+        report: &mut CanonTransferReport,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let source_indexes = Self::vector_indexes(source)?;
+        let target_model = recorded_canon_model(target)?;
+
+        for &doc_id in doc_ids {
+            let document = match read_document_row(source, doc_id)? {
+                Some(document) => document,
+                None => {
+                    log::warn!("Document {} isn't in the source canon; skipping it", doc_id);
+                    continue;
+                }
+            };
+
+            if let Some(hash) = &document.content_hash {
+                let duplicate: bool = target.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM documents WHERE content_hash = ?1 AND embedding_model_name = ?2)",
+                    params![hash, document.embedding_model_name],
+                    |row| row.get(0),
+                )?;
+                if duplicate {
+                    report.duplicates_skipped += 1;
+                    continue;
+                }
+            }
+
+            let existing: Option<i64> = target
+            .query_row(
+                "SELECT id FROM documents WHERE file_path = ?1 AND embedding_model_name = ?2",
+                params![document.file_path, document.embedding_model_name],
+                |row| row.get(0),
+            )
+            .optional()?;
+            if let Some(existing) = existing {
+                let replace = match on_conflict {
+                    PathConflict::KeepExisting => false,
+                    PathConflict::KeepIncoming => true,
+                    PathConflict::KeepNewer => is_newer(&document.changed_at, &changed_at_of(target, existing)?),
+                };
+                if !replace {
+                    report.conflicts_kept_existing += 1;
+                    continue;
+                }
+                Self::delete_document_rows(target, existing)?;
+                report.conflicts_replaced += 1;
+            }
+
+            target.execute(
+                &format!(
+                    "INSERT INTO documents ({}) VALUES ({})",
+                    DOCUMENT_COLUMNS.join(", "),
+                    placeholders(DOCUMENT_COLUMNS.len())
+                ),
+                params_from_iter(document.values.iter()),
+            )?;
+            let new_doc_id = target.last_insert_rowid();
+            collections::tag_document(target, new_doc_id, &collections::tags_of(source, doc_id)?)?;
+            if let Some(metadata) = document_metadata::metadata_of(source, doc_id)? {
+                document_metadata::set_metadata(target, new_doc_id, &metadata)?;
+            }
+            report.documents_copied += 1;
+
+            // Source chunk id -> chunk id in the target
+            let mut chunk_ids = std::collections::HashMap::new();
+            {
+                let mut stmt = source.prepare(&format!(
+                    "SELECT id, {} FROM embeddings WHERE doc_id = ?1 ORDER BY chunk_index, id",
+                    CHUNK_COLUMNS.join(", ")
+                ))?;
+                let mut rows = stmt.query(params![doc_id])?;
+                while let Some(row) = rows.next()? {
+                    let mut values: Vec<Value> = vec![Value::Integer(new_doc_id)];
+                    for i in 0..CHUNK_COLUMNS.len() {
+                        values.push(row.get(i + 1)?);
+                    }
+                    target.execute(
+                        &format!(
+                            "INSERT INTO embeddings (doc_id, {}) VALUES ({})",
+                            CHUNK_COLUMNS.join(", "),
+                            placeholders(CHUNK_COLUMNS.len() + 1)
+                        ),
+                        params_from_iter(values.iter()),
+                    )?;
+                    chunk_ids.insert(row.get::<_, i64>(0)?, target.last_insert_rowid());
+                }
+            }
+            report.chunks_copied += chunk_ids.len();
+
+            let mut models_copied = HashSet::new();
+            for index in &source_indexes {
+                let mut stmt = source.prepare(&format!(
+                    "SELECT v.rowid, v.embedding FROM {} v JOIN embeddings e ON e.id = v.rowid WHERE e.doc_id = ?1",
+                    index.table_name
+                ))?;
+                let mut rows = stmt.query(params![doc_id])?;
+                while let Some(row) = rows.next()? {
+                    let chunk_id = match chunk_ids.get(&row.get::<_, i64>(0)?) {
+                        Some(chunk_id) => *chunk_id,
+                        None => continue,
+                    };
+                    let blob: Vec<u8> = row.get(1)?;
+                    Self::insert_vector(target, chunk_id, &blob_to_vector(&blob), &index.embedding_model_name)?;
+                    report.vectors_copied += 1;
+                    models_copied.insert(index.embedding_model_name.clone());
+                }
+                if models_copied.contains(&index.embedding_model_name) {
+                    if let Some(provider) = &index.provider {
+                        Self::record_index_provider(target, &index.embedding_model_name, provider)?;
+                    }
+                }
+            }
+
+            let model = target_model.as_ref().unwrap_or(&document.embedding_model_name);
+            if !chunk_ids.is_empty() && !models_copied.contains(model) {
+                report.needs_reembedding.push(new_doc_id);
+            }
+        }
+
+        if target_model.is_none() {
+            if let Some(model) = recorded_canon_model(source)? {
+                Self::record_canon_embedding_model(target, target_canon_name, &model)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_same_canon(&self, other: &DocumentStore) -> bool {
+        match (
+            std::fs::canonicalize(other.get_database_path()),
+            std::fs::canonicalize(self.get_database_path()),
+        ) {
+            (Ok(other_path), Ok(this_path)) => other_path == this_path,
+            _ => other.get_database_path() == self.get_database_path(),
+        }
+    }
+
+    /// Opens another canon file to write to, refusing this canon itself. A path that
+    /// doesn't exist yet is created as a canon file rather than a folder, so
+    /// `chapters.canon` ends up as the file the user named.
+    fn open_other_canon(&self, path: &Path) -> Result<DocumentStore, Box<dyn std::error::Error + Send + Sync>> {
+        if !path.exists() && path.extension().is_some() {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::File::create(path)?;
+        }
+        let other = DocumentStore::new(path.to_path_buf()).map_err(|e| e.to_string())?;
+        if self.is_same_canon(&other) {
+            return Err(CanonTransferError::SameCanon.into());
+        }
+        Ok(other)
+    }
+
+    /// Opens another canon file read-only to copy from, refusing this canon itself.
+    /// Nothing is migrated, so the canon must already be at this build's schema.
+    fn open_source_canon(&self, path: &Path) -> Result<DocumentStore, Box<dyn std::error::Error + Send + Sync>> {
+        let other = DocumentStore::open_read_only(path.to_path_buf()).map_err(|e| e.to_string())?;
+        if self.is_same_canon(&other) {
+            return Err(CanonTransferError::SameCanon.into());
+        }
+        Ok(other)
+    }
+
+    /// Ids of the documents a selection picks out, in id order
+    pub async fn select_documents(&self, selection: &DocumentSelection) -> Result<Vec<i64>, Box<dyn std::error::Error + Send + Sync>> {
+        let wanted_authors: HashSet<String> = selection.authors.iter().map(|author| author.trim().to_lowercase()).collect();
+        let conn = self.conn.lock().await;
+        let tagged: HashSet<i64> = collections::documents_tagged(&conn, &selection.tags)?.into_iter().collect();
+        let mut stmt = conn.prepare("SELECT id, authors FROM documents ORDER BY id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?)))?;
+
+        let mut doc_ids = Vec::new();
+        for row in rows {
+            let (doc_id, authors) = row?;
+            let by_author = authors
+            .and_then(|authors| serde_json::from_str::<Vec<String>>(&authors).ok())
+            .unwrap_or_default()
+            .iter()
+            .any(|author| wanted_authors.contains(&author.trim().to_lowercase()));
+            if by_author || tagged.contains(&doc_id) || selection.doc_ids.contains(&doc_id) {
+                doc_ids.push(doc_id);
+            }
+        }
+        Ok(doc_ids)
+    }
+
+    /// Merges every document of the canon at `source_path` into this one
+    pub async fn merge_canon(
+        &self,
+        source_path: &Path,
+        on_conflict: PathConflict,
+    ) -> Result<CanonTransferReport, Box<dyn std::error::Error + Send + Sync>> {
+        if !source_path.exists() {
+            return Err(format!("{} doesn't exist", source_path.display()).into());
+        }
+        let source = self.open_source_canon(source_path)?;
+        let source_conn = source.conn.lock().await;
+        migrations::check_current(&source_conn).map_err(|e| e.to_string())?;
+        let doc_ids: Vec<i64> = {
+            let mut stmt = source_conn.prepare("SELECT id FROM documents ORDER BY id")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        let mut report = CanonTransferReport::default();
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        Self::transfer_documents(&source_conn, &tx, self.get_database_name(), &doc_ids, on_conflict, &mut report)
+        .map_err(|e| e.to_string())?;
+        tx.commit()?;
+
+        log::info!(
+            "Merged {} documents from {} ({} duplicates, {} conflicts kept, {} replaced)",
+            report.documents_copied, source_path.display(), report.duplicates_skipped,
+            report.conflicts_kept_existing, report.conflicts_replaced
+        );
+        Ok(report)
+    }
+
+    /// Copies documents from this canon into the canon at `target_path`, creating it if needed
+    pub async fn copy_documents_to(
+        &self,
+        doc_ids: &[i64],
+        target_path: &Path,
+        on_conflict: PathConflict,
+    ) -> Result<CanonTransferReport, Box<dyn std::error::Error + Send + Sync>> {
+        let target = self.open_other_canon(target_path)?;
+        let conn = self.conn.lock().await;
+        let mut report = CanonTransferReport::default();
+        let mut target_conn = target.conn.lock().await;
+        let tx = target_conn.transaction()?;
+        Self::transfer_documents(&conn, &tx, target.get_database_name(), doc_ids, on_conflict, &mut report)
+        .map_err(|e| e.to_string())?;
+        tx.commit()?;
+
+        log::info!("Copied {} documents to {}", report.documents_copied, target_path.display());
+        Ok(report)
+    }
+
+    /// Splits the selected documents out into a new canon at `target_path`, optionally
+    /// removing them from this one once the copy has been committed
+    pub async fn split_canon(
+        &self,
+        selection: &DocumentSelection,
+        target_path: &Path,
+        remove_from_source: bool,
+    ) -> Result<CanonTransferReport, Box<dyn std::error::Error + Send + Sync>> {
+        if target_path.exists() {
+            return Err(CanonTransferError::TargetExists(target_path.display().to_string()).into());
+        }
+        let doc_ids = self.select_documents(selection).await?;
+        if doc_ids.is_empty() {
+            return Err(CanonTransferError::EmptySelection.into());
+        }
+
+        let mut report = match self.copy_documents_to(&doc_ids, target_path, PathConflict::KeepExisting).await {
+            Ok(report) => report,
+            Err(e) => {
+                remove_created_canon(target_path);
+                return Err(e);
+            }
+        };
+        if remove_from_source {
+            let mut conn = self.conn.lock().await;
+            let tx = conn.transaction()?;
+            for doc_id in &doc_ids {
+                Self::delete_document_rows(&tx, *doc_id)?;
+            }
+            tx.commit()?;
+            report.removed_from_source = doc_ids.len();
+        }
+
+        log::info!("Split {} documents out into {}", doc_ids.len(), target_path.display());
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document_store::insert_test_document;
+
+    fn add_document(store: &DocumentStore, id: i64, path: &str, authors: &str, modified_at: &str, chunk: &str, vector: &[f32]) {
+        let conn = store.conn.blocking_lock();
+        insert_test_document(&conn, id, path, "test-model");
+        conn.execute(
+            "UPDATE documents SET authors = ?2, content_hash = ?3, source_modified_at = ?4 WHERE id = ?1",
+            params![id, authors, crate::document_store::content_hash(chunk), modified_at],
+        ).unwrap();
+        DocumentStore::insert_chunk_embedding(&conn, id, chunk, vector, "test-model").unwrap();
+    }
+
+    #[test]
+    fn test_merge_resolves_paths_and_skips_duplicates() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let ours = DocumentStore::new(dir.path().join("ours.canon")).unwrap();
+        let theirs = DocumentStore::new(dir.path().join("theirs.canon")).unwrap();
+
+        add_document(&ours, 1, "/canon/shared.md", "[]", "2026-01-01T00:00:00+00:00", "old draft", &[1.0, 0.0, 0.0]);
+        add_document(&ours, 2, "/canon/same.md", "[]", "2026-01-01T00:00:00+00:00", "same words", &[0.0, 1.0, 0.0]);
+        add_document(&theirs, 1, "/canon/shared.md", "[]", "2026-02-01T00:00:00+00:00", "new draft", &[0.0, 0.0, 1.0]);
+        add_document(&theirs, 2, "/elsewhere/same.md", "[]", "2026-02-01T00:00:00+00:00", "same words", &[0.0, 1.0, 0.0]);
+        add_document(&theirs, 3, "/canon/theirs.md", "[]", "2026-02-01T00:00:00+00:00", "their chapter", &[0.6, 0.8, 0.0]);
+        drop(theirs);
+
+        let report = runtime
+        .block_on(ours.merge_canon(&dir.path().join("theirs.canon"), PathConflict::KeepNewer))
+        .unwrap();
+        assert_eq!((report.documents_copied, report.conflicts_replaced, report.duplicates_skipped), (2, 1, 1));
+        assert_eq!(report.vectors_copied, 2);
+        assert!(report.needs_reembedding.is_empty());
+
+        let conn = ours.conn.blocking_lock();
+        let results = DocumentStore::knn_search(&conn, "test-model", &[0.0, 0.0, 1.0], 1).unwrap();
+        assert_eq!(results[0].3, "new draft");
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM documents", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 3);
+        drop(conn);
+
+        assert!(runtime.block_on(ours.merge_canon(&dir.path().join("ours.canon"), PathConflict::KeepExisting)).is_err());
+    }
+
+    #[test]
+    fn test_split_by_author_moves_documents_to_a_new_canon() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let store = DocumentStore::new(dir.path().join("all.canon")).unwrap();
+        add_document(&store, 1, "/canon/marlowe.md", r#"["Raymond Chandler"]"#, "2026-01-01T00:00:00+00:00", "Marlowe", &[1.0, 0.0]);
+        add_document(&store, 2, "/canon/spade.md", r#"["Dashiell Hammett"]"#, "2026-01-01T00:00:00+00:00", "Spade", &[0.0, 1.0]);
+
+        let selection = DocumentSelection { authors: vec!["raymond chandler".to_string()], ..Default::default() };
+        let target = dir.path().join("chandler.canon");
+        let report = runtime.block_on(store.split_canon(&selection, &target, true)).unwrap();
+        assert_eq!((report.documents_copied, report.vectors_copied, report.removed_from_source), (1, 1, 1));
+
+        // A split never writes into an existing canon
+        assert!(runtime.block_on(store.split_canon(&selection, &target, false)).is_err());
+
+        let remaining = runtime.block_on(store.select_documents(&DocumentSelection { doc_ids: vec![1, 2], ..Default::default() })).unwrap();
+        assert_eq!(remaining, vec![2]);
+        let split = DocumentStore::new(target).unwrap();
+        let conn = split.conn.blocking_lock();
+        let results = DocumentStore::knn_search(&conn, "test-model", &[1.0, 0.0], 1).unwrap();
+        assert_eq!(results[0].3, "Marlowe");
+    }
+
+    #[test]
+    fn test_failed_split_leaves_no_canon_behind() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let store = DocumentStore::new(dir.path().join("all.canon")).unwrap();
+        add_document(&store, 1, "/canon/marlowe.md", "[]", "2026-01-01T00:00:00+00:00", "Marlowe", &[1.0, 0.0]);
+        {
+            // Vectors that can't be read fail the copy part way through
+            let conn = store.conn.blocking_lock();
+            let table_name = DocumentStore::vector_indexes(&conn).unwrap()[0].table_name.clone();
+            conn.execute_batch(&format!("DROP TABLE {}", table_name)).unwrap();
+        }
+
+        let selection = DocumentSelection { doc_ids: vec![1], ..Default::default() };
+        let target = dir.path().join("marlowe.canon");
+        assert!(runtime.block_on(store.split_canon(&selection, &target, true)).is_err());
+        assert!(!target.exists());
+        let remaining = runtime.block_on(store.select_documents(&selection)).unwrap();
+        assert_eq!(remaining, vec![1]);
+    }
+
+    #[test]
+    fn test_merge_reads_the_source_without_migrating_it() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let ours = DocumentStore::new(dir.path().join("ours.canon")).unwrap();
+        let older_path = dir.path().join("older.canon");
+        {
+            let mut conn = Connection::open(&older_path).unwrap();
+            migrations::migrate_to(&mut conn, 10).unwrap();
+        }
+
+        assert!(runtime.block_on(ours.merge_canon(&older_path, PathConflict::KeepExisting)).is_err());
+        let conn = Connection::open(&older_path).unwrap();
+        assert_eq!(migrations::current_version(&conn).unwrap(), 10);
+    }
+
+    #[test]
+    fn test_split_by_tag_keeps_the_tags() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let store = DocumentStore::new(dir.path().join("all.canon")).unwrap();
+        add_document(&store, 1, "/canon/marlowe.md", "[]", "2026-01-01T00:00:00+00:00", "Marlowe", &[1.0, 0.0]);
+        add_document(&store, 2, "/canon/spade.md", "[]", "2026-01-01T00:00:00+00:00", "Spade", &[0.0, 1.0]);
+        runtime.block_on(store.tag_documents(&[1], &["noir-reference".to_string(), "chandler".to_string()])).unwrap();
+
+        let selection = DocumentSelection { tags: vec!["noir-reference".to_string()], ..Default::default() };
+        let target = dir.path().join("noir.canon");
+        let report = runtime.block_on(store.split_canon(&selection, &target, false)).unwrap();
+        assert_eq!(report.documents_copied, 1);
+
+        let split = DocumentStore::new(target).unwrap();
+        let tags: Vec<String> = runtime.block_on(split.list_tags()).unwrap().into_iter().map(|tag| tag.name).collect();
+        assert_eq!(tags, vec!["chandler".to_string(), "noir-reference".to_string()]);
+    }
+
+    #[test]
+    fn test_copy_reports_documents_needing_the_target_model() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let source = DocumentStore::new(dir.path().join("source.canon")).unwrap();
+        add_document(&source, 1, "/canon/a.md", "[]", "2026-01-01T00:00:00+00:00", "a", &[1.0, 0.0]);
+
+        let target_path = dir.path().join("target.canon");
+        {
+            let target = DocumentStore::new(target_path.clone()).unwrap();
+            let conn = target.conn.blocking_lock();
+            DocumentStore::record_canon_embedding_model(&conn, "target.canon", "other-model").unwrap();
+        }
+
+        let report = runtime.block_on(source.copy_documents_to(&[1], &target_path, PathConflict::KeepExisting)).unwrap();
+        assert_eq!(report.documents_copied, 1);
+        assert_eq!(report.needs_reembedding.len(), 1);
+    }
+}
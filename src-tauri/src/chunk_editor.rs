@@ -0,0 +1,451 @@
+#![allow(dead_code)]
+// src/chunk_editor.rs
+//
+// Browsing and hand-editing a document's chunks: paging through them, finding text
+// within them, fixing a chunk's text, deleting junk such as running headers, and
+// splitting or merging neighbours. Only the chunks that change are re-embedded, with
+// the model they were embedded with. Edits last until the document's source changes
+// and it is re-ingested.
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+
+use crate::ai::providers::Provider;
+use crate::chunking::ChunkProvenance;
+use crate::document_store::{content_hash, DocumentStore};
+
+/// A chunk as shown in the chunk browser
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChunkView {
+    pub id: i64,
+    pub doc_id: i64,
+    /// Position within the document, from 0
+    pub index: usize,
+    pub content: String,
+    pub embedding_model_name: String,
+    pub provenance: ChunkProvenance,
+}
+
+/// One page of a document's chunks
+#[derive(Debug, Clone, Serialize)]
+pub struct ChunkPage {
+    pub doc_id: i64,
+    /// Chunks in the whole document
+    pub total: usize,
+    pub offset: usize,
+    pub chunks: Vec<ChunkView>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChunkEditError {
+    #[error("Chunk {0} not found")]
+    ChunkNotFound(i64),
+    #[error("Chunks can't be empty")]
+    EmptyChunk,
+    #[error("Splitting chunk {chunk_id} at {at} would leave an empty chunk")]
+    SplitOutOfRange { chunk_id: i64, at: usize },
+    #[error("Chunks {0} and {1} aren't neighbours in the same document")]
+    NotAdjacent(i64, i64),
+}
+
+const CHUNK_SELECT: &str = "SELECT id, doc_id, chunk, embedding_model_name, start_offset, end_offset, page, section FROM embeddings";
+
+/// Maps a `CHUNK_SELECT` row; the index is filled in by the caller
+fn chunk_from_row(row: &Row) -> Result<ChunkView, rusqlite::Error> {
+    Ok(ChunkView {
+        id: row.get(0)?,
+        doc_id: row.get(1)?,
+        index: 0,
+        content: row.get(2)?,
+        embedding_model_name: row.get::<_, Option<String>>(3)?.unwrap_or_else(|| "unknown".to_string()),
+        provenance: ChunkProvenance {
+            start_offset: row.get::<_, Option<i64>>(4)?.map(|offset| offset as usize),
+            end_offset: row.get::<_, Option<i64>>(5)?.map(|offset| offset as usize),
+            page: row.get(6)?,
+            section: row.get(7)?,
+        },
+    })
+}
+
+/// A document's chunk ids in reading order
+fn ordered_chunk_ids(conn: &Connection, doc_id: i64) -> Result<Vec<i64>, rusqlite::Error> {
+    let mut stmt = conn.prepare("SELECT id FROM embeddings WHERE doc_id = ?1 ORDER BY chunk_index, id")?;
+    let ids = stmt.query_map(params![doc_id], |row| row.get(0))?;
+    ids.collect()
+}
+
+fn chunk_view(conn: &Connection, chunk_id: i64) -> Result<ChunkView, Box<dyn std::error::Error + Send + Sync>> {
+    let mut chunk = conn
+    .query_row(&format!("{} WHERE id = ?1", CHUNK_SELECT), params![chunk_id], chunk_from_row)
+    .optional()?
+    .ok_or(ChunkEditError::ChunkNotFound(chunk_id))?;
+    chunk.index = ordered_chunk_ids(conn, chunk.doc_id)?.iter().position(|&id| id == chunk_id).unwrap_or(0);
+    Ok(chunk)
+}
+
+/// Numbers a document's chunks 0, 1, 2... in their current order
+pub(crate) fn renumber_chunks(conn: &Connection, doc_id: i64) -> Result<(), rusqlite::Error> {
+    for (index, chunk_id) in ordered_chunk_ids(conn, doc_id)?.into_iter().enumerate() {
+        conn.execute("UPDATE embeddings SET chunk_index = ?1 WHERE id = ?2", params![index as i64, chunk_id])?;
+    }
+    Ok(())
+}
+
+/// Replaces a chunk's text and vector. Vectors from other models were of the old text,
+/// so they're dropped; the chunk gets them back when re-embedded with those models.
+fn replace_chunk(
+    conn: &Connection,
+    chunk: &ChunkView,
+    text: &str,
+    vector: &[f32],
+    provenance: &ChunkProvenance,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let updated = conn.execute(
+        "UPDATE embeddings SET chunk = ?1, content_hash = ?2 WHERE id = ?3",
+        params![text, content_hash(text), chunk.id],
+    )?;
+    if updated == 0 {
+        return Err(Box::new(ChunkEditError::ChunkNotFound(chunk.id)));
+    }
+    for index in DocumentStore::vector_indexes(conn)? {
+        conn.execute(&format!("DELETE FROM {} WHERE rowid = ?1", index.table_name), params![chunk.id])?;
+    }
+    DocumentStore::insert_vector(conn, chunk.id, vector, &chunk.embedding_model_name).map_err(|e| e.to_string())?;
+    DocumentStore::set_chunk_position(conn, chunk.id, chunk.index, text, provenance)?;
+    Ok(())
+}
+
+/// Splits `text` at character `at`, trimming the whitespace either side of the cut
+fn split_text(text: &str, at: usize) -> Option<(&str, &str)> {
+    let byte_offset = text.char_indices().nth(at).map(|(offset, _)| offset)?;
+    let (first, second) = (text[..byte_offset].trim(), text[byte_offset..].trim());
+    if first.is_empty() || second.is_empty() {
+        None
+    } else {
+        Some((first, second))
+    }
+}
+
+/// Joins two neighbouring chunks. Chunking overlap repeats the end of one chunk at the
+/// start of the next, so a run of three or more repeated words is only kept once.
+fn join_chunks(first: &str, second: &str) -> String {
+    let first_words: Vec<&str> = first.split_whitespace().collect();
+    let second_words: Vec<&str> = second.split_whitespace().collect();
+    let overlap = (3..=first_words.len().min(second_words.len()))
+    .rev()
+    .find(|&k| first_words[first_words.len() - k..] == second_words[..k])
+    .unwrap_or(0);
+    if overlap == 0 {
+        return format!("{}\n\n{}", first.trim_end(), second.trim_start());
+    }
+
+    let mut rest = second;
+    for _ in 0..overlap {
+        let trimmed = rest.trim_start();
+        rest = &trimmed[trimmed.find(char::is_whitespace).unwrap_or(trimmed.len())..];
+    }
+    match rest.trim_start() {
+        "" => first.trim_end().to_string(),
+        rest => format!("{} {}", first.trim_end(), rest),
+    }
+}
+
+/// Writes the two halves of a split chunk: the first keeps the chunk's row, the second
+/// is a new chunk right after it. Offsets are only carried over when the chunk had both.
+fn apply_split(
+    conn: &Connection,
+    chunk: &ChunkView,
+    first: &str,
+    second: &str,
+    vectors: &[Vec<f32>],
+) -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
+    let (start, end) = match (chunk.provenance.start_offset, chunk.provenance.end_offset) {
+        (Some(start), Some(end)) => (Some(start), Some(end)),
+        _ => (None, None),
+    };
+    let first_provenance = ChunkProvenance {
+        end_offset: start.map(|start| start + first.chars().count()),
+        ..chunk.provenance.clone()
+    };
+    let second_provenance = ChunkProvenance {
+        start_offset: end.map(|end| end.saturating_sub(second.chars().count())),
+        ..chunk.provenance.clone()
+    };
+    replace_chunk(conn, chunk, first, &vectors[0], &first_provenance)?;
+
+    // Same index as the first half, and a later id, so it sorts right after it
+    let second_id = DocumentStore::insert_chunk_embedding(conn, chunk.doc_id, second, &vectors[1], &chunk.embedding_model_name)
+    .map_err(|e| e.to_string())?;
+    DocumentStore::set_chunk_position(conn, second_id, chunk.index, second, &second_provenance)?;
+    renumber_chunks(conn, chunk.doc_id)?;
+    Ok(second_id)
+}
+
+/// Writes a merged chunk into the first chunk's row and removes the second
+fn apply_merge(
+    conn: &Connection,
+    first: &ChunkView,
+    second: &ChunkView,
+    merged: &str,
+    vector: &[f32],
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let provenance = ChunkProvenance {
+        start_offset: first.provenance.start_offset,
+        end_offset: second.provenance.end_offset.or(first.provenance.end_offset),
+        page: first.provenance.page.or(second.provenance.page),
+        section: first.provenance.section.clone().or_else(|| second.provenance.section.clone()),
+    };
+    replace_chunk(conn, first, merged, vector, &provenance)?;
+    DocumentStore::delete_chunks(conn, &[second.id]).map_err(|e| e.to_string())?;
+    renumber_chunks(conn, first.doc_id)?;
+    Ok(())
+}
+
+impl DocumentStore {
+    /// `limit` chunks of a document starting at position `offset`
+    pub async fn list_document_chunks(
+        &self,
+        doc_id: i64,
+        offset: usize,
+        limit: usize,
+    ) -> Result<ChunkPage, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        let total: i64 = conn.query_row("SELECT COUNT(*) FROM embeddings WHERE doc_id = ?1", params![doc_id], |row| row.get(0))?;
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE doc_id = ?1 ORDER BY chunk_index, id LIMIT ?2 OFFSET ?3",
+            CHUNK_SELECT
+        ))?;
+        let rows = stmt.query_map(params![doc_id, limit as i64, offset as i64], chunk_from_row)?;
+        let mut chunks = Vec::new();
+        for (position, row) in rows.enumerate() {
+            chunks.push(ChunkView { index: offset + position, ..row? });
+        }
+        Ok(ChunkPage { doc_id, total: total as usize, offset, chunks })
+    }
+
+    /// A document's chunks containing `query`, ignoring ASCII case, in reading order
+    pub async fn search_document_chunks(
+        &self,
+        doc_id: i64,
+        query: &str,
+    ) -> Result<Vec<ChunkView>, Box<dyn std::error::Error + Send + Sync>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pattern = format!("%{}%", query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        let conn = self.conn.lock().await;
+        let order = ordered_chunk_ids(&conn, doc_id)?;
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE doc_id = ?1 AND chunk LIKE ?2 ESCAPE '\\' ORDER BY chunk_index, id",
+            CHUNK_SELECT
+        ))?;
+        let rows = stmt.query_map(params![doc_id, pattern], chunk_from_row)?;
+        let mut chunks = Vec::new();
+        for row in rows {
+            let chunk = row?;
+            let index = order.iter().position(|&id| id == chunk.id).unwrap_or(0);
+            chunks.push(ChunkView { index, ..chunk });
+        }
+        Ok(chunks)
+    }
+
+    /// Replaces a chunk's text and re-embeds just that chunk
+    pub async fn edit_chunk(
+        &self,
+        chunk_id: i64,
+        text: &str,
+        provider: &Provider,
+    ) -> Result<ChunkView, Box<dyn std::error::Error + Send + Sync>> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(Box::new(ChunkEditError::EmptyChunk));
+        }
+        let chunk = chunk_view(&*self.conn.lock().await, chunk_id)?;
+        let vectors = Self::embed_batch_with_retry(provider, &chunk.embedding_model_name, &[text.to_string()], self.get_embedding_batch_config())
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        replace_chunk(&tx, &chunk, text, &vectors[0], &chunk.provenance)?;
+        let edited = chunk_view(&tx, chunk_id)?;
+        tx.commit()?;
+        log::info!("Edited chunk {} of document {}", chunk_id, chunk.doc_id);
+        Ok(edited)
+    }
+
+    /// Deletes chunks, say running headers and footers, along with their vectors.
+    /// Returns how many were deleted.
+    pub async fn remove_chunks(&self, chunk_ids: &[i64]) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        let mut doc_ids = Vec::new();
+        let mut found = Vec::new();
+        for &chunk_id in chunk_ids {
+            let doc_id: Option<i64> = tx
+            .query_row("SELECT doc_id FROM embeddings WHERE id = ?1", params![chunk_id], |row| row.get(0))
+            .optional()?;
+            if let Some(doc_id) = doc_id {
+                found.push(chunk_id);
+                if !doc_ids.contains(&doc_id) {
+                    doc_ids.push(doc_id);
+                }
+            }
+        }
+        Self::delete_chunks(&tx, &found).map_err(|e| e.to_string())?;
+        for doc_id in doc_ids {
+            renumber_chunks(&tx, doc_id)?;
+        }
+        tx.commit()?;
+        log::info!("Deleted {} chunks", found.len());
+        Ok(found.len())
+    }
+
+    /// Splits a chunk in two at character `at` and embeds both halves.
+    /// Returns the two chunks.
+    pub async fn split_chunk(
+        &self,
+        chunk_id: i64,
+        at: usize,
+        provider: &Provider,
+    ) -> Result<Vec<ChunkView>, Box<dyn std::error::Error + Send + Sync>> {
+        let chunk = chunk_view(&*self.conn.lock().await, chunk_id)?;
+        let (first, second) = split_text(&chunk.content, at).ok_or(ChunkEditError::SplitOutOfRange { chunk_id, at })?;
+        let texts = vec![first.to_string(), second.to_string()];
+        let vectors = Self::embed_batch_with_retry(provider, &chunk.embedding_model_name, &texts, self.get_embedding_batch_config())
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        let second_id = apply_split(&tx, &chunk, first, second, &vectors)?;
+        let halves = vec![chunk_view(&tx, chunk_id)?, chunk_view(&tx, second_id)?];
+        tx.commit()?;
+        log::info!("Split chunk {} of document {} into {} and {}", chunk_id, chunk.doc_id, chunk_id, second_id);
+        Ok(halves)
+    }
+
+    /// Merges a chunk with the one right after it and embeds the result in place of the
+    /// first. Returns the merged chunk.
+    pub async fn merge_chunks(
+        &self,
+        first_id: i64,
+        second_id: i64,
+        provider: &Provider,
+    ) -> Result<ChunkView, Box<dyn std::error::Error + Send + Sync>> {
+        let (first, second) = {
+            let conn = self.conn.lock().await;
+            let (first, second) = (chunk_view(&conn, first_id)?, chunk_view(&conn, second_id)?);
+            if first.doc_id != second.doc_id || first.index + 1 != second.index {
+                return Err(Box::new(ChunkEditError::NotAdjacent(first_id, second_id)));
+            }
+            (first, second)
+        };
+        let merged = join_chunks(&first.content, &second.content);
+        let vectors = Self::embed_batch_with_retry(provider, &first.embedding_model_name, &[merged.clone()], self.get_embedding_batch_config())
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        apply_merge(&tx, &first, &second, &merged, &vectors[0])?;
+        let merged_chunk = chunk_view(&tx, first_id)?;
+        tx.commit()?;
+        log::info!("Merged chunk {} into {} in document {}", second_id, first_id, first.doc_id);
+        Ok(merged_chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document_store::insert_test_document;
+
+    fn store_with_chunks(dir: &std::path::Path, chunks: &[&str]) -> (DocumentStore, Vec<i64>) {
+        let store = DocumentStore::new(dir.join("chunks.canon")).unwrap();
+        let mut ids = Vec::new();
+        {
+            let conn = store.conn.blocking_lock();
+            insert_test_document(&conn, 1, "/tmp/noir.pdf", "test-model");
+            for (index, chunk) in chunks.iter().enumerate() {
+                let id = DocumentStore::insert_chunk_embedding(&conn, 1, chunk, &[1.0, 0.0, 0.0], "test-model").unwrap();
+                let provenance = ChunkProvenance { page: Some(index as u32 + 1), ..Default::default() };
+                DocumentStore::set_chunk_position(&conn, id, index, chunk, &provenance).unwrap();
+                ids.push(id);
+            }
+        }
+        (store, ids)
+    }
+
+    #[test]
+    fn test_browse_search_and_delete_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, ids) = store_with_chunks(dir.path(), &[
+            "THE BIG SLEEP 12",
+            "Rain on the window.",
+            "A gun in the drawer, 100% loaded.",
+            "THE BIG SLEEP 13",
+        ]);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let page = runtime.block_on(store.list_document_chunks(1, 1, 2)).unwrap();
+        assert_eq!(page.total, 4);
+        assert_eq!(page.chunks.iter().map(|chunk| (chunk.index, chunk.id)).collect::<Vec<_>>(), vec![(1, ids[1]), (2, ids[2])]);
+        assert_eq!(page.chunks[0].provenance.page, Some(2));
+
+        let headers = runtime.block_on(store.search_document_chunks(1, "the big sleep")).unwrap();
+        assert_eq!(headers.iter().map(|chunk| chunk.index).collect::<Vec<_>>(), vec![0, 3]);
+        assert_eq!(runtime.block_on(store.search_document_chunks(1, "100%")).unwrap().len(), 1);
+        assert!(runtime.block_on(store.search_document_chunks(1, "0_")).unwrap().is_empty());
+
+        let removed = runtime.block_on(store.remove_chunks(&[ids[0], ids[3], 999])).unwrap();
+        assert_eq!(removed, 2);
+        let page = runtime.block_on(store.list_document_chunks(1, 0, 10)).unwrap();
+        assert_eq!(page.chunks.iter().map(|chunk| chunk.id).collect::<Vec<_>>(), vec![ids[1], ids[2]]);
+        let conn = store.conn.blocking_lock();
+        let indexes: Vec<i64> = conn
+        .prepare("SELECT chunk_index FROM embeddings ORDER BY chunk_index")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+        assert_eq!(indexes, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_split_and_merge_keep_reading_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let (store, ids) = store_with_chunks(dir.path(), &["Rain on the window. A gun in the drawer.", "The phone rang twice."]);
+        let conn = store.conn.blocking_lock();
+
+        let chunk = chunk_view(&conn, ids[0]).unwrap();
+        let (first, second) = split_text(&chunk.content, 19).unwrap();
+        assert_eq!((first, second), ("Rain on the window.", "A gun in the drawer."));
+        assert!(split_text(&chunk.content, 0).is_none());
+        let second_id = apply_split(&conn, &chunk, first, second, &[vec![0.0, 1.0, 0.0], vec![0.0, 0.0, 1.0]]).unwrap();
+        let order = ordered_chunk_ids(&conn, 1).unwrap();
+        assert_eq!(order, vec![ids[0], second_id, ids[1]]);
+        assert_eq!(chunk_view(&conn, second_id).unwrap().provenance.page, Some(1));
+
+        let (first, second) = (chunk_view(&conn, second_id).unwrap(), chunk_view(&conn, ids[1]).unwrap());
+        let merged = join_chunks(&first.content, &second.content);
+        apply_merge(&conn, &first, &second, &merged, &[1.0, 0.0, 0.0]).unwrap();
+        assert_eq!(ordered_chunk_ids(&conn, 1).unwrap(), vec![ids[0], second_id]);
+        assert_eq!(chunk_view(&conn, second_id).unwrap().content, "A gun in the drawer.\n\nThe phone rang twice.");
+        let fts_hits: i64 = conn
+        .query_row("SELECT COUNT(*) FROM embeddings_fts WHERE embeddings_fts MATCH 'phone'", [], |row| row.get(0))
+        .unwrap();
+        assert_eq!(fts_hits, 1);
+    }
+
+    #[test]
+    fn test_join_drops_chunk_overlap() {
+        assert_eq!(
+            join_chunks("Rain on the window and a gun", "on the window and a gun in the drawer."),
+            "Rain on the window and a gun in the drawer."
+        );
+        assert_eq!(join_chunks("Rain on the", "the phone"), "Rain on the\n\nthe phone");
+    }
+}
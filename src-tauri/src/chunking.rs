@@ -0,0 +1,747 @@
+// src/chunking.rs
+//
+// Splits document text into the chunks that get embedded. Each strategy packs
+// natural units (words, sentences, paragraphs, Markdown sections) into chunks
+// no larger than `max_size`, carrying whole trailing units of up to `overlap`
+// into the next chunk. Units that are too big on their own fall back to the
+// next smaller unit, down to single words. Chunks are then located back in the
+// text to record where each came from.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::ingest::DocumentSegment;
+
+/// Splits text into chunks for embedding
+pub trait Chunker: Send + Sync {
+    fn chunk(&self, text: &str) -> Vec<String>;
+
+    /// The strategy and parameters this chunker was built from, as recorded with each document
+    fn config(&self) -> ChunkingConfig;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkingStrategy {
+    /// Whitespace-separated words packed by character count
+    Window,
+    /// Sentences packed by character count
+    Sentence,
+    /// Blank-line separated paragraphs packed by character count
+    Paragraph,
+    /// One chunk per Markdown heading section, split by paragraph when a section is too long
+    Heading,
+    /// Sentences packed by estimated token count
+    TokenBudget,
+}
+
+impl ChunkingStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChunkingStrategy::Window => "window",
+            ChunkingStrategy::Sentence => "sentence",
+            ChunkingStrategy::Paragraph => "paragraph",
+            ChunkingStrategy::Heading => "heading",
+            ChunkingStrategy::TokenBudget => "token_budget",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "window" => Some(ChunkingStrategy::Window),
+            "sentence" => Some(ChunkingStrategy::Sentence),
+            "paragraph" => Some(ChunkingStrategy::Paragraph),
+            "heading" => Some(ChunkingStrategy::Heading),
+            "token_budget" => Some(ChunkingStrategy::TokenBudget),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ChunkingStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A chunking strategy and its parameters. `max_size` and `overlap` are in
+/// characters, except for `TokenBudget` where they're estimated tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkingConfig {
+    pub strategy: ChunkingStrategy,
+    pub max_size: usize,
+    pub overlap: usize,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            strategy: ChunkingStrategy::Window,
+            max_size: 1024,
+            overlap: 200,
+        }
+    }
+}
+
+impl ChunkingConfig {
+    pub fn new(strategy: ChunkingStrategy, max_size: usize, overlap: usize) -> Self {
+        Self { strategy, max_size, overlap }
+    }
+
+    pub fn from_preferences(preferences: &crate::preferences::Preferences) -> Self {
+        let default = Self::default();
+        Self {
+            strategy: ChunkingStrategy::from_name(&preferences.chunking_strategy).unwrap_or(default.strategy),
+            max_size: if preferences.chunk_size == 0 { default.max_size } else { preferences.chunk_size },
+            overlap: preferences.chunk_overlap,
+        }
+    }
+
+    pub fn chunker(&self) -> Box<dyn Chunker> {
+        match self.strategy {
+            ChunkingStrategy::Window => Box::new(WindowChunker::new(self.max_size, self.overlap)),
+            ChunkingStrategy::Sentence => Box::new(SentenceChunker::new(self.max_size, self.overlap)),
+            ChunkingStrategy::Paragraph => Box::new(ParagraphChunker::new(self.max_size, self.overlap)),
+            ChunkingStrategy::Heading => Box::new(HeadingChunker::new(self.max_size, self.overlap)),
+            ChunkingStrategy::TokenBudget => Box::new(TokenBudgetChunker::new(self.max_size, self.overlap)),
+        }
+    }
+
+    /// Parameters as stored in documents.chunking_params
+    pub fn params_json(&self) -> String {
+        serde_json::json!({
+            "max_size": self.max_size,
+            "overlap": self.overlap,
+        })
+        .to_string()
+    }
+}
+
+/// Word windows of at most `max_chars`, each starting with the trailing words
+/// of the previous window that fit in `overlap` characters
+pub struct WindowChunker {
+    max_chars: usize,
+    overlap: usize,
+}
+
+impl WindowChunker {
+    pub fn new(max_chars: usize, overlap: usize) -> Self {
+        Self { max_chars, overlap }
+    }
+}
+
+impl Chunker for WindowChunker {
+    fn chunk(&self, text: &str) -> Vec<String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        pack_units(&words, " ", self.max_chars, self.overlap, &char_count)
+    }
+
+    fn config(&self) -> ChunkingConfig {
+        ChunkingConfig::new(ChunkingStrategy::Window, self.max_chars, self.overlap)
+    }
+}
+
+/// Whole sentences packed up to `max_chars`
+pub struct SentenceChunker {
+    max_chars: usize,
+    overlap: usize,
+}
+
+impl SentenceChunker {
+    pub fn new(max_chars: usize, overlap: usize) -> Self {
+        Self { max_chars, overlap }
+    }
+}
+
+impl Chunker for SentenceChunker {
+    fn chunk(&self, text: &str) -> Vec<String> {
+        pack_spans(text, &sentence_spans(text), self.max_chars, self.overlap, &char_count)
+    }
+
+    fn config(&self) -> ChunkingConfig {
+        ChunkingConfig::new(ChunkingStrategy::Sentence, self.max_chars, self.overlap)
+    }
+}
+
+/// Whole paragraphs packed up to `max_chars`; a paragraph that is too long by
+/// itself is split by sentence
+pub struct ParagraphChunker {
+    max_chars: usize,
+    overlap: usize,
+}
+
+impl ParagraphChunker {
+    pub fn new(max_chars: usize, overlap: usize) -> Self {
+        Self { max_chars, overlap }
+    }
+}
+
+impl Chunker for ParagraphChunker {
+    fn chunk(&self, text: &str) -> Vec<String> {
+        chunk_paragraphs(text, self.max_chars, self.overlap)
+    }
+
+    fn config(&self) -> ChunkingConfig {
+        ChunkingConfig::new(ChunkingStrategy::Paragraph, self.max_chars, self.overlap)
+    }
+}
+
+/// One chunk per Markdown section. Long sections are split by paragraph and
+/// every piece is prefixed with the section's headings so it keeps its context.
+pub struct HeadingChunker {
+    max_chars: usize,
+    overlap: usize,
+}
+
+impl HeadingChunker {
+    pub fn new(max_chars: usize, overlap: usize) -> Self {
+        Self { max_chars, overlap }
+    }
+}
+
+impl Chunker for HeadingChunker {
+    fn chunk(&self, text: &str) -> Vec<String> {
+        let mut chunks = Vec::new();
+        for (headings, body) in split_sections(text) {
+            let body = body.trim();
+            let prefix = headings.join("\n");
+            if body.is_empty() {
+                if !prefix.is_empty() {
+                    chunks.push(prefix);
+                }
+                continue;
+            }
+            if prefix.is_empty() {
+                chunks.extend(chunk_paragraphs(body, self.max_chars, self.overlap));
+                continue;
+            }
+
+            let whole = format!("{}\n\n{}", prefix, body);
+            if char_count(&whole) <= self.max_chars {
+                chunks.push(whole);
+                continue;
+            }
+            // Leave room for the headings on every piece, but never less than a useful amount
+            let budget = self.max_chars.saturating_sub(char_count(&prefix) + 2).max(self.max_chars / 2).max(1);
+            for piece in chunk_paragraphs(body, budget, self.overlap.min(budget / 2)) {
+                chunks.push(format!("{}\n\n{}", prefix, piece));
+            }
+        }
+        chunks
+    }
+
+    fn config(&self) -> ChunkingConfig {
+        ChunkingConfig::new(ChunkingStrategy::Heading, self.max_chars, self.overlap)
+    }
+}
+
+/// Whole sentences packed up to `max_tokens` estimated tokens
+pub struct TokenBudgetChunker {
+    max_tokens: usize,
+    overlap_tokens: usize,
+}
+
+impl TokenBudgetChunker {
+    pub fn new(max_tokens: usize, overlap_tokens: usize) -> Self {
+        Self { max_tokens, overlap_tokens }
+    }
+}
+
+impl Chunker for TokenBudgetChunker {
+    fn chunk(&self, text: &str) -> Vec<String> {
+        pack_spans(text, &sentence_spans(text), self.max_tokens, self.overlap_tokens, &estimate_tokens)
+    }
+
+    fn config(&self) -> ChunkingConfig {
+        ChunkingConfig::new(ChunkingStrategy::TokenBudget, self.max_tokens, self.overlap_tokens)
+    }
+}
+
+fn char_count(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Rough token count without a model tokenizer: about four characters per token,
+/// and at least one token per word
+pub fn estimate_tokens(text: &str) -> usize {
+    text.split_whitespace()
+    .map(|word| (word.chars().count() + 3) / 4)
+    .sum()
+}
+
+/// Greedily packs units into chunks of at most `max_size` (as measured by `measure`,
+/// separators included). When a chunk is full, its trailing units that fit within
+/// `overlap` start the next one. A unit bigger than `max_size` is split into words;
+/// a single word bigger than `max_size` becomes a chunk of its own.
+fn pack_units<S: AsRef<str>>(
+    units: &[S],
+    separator: &str,
+    max_size: usize,
+    overlap: usize,
+    measure: &dyn Fn(&str) -> usize,
+) -> Vec<String> {
+    let max_size = max_size.max(1);
+    let separator_size = measure(separator);
+    let mut chunks = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut current_size = 0;
+
+    let flush = |current: &mut Vec<&str>, current_size: &mut usize, chunks: &mut Vec<String>, keep_overlap: bool| {
+        if current.is_empty() {
+            return;
+        }
+        chunks.push(current.join(separator));
+
+        let mut carried = 0;
+        let mut carry_size = 0;
+        if keep_overlap {
+            // Never carry the whole chunk, or the next one could repeat it
+            while carried + 1 < current.len() {
+                let unit_size = measure(current[current.len() - carried - 1]);
+                let added = if carried == 0 { unit_size } else { unit_size + separator_size };
+                if carry_size + added > overlap {
+                    break;
+                }
+                carry_size += added;
+                carried += 1;
+            }
+        }
+        let start = current.len() - carried;
+        current.drain(..start);
+        *current_size = carry_size;
+    };
+
+    for unit in units {
+        let unit = unit.as_ref().trim();
+        if unit.is_empty() {
+            continue;
+        }
+        let unit_size = measure(unit);
+
+        if unit_size > max_size {
+            flush(&mut current, &mut current_size, &mut chunks, false);
+            let words: Vec<&str> = unit.split_whitespace().collect();
+            if words.len() > 1 {
+                chunks.extend(pack_units(&words, " ", max_size, overlap, measure));
+            } else {
+                chunks.push(unit.to_string());
+            }
+            continue;
+        }
+
+        let added = if current.is_empty() { unit_size } else { unit_size + separator_size };
+        if current_size + added > max_size {
+            flush(&mut current, &mut current_size, &mut chunks, true);
+            // Drop the carried overlap if it leaves no room for this unit
+            if current_size + unit_size + separator_size > max_size {
+                current.clear();
+                current_size = 0;
+            }
+        }
+        current_size += if current.is_empty() { unit_size } else { unit_size + separator_size };
+        current.push(unit);
+    }
+    flush(&mut current, &mut current_size, &mut chunks, false);
+
+    chunks
+}
+
+/// Greedily packs spans of `text` into chunks of at most `max_size` (as measured by
+/// `measure`). A chunk runs from its first span to its last in the original text, so
+/// the line breaks between and inside them are kept. When a chunk is full, its
+/// trailing spans that fit within `overlap` start the next one. A span bigger than
+/// `max_size` is packed by its words; a single word bigger than that is a chunk alone.
+fn pack_spans(
+    text: &str,
+    spans: &[(usize, usize)],
+    max_size: usize,
+    overlap: usize,
+    measure: &dyn Fn(&str) -> usize,
+) -> Vec<String> {
+    let max_size = max_size.max(1);
+    let joined = |spans: &[(usize, usize)]| &text[spans[0].0..spans[spans.len() - 1].1];
+    let mut chunks = Vec::new();
+    let mut current: Vec<(usize, usize)> = Vec::new();
+
+    let flush = |current: &mut Vec<(usize, usize)>, chunks: &mut Vec<String>, keep_overlap: bool| {
+        if current.is_empty() {
+            return;
+        }
+        chunks.push(joined(current).to_string());
+        // Never carry the whole chunk, or the next one could repeat it
+        let mut carried = 0;
+        while keep_overlap && carried + 1 < current.len() && measure(joined(&current[current.len() - carried - 1..])) <= overlap {
+            carried += 1;
+        }
+        current.drain(..current.len() - carried);
+    };
+
+    for &(start, end) in spans {
+        if measure(&text[start..end]) > max_size {
+            flush(&mut current, &mut chunks, false);
+            let words = word_byte_spans(text, start, end);
+            if words.len() > 1 {
+                chunks.extend(pack_spans(text, &words, max_size, overlap, measure));
+            } else {
+                chunks.push(text[start..end].to_string());
+            }
+            continue;
+        }
+        if !current.is_empty() && measure(&text[current[0].0..end]) > max_size {
+            flush(&mut current, &mut chunks, true);
+            // Drop the carried overlap if it leaves no room for this span
+            if !current.is_empty() && measure(&text[current[0].0..end]) > max_size {
+                current.clear();
+            }
+        }
+        current.push((start, end));
+    }
+    flush(&mut current, &mut chunks, false);
+
+    chunks
+}
+
+/// Byte spans of the whitespace-separated words between `start` and `end`
+fn word_byte_spans(text: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut word_start: Option<usize> = None;
+    for (offset, c) in text[start..end].char_indices() {
+        if c.is_whitespace() {
+            if let Some(word_start) = word_start.take() {
+                spans.push((start + word_start, start + offset));
+            }
+        } else if word_start.is_none() {
+            word_start = Some(offset);
+        }
+    }
+    if let Some(word_start) = word_start {
+        spans.push((start + word_start, end));
+    }
+    spans
+}
+
+/// Splits text into sentences at `.`, `!` or `?` (plus any closing quotes or
+/// brackets) followed by whitespace and a word that isn't lowercase, and at blank
+/// lines. Abbreviations like "Mr." still split early; that only costs a shorter unit.
+/// Each sentence is trimmed but otherwise as written, line breaks included.
+pub fn split_sentences(text: &str) -> Vec<String> {
+    sentence_spans(text).into_iter().map(|(start, end)| text[start..end].to_string()).collect()
+}
+
+/// Byte spans of the sentences `split_sentences` finds
+fn sentence_spans(text: &str) -> Vec<(usize, usize)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let byte_at = |i: usize| chars.get(i).map_or(text.len(), |&(offset, _)| offset);
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i].1;
+        if c == '\n' {
+            // A blank line ends the sentence whatever its punctuation
+            let blank_line_follows = chars[i + 1..].iter().take_while(|(_, c)| c.is_whitespace()).any(|&(_, c)| c == '\n');
+            if blank_line_follows {
+                push_trimmed_span(text, start, byte_at(i), &mut spans);
+                start = byte_at(i);
+            }
+        } else if matches!(c, '.' | '!' | '?') {
+            while i + 1 < chars.len() && matches!(chars[i + 1].1, '"' | '\'' | '”' | '’' | ')' | ']') {
+                i += 1;
+            }
+            // A lowercase word next means the sentence carries on, as in
+            // dialogue attribution ("Who's there?" she asked) or "e.g. this"
+            let next_word_start = chars[i + 1..].iter().map(|&(_, c)| c).find(|c| !c.is_whitespace());
+            let continues = next_word_start.map(|c| c.is_lowercase()).unwrap_or(false);
+            if (i + 1 >= chars.len() || chars[i + 1].1.is_whitespace()) && !continues {
+                push_trimmed_span(text, start, byte_at(i + 1), &mut spans);
+                start = byte_at(i + 1);
+            }
+        }
+        i += 1;
+    }
+    push_trimmed_span(text, start, text.len(), &mut spans);
+    spans
+}
+
+fn push_trimmed_span(text: &str, start: usize, end: usize, spans: &mut Vec<(usize, usize)>) {
+    let piece = &text[start..end];
+    let trimmed = piece.trim();
+    if !trimmed.is_empty() {
+        let trimmed_start = start + (piece.len() - piece.trim_start().len());
+        spans.push((trimmed_start, trimmed_start + trimmed.len()));
+    }
+}
+
+/// Splits text at blank lines, dropping empty paragraphs
+pub fn split_paragraphs(text: &str) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(current.join("\n"));
+                current.clear();
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(current.join("\n"));
+    }
+    paragraphs
+}
+
+fn chunk_paragraphs(text: &str, max_chars: usize, overlap: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut fitting: Vec<String> = Vec::new();
+    for paragraph in split_paragraphs(text) {
+        if char_count(&paragraph) > max_chars {
+            chunks.extend(pack_units(&fitting, "\n\n", max_chars, overlap, &char_count));
+            fitting.clear();
+            chunks.extend(pack_spans(&paragraph, &sentence_spans(&paragraph), max_chars, overlap, &char_count));
+        } else {
+            fitting.push(paragraph);
+        }
+    }
+    chunks.extend(pack_units(&fitting, "\n\n", max_chars, overlap, &char_count));
+    chunks
+}
+
+/// Splits Markdown into (heading path, body) sections at ATX headings outside
+/// code fences. The heading path holds the current heading and its parents.
+fn split_sections(text: &str) -> Vec<(Vec<String>, String)> {
+    let mut sections: Vec<(Vec<String>, String)> = Vec::new();
+    let mut headings: Vec<(usize, String)> = Vec::new();
+    let mut body = String::new();
+    let mut in_fence = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        let level = heading_level(trimmed);
+        if !in_fence && level > 0 {
+            sections.push((headings.iter().map(|(_, h)| h.clone()).collect(), std::mem::take(&mut body)));
+            headings.retain(|(existing, _)| *existing < level);
+            headings.push((level, trimmed.trim_end().to_string()));
+        } else {
+            body.push_str(line);
+            body.push('\n');
+        }
+    }
+    sections.push((headings.into_iter().map(|(_, h)| h).collect(), body));
+
+    // A heading followed straight away by a subheading has no body of its own;
+    // the subheading's section already carries it in its heading path
+    let mut kept = Vec::new();
+    for (index, (path, body)) in sections.iter().enumerate() {
+        let next_extends_path = sections
+        .get(index + 1)
+        .map(|(next, _)| next.len() > path.len() && next.starts_with(path))
+        .unwrap_or(false);
+        if body.trim().is_empty() && (path.is_empty() || next_extends_path) {
+            continue;
+        }
+        kept.push((path.clone(), body.clone()));
+    }
+    kept
+}
+
+fn heading_level(line: &str) -> usize {
+    let hashes = line.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+        hashes
+    } else {
+        0
+    }
+}
+
+/// Where a chunk came from in its document
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkProvenance {
+    /// Character offsets of the chunk in the document text; None when the chunk couldn't
+    /// be found there word for word
+    pub start_offset: Option<usize>,
+    pub end_offset: Option<usize>,
+    pub page: Option<u32>,
+    pub section: Option<String>,
+}
+
+/// Locates each chunk in the text it was cut from and looks up its page and section.
+/// Chunkers normalise whitespace and the heading chunker prefixes section headings, so
+/// chunks are matched word by word, and without their heading lines when that fails.
+pub fn chunk_provenance(text: &str, chunks: &[String], segments: &[DocumentSegment]) -> Vec<ChunkProvenance> {
+    let words = word_spans(text);
+    let mut from = 0;
+    chunks
+    .iter()
+    .map(|chunk| {
+        let found = find_words(&words, chunk, from).or_else(|| {
+            let body = without_heading_lines(chunk);
+            if body.len() < chunk.len() { find_words(&words, body, from) } else { None }
+        });
+        match found {
+            Some((first_word, start, end)) => {
+                // Chunks come in order; overlap means the next one may start inside this one
+                from = first_word + 1;
+                ChunkProvenance {
+                    start_offset: Some(start),
+                    end_offset: Some(end),
+                    page: segments.iter().find(|segment| segment.page.is_some() && segment.contains(start)).and_then(|segment| segment.page),
+                    // The innermost section, when sections nest or overlap
+                    section: segments
+                    .iter()
+                    .filter(|segment| segment.section.is_some() && segment.contains(start))
+                    .max_by_key(|segment| segment.start)
+                    .and_then(|segment| segment.section.clone()),
+                }
+            }
+            None => ChunkProvenance::default(),
+        }
+    })
+    .collect()
+}
+
+/// Words of the text with their character offsets
+fn word_spans(text: &str) -> Vec<(&str, usize, usize)> {
+    let mut spans = Vec::new();
+    let mut word_start: Option<(usize, usize)> = None;
+    let mut char_offset = 0;
+    for (byte_offset, c) in text.char_indices() {
+        if c.is_whitespace() {
+            if let Some((start_byte, start_char)) = word_start.take() {
+                spans.push((&text[start_byte..byte_offset], start_char, char_offset));
+            }
+        } else if word_start.is_none() {
+            word_start = Some((byte_offset, char_offset));
+        }
+        char_offset += 1;
+    }
+    if let Some((start_byte, start_char)) = word_start {
+        spans.push((&text[start_byte..], start_char, char_offset));
+    }
+    spans
+}
+
+/// The first run of `words`, at or after word `from`, that spells out `chunk`.
+/// Returns the index of its first word and its character offsets.
+fn find_words(words: &[(&str, usize, usize)], chunk: &str, from: usize) -> Option<(usize, usize, usize)> {
+    let wanted: Vec<&str> = chunk.split_whitespace().collect();
+    if wanted.is_empty() || wanted.len() > words.len() {
+        return None;
+    }
+    (from..=words.len() - wanted.len())
+    .find(|&start| wanted.iter().enumerate().all(|(i, word)| words[start + i].0 == *word))
+    .map(|start| (start, words[start].1, words[start + wanted.len() - 1].2))
+}
+
+fn without_heading_lines(chunk: &str) -> &str {
+    let mut rest = chunk;
+    while rest.starts_with('#') {
+        rest = rest.split_once('\n').map_or("", |(_, after)| after);
+    }
+    rest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_overlap_is_whole_words_within_budget() {
+        let text = "one two three four five six seven eight nine ten";
+        let chunks = WindowChunker::new(15, 8).chunk(text);
+
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 15));
+        for pair in chunks.windows(2) {
+            let last_word = pair[0].split_whitespace().last().unwrap();
+            assert!(pair[1].starts_with(last_word), "{:?} should overlap {:?}", pair[1], pair[0]);
+        }
+        assert_eq!(chunks.last().unwrap().split_whitespace().last(), Some("ten"));
+    }
+
+    #[test]
+    fn test_sentence_chunks_end_on_sentence_boundaries() {
+        let text = "The rain fell. \"Who's there?\" she asked. Nobody answered!\n\nThe door creaked";
+        let sentences = split_sentences(text);
+        assert_eq!(sentences, vec!["The rain fell.", "\"Who's there?\" she asked.", "Nobody answered!", "The door creaked"]);
+
+        let chunks = SentenceChunker::new(45, 0).chunk(text);
+        assert_eq!(chunks, vec!["The rain fell. \"Who's there?\" she asked.", "Nobody answered!\n\nThe door creaked"]);
+    }
+
+    #[test]
+    fn test_sentence_chunks_keep_the_original_line_breaks() {
+        let text = "Rain on the window,\na gun in the drawer.\nThe phone rang.\n\nNobody answered it.";
+        assert_eq!(split_sentences(text)[0], "Rain on the window,\na gun in the drawer.");
+
+        let chunks = SentenceChunker::new(60, 20).chunk(text);
+        assert_eq!(chunks, vec![
+            "Rain on the window,\na gun in the drawer.\nThe phone rang.",
+            "The phone rang.\n\nNobody answered it.",
+        ]);
+        // Every chunk is a stretch of the text as written
+        assert!(chunks.iter().all(|chunk| text.contains(chunk.as_str())));
+    }
+
+    #[test]
+    fn test_paragraph_chunker_keeps_paragraphs_whole() {
+        let text = "First paragraph.\n\nSecond paragraph.\n\nThird paragraph, which is rather longer.";
+        let chunks = ParagraphChunker::new(40, 0).chunk(text);
+        assert_eq!(chunks, vec!["First paragraph.\n\nSecond paragraph.", "Third paragraph, which is rather longer."]);
+    }
+
+    #[test]
+    fn test_heading_chunker_prefixes_heading_path() {
+        let text = "# Part One\n\n## The City\n\nNeon everywhere.\n\n```\n# not a heading\n```\n\n## The Oraculators\n\nThey hum.";
+        let chunks = HeadingChunker::new(200, 0).chunk(text);
+
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].starts_with("# Part One\n## The City\n\nNeon everywhere."));
+        assert!(chunks[0].contains("# not a heading"));
+        assert_eq!(chunks[1], "# Part One\n## The Oraculators\n\nThey hum.");
+    }
+
+    #[test]
+    fn test_token_budget_respects_estimate() {
+        let text = "Short one. Another short sentence here. And a third sentence to finish.";
+        let chunks = TokenBudgetChunker::new(6, 0).chunk(text);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| estimate_tokens(chunk) <= 6));
+    }
+
+    #[test]
+    fn test_config_round_trips_through_chunker() {
+        let config = ChunkingConfig::new(ChunkingStrategy::Heading, 512, 64);
+        assert_eq!(config.chunker().config(), config);
+        assert_eq!(ChunkingStrategy::from_name(config.strategy.as_str()), Some(ChunkingStrategy::Heading));
+    }
+
+    #[test]
+    fn test_provenance_finds_pages_and_sections() {
+        let text = "# Chapter 3\nThe General's orchids.\nRain on the\nwindow. A gun in the drawer.";
+        let page_break = text.find("window").unwrap();
+        let segments = vec![
+            DocumentSegment::page(0, page_break, 40),
+            DocumentSegment::page(page_break, text.chars().count(), 41),
+            DocumentSegment::section(0, text.chars().count(), "Chapter 3"),
+        ];
+        // Heading chunks carry their section's headings, with whitespace normalised
+        let chunks = vec![
+            "# Chapter 3\n\nThe General's orchids. Rain on the".to_string(),
+            "# Chapter 3\n\nwindow. A gun in the drawer.".to_string(),
+        ];
+        let provenance = chunk_provenance(text, &chunks, &segments);
+
+        assert_eq!(provenance.len(), 2);
+        assert!(provenance.iter().all(|found| found.section.as_deref() == Some("Chapter 3")));
+        assert_eq!((provenance[0].start_offset, provenance[0].page), (Some(0), Some(40)));
+        let last = &provenance[1];
+        assert_eq!(last.page, Some(41));
+        let (start, end) = (last.start_offset.unwrap(), last.end_offset.unwrap());
+        assert_eq!(text.chars().skip(start).take(end - start).collect::<String>(), "window. A gun in the drawer.");
+        assert_eq!(chunk_provenance(text, &["not in the text".to_string()], &segments)[0], ChunkProvenance::default());
+    }
+}
@@ -0,0 +1,475 @@
+#![allow(dead_code)]
+// src/collections.rs
+//
+// Tags group documents into collections ("noir-reference", "chapter-drafts"). A search
+// can be limited to documents carrying given tags, and a whole collection can be paused
+// or unpaused at once. Retrieval presets name a combination of collections to switch
+// on and off together, so moving from drafting to research is one action rather than
+// toggling documents one by one. Tag names are trimmed and compared case-insensitively.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::Local;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::document_store::DocumentStore;
+
+/// A tag with how many documents carry it
+#[derive(Debug, Clone, Serialize)]
+pub struct TagInfo {
+    pub name: String,
+    pub documents: usize,
+    /// Of those documents, how many are paused
+    pub paused_documents: usize,
+}
+
+/// Collections to switch on and off together
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RetrievalPreset {
+    pub name: String,
+    /// Documents with any of these tags are unpaused
+    #[serde(default)]
+    pub active_tags: Vec<String>,
+    /// Documents with any of these tags are paused, unless they also carry an active tag
+    #[serde(default)]
+    pub paused_tags: Vec<String>,
+    /// Pause every document outside the active collections, tagged or not
+    #[serde(default)]
+    pub pause_others: bool,
+    #[serde(default)]
+    pub applied_at: Option<String>,
+}
+
+/// Documents whose paused flag changed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PauseChanges {
+    pub paused: Vec<i64>,
+    pub unpaused: Vec<i64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CollectionError {
+    #[error("Tag names can't be empty")]
+    EmptyTagName,
+    #[error("Preset names can't be empty")]
+    EmptyPresetName,
+    #[error("Tag {0} not found")]
+    TagNotFound(String),
+    #[error("Retrieval preset {0} not found")]
+    PresetNotFound(String),
+}
+
+pub(crate) fn initialize_collection_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS tags
+        (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+        created_at TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS document_tags
+        (
+        doc_id INTEGER NOT NULL,
+        tag_id INTEGER NOT NULL,
+        PRIMARY KEY (doc_id, tag_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_document_tags_tag ON document_tags(tag_id);
+        CREATE TABLE IF NOT EXISTS retrieval_presets
+        (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+        active_tags JSON,
+        paused_tags JSON,
+        pause_others BOOLEAN DEFAULT 0,
+        created_at TEXT NOT NULL,
+        applied_at TEXT
+        );",
+    )?;
+    Ok(())
+}
+
+/// Trimmed tag names with blanks and case-insensitive repeats dropped
+fn clean_tag_names(tags: &[String]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    tags.iter()
+    .map(|tag| tag.trim().to_string())
+    .filter(|tag| !tag.is_empty() && seen.insert(tag.to_lowercase()))
+    .collect()
+}
+
+fn placeholders(count: usize) -> String {
+    (1..=count).map(|i| format!("?{}", i)).collect::<Vec<_>>().join(", ")
+}
+
+/// The tag's id, creating the tag if the canon doesn't have it yet
+pub(crate) fn ensure_tag(conn: &Connection, name: &str) -> Result<i64, rusqlite::Error> {
+    conn.execute(
+        "INSERT OR IGNORE INTO tags (name, created_at) VALUES (?1, ?2)",
+        params![name, Local::now().to_rfc3339()],
+    )?;
+    conn.query_row("SELECT id FROM tags WHERE name = ?1", params![name], |row| row.get(0))
+}
+
+/// Ids of documents carrying any of `tags`, in id order
+pub(crate) fn documents_tagged(conn: &Connection, tags: &[String]) -> Result<Vec<i64>, rusqlite::Error> {
+    let tags = clean_tag_names(tags);
+    if tags.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare(&format!(
+        "SELECT DISTINCT dt.doc_id FROM document_tags dt
+        JOIN tags t ON t.id = dt.tag_id
+        WHERE t.name IN ({})
+        ORDER BY dt.doc_id",
+        placeholders(tags.len())
+    ))?;
+    let doc_ids = stmt.query_map(params_from_iter(tags.iter()), |row| row.get(0))?;
+    doc_ids.collect()
+}
+
+/// Every document's tag names, by document id
+pub(crate) fn tags_by_document(conn: &Connection) -> Result<HashMap<i64, Vec<String>>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT dt.doc_id, t.name FROM document_tags dt JOIN tags t ON t.id = dt.tag_id ORDER BY t.name COLLATE NOCASE"
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+    let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+    for row in rows {
+        let (doc_id, name) = row?;
+        tags.entry(doc_id).or_default().push(name);
+    }
+    Ok(tags)
+}
+
+/// One document's tag names
+pub(crate) fn tags_of(conn: &Connection, doc_id: i64) -> Result<Vec<String>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT t.name FROM document_tags dt JOIN tags t ON t.id = dt.tag_id WHERE dt.doc_id = ?1 ORDER BY t.name COLLATE NOCASE"
+    )?;
+    let names = stmt.query_map(params![doc_id], |row| row.get(0))?;
+    names.collect()
+}
+
+/// Tags a document, creating tags as needed. Returns how many tags were new to it.
+pub(crate) fn tag_document(conn: &Connection, doc_id: i64, tags: &[String]) -> Result<usize, rusqlite::Error> {
+    let mut added = 0;
+    for tag in clean_tag_names(tags) {
+        let tag_id = ensure_tag(conn, &tag)?;
+        added += conn.execute(
+            "INSERT OR IGNORE INTO document_tags (doc_id, tag_id) VALUES (?1, ?2)",
+            params![doc_id, tag_id],
+        )?;
+    }
+    Ok(added)
+}
+
+/// Sets the paused flag on documents, returning the ids whose flag actually changed
+fn set_paused(conn: &Connection, doc_ids: &[i64], paused: bool) -> Result<Vec<i64>, rusqlite::Error> {
+    let mut changed = Vec::new();
+    for &doc_id in doc_ids {
+        let updated = conn.execute(
+            "UPDATE documents SET paused = ?1 WHERE id = ?2 AND COALESCE(paused, 0) != ?1",
+            params![paused, doc_id],
+        )?;
+        if updated > 0 {
+            changed.push(doc_id);
+        }
+    }
+    Ok(changed)
+}
+
+fn preset_from_row(row: &rusqlite::Row) -> rusqlite::Result<RetrievalPreset> {
+    let active_tags: Option<String> = row.get(1)?;
+    let paused_tags: Option<String> = row.get(2)?;
+    Ok(RetrievalPreset {
+        name: row.get(0)?,
+        active_tags: active_tags.and_then(|tags| serde_json::from_str(&tags).ok()).unwrap_or_default(),
+        paused_tags: paused_tags.and_then(|tags| serde_json::from_str(&tags).ok()).unwrap_or_default(),
+        pause_others: row.get::<_, Option<bool>>(3)?.unwrap_or(false),
+        applied_at: row.get(4)?,
+    })
+}
+
+const PRESET_COLUMNS: &str = "name, active_tags, paused_tags, pause_others, applied_at";
+
+impl DocumentStore {
+    /// Adds tags to documents. Returns how many document/tag pairs were new.
+    pub async fn tag_documents(&self, doc_ids: &[i64], tags: &[String]) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        if clean_tag_names(tags).is_empty() {
+            return Err(CollectionError::EmptyTagName.into());
+        }
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        let mut added = 0;
+        for &doc_id in doc_ids {
+            added += tag_document(&tx, doc_id, tags)?;
+        }
+        tx.commit()?;
+        Ok(added)
+    }
+
+    /// Removes tags from documents. Tags left on no document stay until deleted.
+    pub async fn untag_documents(&self, doc_ids: &[i64], tags: &[String]) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        let mut removed = 0;
+        for tag in clean_tag_names(tags) {
+            for &doc_id in doc_ids {
+                removed += tx.execute(
+                    "DELETE FROM document_tags WHERE doc_id = ?1 AND tag_id = (SELECT id FROM tags WHERE name = ?2)",
+                    params![doc_id, tag],
+                )?;
+            }
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    pub async fn list_tags(&self) -> Result<Vec<TagInfo>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT t.name, COUNT(d.id), COALESCE(SUM(CASE WHEN d.paused THEN 1 ELSE 0 END), 0)
+            FROM tags t
+            LEFT JOIN document_tags dt ON dt.tag_id = t.id
+            LEFT JOIN documents d ON d.id = dt.doc_id
+            GROUP BY t.id
+            ORDER BY t.name COLLATE NOCASE",
+        )?;
+        let tags = stmt
+        .query_map([], |row| Ok(TagInfo {
+            name: row.get(0)?,
+            documents: row.get::<_, i64>(1)? as usize,
+            paused_documents: row.get::<_, i64>(2)? as usize,
+        }))?
+        .collect::<Result<Vec<_>, _>>()?;
+        Ok(tags)
+    }
+
+    pub async fn document_tags(&self, doc_id: i64) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        Ok(tags_of(&conn, doc_id)?)
+    }
+
+    /// Renames a tag. Renaming onto an existing tag merges the two.
+    pub async fn rename_tag(&self, name: &str, new_name: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let new_name = new_name.trim();
+        if new_name.is_empty() {
+            return Err(CollectionError::EmptyTagName.into());
+        }
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        let tag_id: i64 = tx
+        .query_row("SELECT id FROM tags WHERE name = ?1", params![name.trim()], |row| row.get(0))
+        .optional()?
+        .ok_or_else(|| CollectionError::TagNotFound(name.to_string()))?;
+        let existing: Option<i64> = tx
+        .query_row("SELECT id FROM tags WHERE name = ?1 AND id != ?2", params![new_name, tag_id], |row| row.get(0))
+        .optional()?;
+        match existing {
+            Some(existing) => {
+                tx.execute(
+                    "INSERT OR IGNORE INTO document_tags (doc_id, tag_id) SELECT doc_id, ?1 FROM document_tags WHERE tag_id = ?2",
+                    params![existing, tag_id],
+                )?;
+                tx.execute("DELETE FROM document_tags WHERE tag_id = ?1", params![tag_id])?;
+                tx.execute("DELETE FROM tags WHERE id = ?1", params![tag_id])?;
+            }
+            None => {
+                tx.execute("UPDATE tags SET name = ?1 WHERE id = ?2", params![new_name, tag_id])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Deletes a tag from the canon. Its documents stay; presets naming it just match nothing.
+    pub async fn delete_tag(&self, name: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM document_tags WHERE tag_id IN (SELECT id FROM tags WHERE name = ?1)", params![name.trim()])?;
+        let deleted = tx.execute("DELETE FROM tags WHERE name = ?1", params![name.trim()])?;
+        if deleted == 0 {
+            return Err(CollectionError::TagNotFound(name.to_string()).into());
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Pauses or unpauses every document in a collection
+    pub async fn set_collection_paused(&self, tag: &str, paused: bool) -> Result<PauseChanges, Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        let exists: bool = tx.query_row("SELECT EXISTS(SELECT 1 FROM tags WHERE name = ?1)", params![tag.trim()], |row| row.get(0))?;
+        if !exists {
+            return Err(CollectionError::TagNotFound(tag.to_string()).into());
+        }
+        let doc_ids = documents_tagged(&tx, &[tag.to_string()])?;
+        let changed = set_paused(&tx, &doc_ids, paused)?;
+        tx.commit()?;
+        Ok(if paused {
+            PauseChanges { paused: changed, unpaused: Vec::new() }
+        } else {
+            PauseChanges { paused: Vec::new(), unpaused: changed }
+        })
+    }
+
+    /// Saves a preset, replacing any preset with the same name
+    pub async fn save_retrieval_preset(&self, preset: &RetrievalPreset) -> Result<RetrievalPreset, Box<dyn std::error::Error + Send + Sync>> {
+        let name = preset.name.trim();
+        if name.is_empty() {
+            return Err(CollectionError::EmptyPresetName.into());
+        }
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO retrieval_presets (name, active_tags, paused_tags, pause_others, created_at) VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(name) DO UPDATE SET active_tags = excluded.active_tags, paused_tags = excluded.paused_tags,
+            pause_others = excluded.pause_others",
+            params![
+                name,
+                serde_json::to_string(&clean_tag_names(&preset.active_tags))?,
+                serde_json::to_string(&clean_tag_names(&preset.paused_tags))?,
+                preset.pause_others,
+                Local::now().to_rfc3339()
+            ],
+        )?;
+        Ok(conn.query_row(
+            &format!("SELECT {} FROM retrieval_presets WHERE name = ?1", PRESET_COLUMNS),
+            params![name],
+            preset_from_row,
+        )?)
+    }
+
+    pub async fn list_retrieval_presets(&self) -> Result<Vec<RetrievalPreset>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM retrieval_presets ORDER BY name COLLATE NOCASE", PRESET_COLUMNS))?;
+        let presets = stmt.query_map([], preset_from_row)?.collect::<Result<Vec<_>, _>>()?;
+        Ok(presets)
+    }
+
+    pub async fn delete_retrieval_preset(&self, name: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        if conn.execute("DELETE FROM retrieval_presets WHERE name = ?1", params![name.trim()])? == 0 {
+            return Err(CollectionError::PresetNotFound(name.to_string()).into());
+        }
+        Ok(())
+    }
+
+    /// Pauses and unpauses documents as a preset describes, in one transaction
+    pub async fn apply_retrieval_preset(&self, name: &str) -> Result<PauseChanges, Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        let preset = tx
+        .query_row(
+            &format!("SELECT {} FROM retrieval_presets WHERE name = ?1", PRESET_COLUMNS),
+            params![name.trim()],
+            preset_from_row,
+        )
+        .optional()?
+        .ok_or_else(|| CollectionError::PresetNotFound(name.to_string()))?;
+
+        let active = documents_tagged(&tx, &preset.active_tags)?;
+        let active_set: HashSet<i64> = active.iter().copied().collect();
+        let to_pause: Vec<i64> = if preset.pause_others {
+            let mut stmt = tx.prepare("SELECT id FROM documents ORDER BY id")?;
+            let all = stmt.query_map([], |row| row.get::<_, i64>(0))?.collect::<Result<Vec<_>, _>>()?;
+            all.into_iter().filter(|doc_id| !active_set.contains(doc_id)).collect()
+        } else {
+            documents_tagged(&tx, &preset.paused_tags)?
+            .into_iter()
+            .filter(|doc_id| !active_set.contains(doc_id))
+            .collect()
+        };
+
+        let changes = PauseChanges {
+            paused: set_paused(&tx, &to_pause, true)?,
+            unpaused: set_paused(&tx, &active, false)?,
+        };
+        tx.execute(
+            "UPDATE retrieval_presets SET applied_at = ?1 WHERE name = ?2",
+            params![Local::now().to_rfc3339(), preset.name],
+        )?;
+        tx.commit()?;
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document_store::insert_test_document;
+
+    fn add_document(store: &DocumentStore, id: i64, name: &str) {
+        insert_test_document(&store.conn.blocking_lock(), id, name, "test-model");
+    }
+
+    fn paused(store: &DocumentStore, id: i64) -> bool {
+        let conn = store.conn.blocking_lock();
+        conn.query_row("SELECT paused FROM documents WHERE id = ?1", params![id], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_tags_are_trimmed_and_case_insensitive() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DocumentStore::new(dir.path().to_path_buf()).unwrap();
+        add_document(&store, 1, "the-big-sleep.md");
+        add_document(&store, 2, "red-harvest.md");
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let added = runtime.block_on(store.tag_documents(&[1, 2], &[" noir-reference ".to_string(), "Noir-Reference".to_string()])).unwrap();
+        assert_eq!(added, 2);
+        runtime.block_on(store.tag_documents(&[1], &["chandler".to_string()])).unwrap();
+
+        let tags = runtime.block_on(store.list_tags()).unwrap();
+        let counts: Vec<(&str, usize)> = tags.iter().map(|tag| (tag.name.as_str(), tag.documents)).collect();
+        assert_eq!(counts, vec![("chandler", 1), ("noir-reference", 2)]);
+
+        {
+            let conn = store.conn.blocking_lock();
+            assert_eq!(documents_tagged(&conn, &["NOIR-reference".to_string()]).unwrap(), vec![1, 2]);
+        }
+        runtime.block_on(store.untag_documents(&[2], &["noir-reference".to_string()])).unwrap();
+        assert_eq!(runtime.block_on(store.document_tags(2)).unwrap(), Vec::<String>::new());
+
+        runtime.block_on(store.rename_tag("chandler", "noir-reference")).unwrap();
+        assert_eq!(runtime.block_on(store.document_tags(1)).unwrap(), vec!["noir-reference".to_string()]);
+    }
+
+    #[test]
+    fn test_presets_switch_collections_in_one_step() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DocumentStore::new(dir.path().to_path_buf()).unwrap();
+        for (id, name) in [(1, "the-big-sleep.md"), (2, "chapter-1.md"), (3, "both.md"), (4, "untagged.md")] {
+            add_document(&store, id, name);
+        }
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(store.tag_documents(&[1, 3], &["noir-reference".to_string()])).unwrap();
+        runtime.block_on(store.tag_documents(&[2, 3], &["drafts".to_string()])).unwrap();
+
+        runtime.block_on(store.save_retrieval_preset(&RetrievalPreset {
+            name: "research".to_string(),
+            active_tags: vec!["noir-reference".to_string()],
+            paused_tags: vec!["drafts".to_string()],
+            ..Default::default()
+        })).unwrap();
+        let changes = runtime.block_on(store.apply_retrieval_preset("Research")).unwrap();
+        assert_eq!(changes.paused, vec![2]);
+        assert!(changes.unpaused.is_empty());
+        assert!(!paused(&store, 3), "an active tag wins over a paused one");
+        assert!(!paused(&store, 4));
+
+        runtime.block_on(store.save_retrieval_preset(&RetrievalPreset {
+            name: "drafting".to_string(),
+            active_tags: vec!["drafts".to_string()],
+            pause_others: true,
+            ..Default::default()
+        })).unwrap();
+        let changes = runtime.block_on(store.apply_retrieval_preset("drafting")).unwrap();
+        assert_eq!(changes.paused, vec![1, 4]);
+        assert_eq!(changes.unpaused, vec![2]);
+
+        let changes = runtime.block_on(store.set_collection_paused("noir-reference", false)).unwrap();
+        assert_eq!(changes.unpaused, vec![1]);
+        assert_eq!(runtime.block_on(store.list_retrieval_presets()).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,296 @@
+#![allow(dead_code)]
+// src/document_metadata.rs
+//
+// What an ingestor knew about a document's source: its type, author, dates and any
+// frontmatter, kept one row per document in `document_metadata`. Searches can be
+// narrowed by it, say to EPUB sources by Le Guin written before 1975.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::ingest::frontmatter::pod_to_json;
+use crate::ingest::DocumentMetadata;
+
+/// A document's source metadata as stored in the canon
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SourceMetadata {
+    pub source_type: Option<String>,
+    pub author: Option<String>,
+    pub created_date: Option<String>,
+    pub modified_date: Option<String>,
+    /// Frontmatter or other source fields, as a JSON object
+    #[serde(default)]
+    pub frontmatter: serde_json::Value,
+}
+
+/// Narrows a search to documents whose metadata matches every field given
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetadataFilter {
+    /// Source types such as "epub", "pdf" or "markdown"; any of them matches
+    pub source_types: Vec<String>,
+    /// Names matched case-insensitively within the document's authors; any of them matches
+    pub authors: Vec<String>,
+    /// Dates such as "1975" or "1975-06-01". A document dated within the given year
+    /// or month is neither before nor after it.
+    pub created_before: Option<String>,
+    pub created_after: Option<String>,
+}
+
+impl MetadataFilter {
+    pub fn is_empty(&self) -> bool {
+        self.source_types.is_empty() && self.authors.is_empty() && self.created_before.is_none() && self.created_after.is_none()
+    }
+}
+
+impl From<&DocumentMetadata> for SourceMetadata {
+    fn from(metadata: &DocumentMetadata) -> Self {
+        Self {
+            source_type: Some(metadata.source_type.clone()).filter(|source_type| !source_type.is_empty()),
+            author: metadata.author.clone(),
+            created_date: metadata.created_date.clone(),
+            modified_date: metadata.modified_date.clone(),
+            frontmatter: serde_json::Value::Object(
+                metadata.frontmatter.iter().map(|(key, value)| (key.clone(), pod_to_json(value))).collect(),
+            ),
+        }
+    }
+}
+
+pub(crate) fn initialize_metadata_table(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS document_metadata
+        (
+        doc_id INTEGER PRIMARY KEY,
+        source_type TEXT,
+        author TEXT,
+        created_date TEXT,
+        modified_date TEXT,
+        frontmatter JSON
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// The source type an ingestor would have recorded for a path, for documents
+/// ingested before metadata was kept
+pub(crate) fn source_type_for_path(file_path: &str) -> Option<&'static str> {
+    if file_path.starts_with("http://") || file_path.starts_with("https://") {
+        return Some("URL");
+    }
+    let extension = Path::new(file_path).extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "pdf" => Some("pdf"),
+        "epub" => Some("epub"),
+        "md" => Some("markdown"),
+        "mdx" => Some("mdx"),
+        "txt" => Some("text"),
+        "mp3" | "wav" | "m4a" | "ogg" | "flac" => Some("audio"),
+        _ => None,
+    }
+}
+
+pub(crate) fn set_metadata(conn: &Connection, doc_id: i64, metadata: &SourceMetadata) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "INSERT INTO document_metadata (doc_id, source_type, author, created_date, modified_date, frontmatter)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        ON CONFLICT(doc_id) DO UPDATE SET source_type = excluded.source_type, author = excluded.author,
+        created_date = excluded.created_date, modified_date = excluded.modified_date, frontmatter = excluded.frontmatter",
+        params![
+            doc_id,
+            metadata.source_type,
+            metadata.author,
+            metadata.created_date,
+            metadata.modified_date,
+            metadata.frontmatter.to_string()
+        ],
+    )?;
+    Ok(())
+}
+
+fn from_row(row: &rusqlite::Row, first: usize) -> Result<SourceMetadata, rusqlite::Error> {
+    let frontmatter: Option<String> = row.get(first + 4)?;
+    Ok(SourceMetadata {
+        source_type: row.get(first)?,
+        author: row.get(first + 1)?,
+        created_date: row.get(first + 2)?,
+        modified_date: row.get(first + 3)?,
+        frontmatter: frontmatter.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or(serde_json::Value::Null),
+    })
+}
+
+pub(crate) fn metadata_of(conn: &Connection, doc_id: i64) -> Result<Option<SourceMetadata>, rusqlite::Error> {
+    conn.query_row(
+        "SELECT source_type, author, created_date, modified_date, frontmatter FROM document_metadata WHERE doc_id = ?1",
+        params![doc_id],
+        |row| from_row(row, 0),
+    )
+    .optional()
+}
+
+pub(crate) fn metadata_by_document(conn: &Connection) -> Result<HashMap<i64, SourceMetadata>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT doc_id, source_type, author, created_date, modified_date, frontmatter FROM document_metadata",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, from_row(row, 1)?)))?;
+    rows.collect()
+}
+
+/// The date part of a free-form date, so "1974-05-01T00:00:00Z", "1974-05-01" and
+/// "May 1974" compare sensibly: an ISO prefix when the text starts with a year,
+/// otherwise the first four-digit year in it
+fn comparable_date(text: &str) -> Option<String> {
+    let text = text.trim();
+    let leading: Vec<char> = text.chars().take(4).collect();
+    if leading.len() == 4 && leading.iter().all(|c| c.is_ascii_digit()) {
+        let date: String = text.chars().take(10).take_while(|c| c.is_ascii_digit() || *c == '-').collect();
+        return Some(date.trim_end_matches('-').to_string());
+    }
+    text
+    .split(|c: char| !c.is_ascii_digit())
+    .find(|digits| digits.len() == 4)
+    .map(|year| year.to_string())
+}
+
+fn is_before(date: &str, bound: &str) -> bool {
+    date < bound && !date.starts_with(bound)
+}
+
+fn is_after(date: &str, bound: &str) -> bool {
+    date > bound && !date.starts_with(bound)
+}
+
+/// Documents matching the filter. Documents without metadata only match a filter on
+/// authors, through the `documents.authors` column.
+pub(crate) fn documents_matching(conn: &Connection, filter: &MetadataFilter) -> Result<HashSet<i64>, rusqlite::Error> {
+    let source_types: Vec<String> = filter.source_types.iter().map(|source_type| source_type.to_lowercase()).collect();
+    let wanted_authors: Vec<String> = filter.authors.iter().map(|author| author.trim().to_lowercase()).filter(|author| !author.is_empty()).collect();
+    let before = filter.created_before.as_deref().and_then(comparable_date);
+    let after = filter.created_after.as_deref().and_then(comparable_date);
+
+    let mut stmt = conn.prepare(
+        "SELECT d.id, d.authors, m.source_type, m.author, m.created_date
+        FROM documents d LEFT JOIN document_metadata m ON m.doc_id = d.id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
+
+    let mut matching = HashSet::new();
+    for row in rows {
+        let (doc_id, authors_json, source_type, author, created_date) = row?;
+        if !source_types.is_empty() && !source_type.map_or(false, |source_type| source_types.contains(&source_type.to_lowercase())) {
+            continue;
+        }
+        if !wanted_authors.is_empty() {
+            let mut authors: Vec<String> = authors_json.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default();
+            authors.extend(author);
+            let authors: Vec<String> = authors.iter().map(|author| author.to_lowercase()).collect();
+            if !wanted_authors.iter().any(|wanted| authors.iter().any(|author| author.contains(wanted.as_str()))) {
+                continue;
+            }
+        }
+        if before.is_some() || after.is_some() {
+            let date = match created_date.as_deref().and_then(comparable_date) {
+                Some(date) => date,
+                None => continue,
+            };
+            if before.as_deref().map_or(false, |bound| !is_before(&date, bound)) || after.as_deref().map_or(false, |bound| !is_after(&date, bound)) {
+                continue;
+            }
+        }
+        matching.insert(doc_id);
+    }
+    Ok(matching)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document_store::{insert_test_document, DocumentStore};
+
+    fn add_document(conn: &Connection, id: i64, file_path: &str, authors: &str, metadata: Option<SourceMetadata>) {
+        insert_test_document(conn, id, file_path, "test-model");
+        conn.execute("UPDATE documents SET authors = ?2 WHERE id = ?1", params![id, authors]).unwrap();
+        if let Some(metadata) = metadata {
+            set_metadata(conn, id, &metadata).unwrap();
+        }
+    }
+
+    fn epub(author: &str, created_date: &str) -> SourceMetadata {
+        SourceMetadata {
+            source_type: Some("epub".to_string()),
+            author: Some(author.to_string()),
+            created_date: Some(created_date.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_filter_by_source_type_author_and_date() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DocumentStore::new(dir.path().join("metadata.canon")).unwrap();
+        let conn = store.conn.blocking_lock();
+        add_document(&conn, 1, "left_hand.epub", "[]", Some(epub("Ursula K. Le Guin", "1969-03-01")));
+        add_document(&conn, 2, "dispossessed.epub", "[]", Some(epub("Ursula K. Le Guin", "1974")));
+        add_document(&conn, 3, "always_coming_home.epub", "[]", Some(epub("Ursula K. Le Guin", "September 1985")));
+        add_document(&conn, 4, "lathe.pdf", "[\"Ursula K. Le Guin\"]", Some(SourceMetadata { source_type: Some("pdf".to_string()), ..epub("", "1971") }));
+        add_document(&conn, 5, "notes.md", "[\"Ursula Le Guin\"]", None);
+
+        let le_guin_epubs_before_1975 = MetadataFilter {
+            source_types: vec!["EPUB".to_string()],
+            authors: vec!["le guin".to_string()],
+            created_before: Some("1975".to_string()),
+            ..Default::default()
+        };
+        let mut found: Vec<i64> = documents_matching(&conn, &le_guin_epubs_before_1975).unwrap().into_iter().collect();
+        found.sort();
+        assert_eq!(found, vec![1, 2]);
+
+        let after_1974 = MetadataFilter { created_after: Some("1974".to_string()), ..Default::default() };
+        assert_eq!(documents_matching(&conn, &after_1974).unwrap(), HashSet::from([3]));
+        let by_author = MetadataFilter { authors: vec!["Le Guin".to_string()], ..Default::default() };
+        assert_eq!(documents_matching(&conn, &by_author).unwrap().len(), 5);
+
+        assert_eq!(metadata_of(&conn, 2).unwrap(), Some(epub("Ursula K. Le Guin", "1974")));
+        assert!(metadata_of(&conn, 5).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_matching_documents_are_searched_beyond_the_knn_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DocumentStore::new(dir.path().join("metadata_search.canon")).unwrap();
+        let conn = store.conn.blocking_lock();
+        add_document(&conn, 1, "notes.md", "[]", None);
+        add_document(&conn, 2, "dispossessed.epub", "[]", Some(epub("Ursula K. Le Guin", "1974")));
+        conn.execute_batch("BEGIN").unwrap();
+        for i in 0..DocumentStore::KNN_MAX_K + 10 {
+            DocumentStore::insert_chunk_embedding(&conn, 1, &format!("note {}", i), &[1.0, 0.1, 0.0], "test-model").unwrap();
+        }
+        conn.execute_batch("COMMIT").unwrap();
+        DocumentStore::insert_chunk_embedding(&conn, 2, "Anarres", &[0.0, 1.0, 0.0], "test-model").unwrap();
+
+        let epubs = MetadataFilter { source_types: vec!["epub".to_string()], ..Default::default() };
+        let matching = documents_matching(&conn, &epubs).unwrap();
+        let results = DocumentStore::knn_search_within(&conn, "test-model", &[1.0, 0.0, 0.0], 5, &matching).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].3, "Anarres");
+    }
+
+    #[test]
+    fn test_source_type_from_path() {
+        assert_eq!(source_type_for_path("/books/Dispossessed.EPUB"), Some("epub"));
+        assert_eq!(source_type_for_path("https://example.com/essay"), Some("URL"));
+        assert_eq!(source_type_for_path("/notes/README"), None);
+    }
+}
@@ -1,41 +1,135 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use walkdir::WalkDir;
-use futures::stream::{self, StreamExt};
+use glob::{MatchOptions, Pattern};
 
+use crate::ingest::{DocumentIngestor, IngestError, IngestedDocument, Resource};
+
+/// Walks a directory for files the registered ingestors can read, narrowed by
+/// include/exclude glob rules. Rules match paths relative to the scanned directory,
+/// so `*.md` or `drafts/**` mean the same thing wherever the folder lives.
 pub struct DocumentScanner {
-    ingestors: Vec<Box<dyn DocumentIngestor>>,
+    ingestors: Vec<Arc<Box<dyn DocumentIngestor>>>,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
 }
 
 impl DocumentScanner {
     pub fn new() -> Self {
         Self {
             ingestors: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    /// Builds a scanner over an existing ingestor registry
+    pub fn with_ingestors(ingestors: Vec<Arc<Box<dyn DocumentIngestor>>>) -> Self {
+        Self {
+            ingestors,
+            include: Vec::new(),
+            exclude: Vec::new(),
         }
     }
 
     pub fn register_ingestor(&mut self, ingestor: Box<dyn DocumentIngestor>) {
-        self.ingestors.push(ingestor);
+        self.ingestors.push(Arc::new(ingestor));
     }
 
-    pub async fn scan_directory(&self, dir: &Path) -> Result<Vec<IngestedDocument>, IngestError> {
-        let mut documents = Vec::new();
+    /// Sets the glob rules. With no include rules every file is a candidate;
+    /// an exclude rule wins over an include rule.
+    pub fn with_rules(mut self, include: &[String], exclude: &[String]) -> Result<Self, glob::PatternError> {
+        self.include = include.iter().map(|rule| Pattern::new(rule)).collect::<Result<_, _>>()?;
+        self.exclude = exclude.iter().map(|rule| Pattern::new(rule)).collect::<Result<_, _>>()?;
+        Ok(self)
+    }
 
+    /// Whether a path relative to the scanned directory passes the glob rules
+    pub fn matches_rules(&self, relative_path: &Path) -> bool {
+        let options = MatchOptions::new();
+        let included = self.include.is_empty()
+            || self.include.iter().any(|rule| rule.matches_path_with(relative_path, options));
+        included && !self.exclude.iter().any(|rule| rule.matches_path_with(relative_path, options))
+    }
+
+    /// Files under `dir` that pass the rules and that some ingestor can handle, in path order
+    pub fn scan_directory(&self, dir: &Path) -> Result<Vec<PathBuf>, IngestError> {
+        if !dir.is_dir() {
+            return Err(IngestError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} is not a directory", dir.display()),
+            )));
+        }
+
+        let mut files = Vec::new();
         for entry in WalkDir::new(dir).follow_links(true).into_iter().filter_map(|e| e.ok()) {
             if !entry.file_type().is_file() {
                 continue;
             }
 
             let path = entry.path();
-            
+            let relative_path = path.strip_prefix(dir).unwrap_or(path);
+            if !self.matches_rules(relative_path) {
+                continue;
+            }
+
+            let resource = Resource::FilePath(path.to_path_buf());
+            if self.ingestors.iter().any(|i| i.can_handle(&resource)) {
+                files.push(path.to_path_buf());
+            }
+        }
+        files.sort();
+
+        Ok(files)
+    }
+
+    /// Scans `dir` and ingests every matching file, skipping (and logging) files that fail
+    pub async fn ingest_directory(&self, dir: &Path) -> Result<Vec<IngestedDocument>, IngestError> {
+        let mut documents = Vec::new();
+
+        for path in self.scan_directory(dir)? {
+            let resource = Resource::FilePath(path.clone());
             // Find appropriate ingestor
-            if let Some(ingestor) = self.ingestors.iter().find(|i| i.can_handle(path)) {
-                match ingestor.ingest_file(path).await {
+            if let Some(ingestor) = self.ingestors.iter().find(|i| i.can_handle(&resource)) {
+                match ingestor.ingest(&resource).await {
                     Ok(doc) => documents.push(doc),
-                    Err(e) => eprintln!("Failed to ingest {}: {}", path.display(), e),
+                    Err(e) => log::warn!("Failed to ingest {}: {}", path.display(), e),
                 }
             }
         }
 
         Ok(documents)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingest::{MarkdownIngestor, TextIngestor};
+
+    #[test]
+    fn test_scan_applies_rules_and_ingestors() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("drafts")).unwrap();
+        std::fs::write(dir.path().join("chapter-1.md"), "# One").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "notes").unwrap();
+        std::fs::write(dir.path().join("drafts/chapter-2.md"), "# Two").unwrap();
+        std::fs::write(dir.path().join("cover.png"), [0u8; 4]).unwrap();
+
+        let mut scanner = DocumentScanner::new();
+        scanner.register_ingestor(Box::new(MarkdownIngestor));
+        scanner.register_ingestor(Box::new(TextIngestor));
+
+        // No ingestor reads PNGs
+        let files = scanner.scan_directory(dir.path()).unwrap();
+        assert_eq!(files.len(), 3);
+
+        let scanner = scanner
+            .with_rules(&["*.md".to_string()], &["drafts/**".to_string()])
+            .unwrap();
+        let files = scanner.scan_directory(dir.path()).unwrap();
+        assert_eq!(files, vec![dir.path().join("chapter-1.md")]);
+
+        assert!(DocumentScanner::new().with_rules(&["[".to_string()], &[]).is_err());
+    }
+}
@@ -1,20 +1,23 @@
 use async_openai::types::AudioInput;
 // src/document_store.rs
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use std::path;
 use std::path::PathBuf;
 use chrono::Local; 
 use serde_json;
-use crate::ingest::{DocumentIngestor, IngestedDocument};
+use crate::ingest::{DocumentIngestor, DocumentMetadata, DocumentSegment, IngestedDocument};
 use std::path::Path;
 use async_trait::async_trait;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
+use std::time::Duration;
+use futures::stream::{self, StreamExt};
+use sha2::{Digest, Sha256};
 use crate::ai::traits::{EmbeddingProvider, PreferredEmbeddingModel, ChatCompletionProvider};
 use crate::ingest::{
-    pdf_ingestor::PdfIngestor,
+    pdf_ingestor::{PdfEngine, PdfIngestor},
     mdx_ingestor::MdxIngestor,
     markdown_ingestor::MarkdownIngestor,
     epub_ingestor::EpubIngestor,
@@ -32,6 +35,11 @@ use serde_json::json;
 use tokio::runtime::Handle;
 use log::{SetLoggerError, LevelFilter, info};
 use crate::ingest::Resource;
+use crate::ingestion_queue::{self, JobCheckpoint, IngestionJobError};
+use crate::migrations;
+use crate::collections;
+use crate::document_metadata::{self, MetadataFilter, SourceMetadata};
+use crate::chunking::{self, ChunkProvenance, ChunkingConfig, ChunkingStrategy};
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Document {
@@ -69,7 +77,15 @@ pub struct DocumentInfo {
     pub embedding_model_name: String,
     pub notes: String,
     pub authors: Vec<String>,
-    
+    /// How the document was chunked; None for documents ingested before this was recorded
+    pub chunking: Option<ChunkingConfig>,
+    pub tags: Vec<String>,
+    /// Multiplier on the document's search scores
+    pub weight: f32,
+    /// Whether the document's best chunk is always retrieved
+    pub pinned: bool,
+    /// What the ingestor recorded about the source; None for documents ingested before it was kept
+    pub metadata: Option<SourceMetadata>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -80,20 +96,288 @@ pub struct DocumentChunk {
     pub embedding_model_name: String,
 }
 
+/// What the canon remembers about a document's source, for deciding whether
+/// re-ingesting it has anything to do
+#[derive(Debug, Clone)]
+pub(crate) struct StoredSource {
+    pub id: i64,
+    pub file_path: String,
+    pub content_hash: Option<String>,
+    pub source_modified_at: Option<String>,
+    /// Version of the ingestor's text extraction that read it; `None` before versions
+    /// were recorded, which counts as 1
+    pub extraction_version: Option<u32>,
+}
+
+/// How a document's freshly chunked text lines up with the chunks already stored for it
+#[derive(Debug, Default)]
+struct ChunkPlan {
+    /// (chunk index, row) for unchanged chunks that already have a vector for the model
+    reused: Vec<(usize, i64)>,
+    /// (chunk index, existing row lacking a vector for the model) for chunks to embed
+    to_embed: Vec<(usize, Option<i64>)>,
+    /// Rows whose text no longer appears in the document
+    stale: Vec<i64>,
+}
+
+/// Controls how ingestion sends chunks to the embedding provider
+#[derive(Debug, Clone)]
+pub struct EmbeddingBatchConfig {
+    /// Chunks per embedding request
+    pub batch_size: usize,
+    /// Embedding requests allowed in flight at once
+    pub max_concurrent_batches: usize,
+    /// Retries per batch after a rate limit response
+    pub max_retries: u32,
+    /// Wait before the first retry; doubles on each subsequent one
+    pub initial_backoff: Duration,
+}
+
+impl Default for EmbeddingBatchConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: crate::preferences::Preferences::EMBEDDING_BATCH_SIZE_DEFAULT,
+            max_concurrent_batches: crate::preferences::Preferences::EMBEDDING_CONCURRENCY_DEFAULT,
+            max_retries: 5,
+            initial_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl EmbeddingBatchConfig {
+    pub fn from_preferences(preferences: &crate::preferences::Preferences) -> Self {
+        let default = Self::default();
+        Self {
+            batch_size: if preferences.embedding_batch_size == 0 { default.batch_size } else { preferences.embedding_batch_size },
+            max_concurrent_batches: if preferences.embedding_concurrency == 0 {
+                default.max_concurrent_batches
+            } else {
+                preferences.embedding_concurrency
+            },
+            ..default
+        }
+    }
+}
+
+/// How `DocumentStore::search` ranks chunks
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SearchMode {
+    /// Cosine similarity over embeddings only
+    Vector,
+    /// Vector and FTS5 keyword ranks fused with reciprocal rank fusion
+    Hybrid,
+}
+
+impl SearchMode {
+    pub fn from_preference(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "hybrid" => SearchMode::Hybrid,
+            _ => SearchMode::Vector,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchMode::Vector => "vector",
+            SearchMode::Hybrid => "hybrid",
+        }
+    }
+}
+
+/// Retrieval settings that come from Preferences rather than the call site
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub mode: SearchMode,
+    /// Share of the fused rank given to keyword matches, 0.0 to 1.0
+    pub lexical_weight: f32,
+    /// Maximal marginal relevance trade-off: 1.0 ranks by relevance alone, lower values
+    /// increasingly penalise chunks that repeat what's already been picked
+    pub mmr_lambda: f32,
+    /// Only search documents carrying one of these tags; empty searches every document
+    pub tags: Vec<String>,
+    /// Only search documents whose source metadata matches
+    pub metadata: MetadataFilter,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            mode: SearchMode::Vector,
+            lexical_weight: crate::preferences::Preferences::LEXICAL_WEIGHT_DEFAULT,
+            mmr_lambda: crate::preferences::Preferences::MMR_LAMBDA_DEFAULT,
+            tags: Vec::new(),
+            metadata: MetadataFilter::default(),
+        }
+    }
+}
+
+impl SearchOptions {
+    pub fn from_preferences(preferences: &crate::preferences::Preferences) -> Self {
+        Self {
+            mode: SearchMode::from_preference(&preferences.search_mode),
+            lexical_weight: preferences.lexical_weight.unwrap_or(crate::preferences::Preferences::LEXICAL_WEIGHT_DEFAULT).clamp(0.0, 1.0),
+            mmr_lambda: preferences.mmr_lambda.unwrap_or(crate::preferences::Preferences::MMR_LAMBDA_DEFAULT).clamp(0.0, 1.0),
+            tags: preferences.search_tags.clone(),
+            metadata: preferences.search_metadata_filter.clone(),
+        }
+    }
+}
+
+/// What a canon records about one embedding model's vectors
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingModelInfo {
+    pub embedding_model_name: String,
+    #[serde(skip)]
+    pub(crate) table_name: String,
+    pub dimension: usize,
+    /// Whether the model returns unit-length vectors; None for an index with no vectors to inspect
+    pub normalized: Option<bool>,
+    /// Provider that produced the first vectors, when known
+    pub provider: Option<String>,
+    pub created_at: Option<String>,
+}
+
+/// Vectors that don't fit what the canon recorded for their model. Raised instead of
+/// storing them or returning scores computed from mismatched vectors.
+#[derive(Debug, thiserror::Error)]
+pub enum EmbeddingMetadataError {
+    #[error("Embedding model {embedding_model_name} is indexed with {expected} dimensions but got a {actual}-dimension vector")]
+    DimensionMismatch {
+        embedding_model_name: String,
+        expected: usize,
+        actual: usize,
+    },
+    
+    #[error("Embedding model {embedding_model_name} stores unit-length vectors but got one of length {norm}; a different model may be using the same name")]
+    NormalizationMismatch {
+        embedding_model_name: String,
+        norm: f32,
+    },
+}
+
+/// How much of a canon one embedding model covers
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingCoverage {
+    pub embedding_model_name: String,
+    pub dimension: usize,
+    pub normalized: Option<bool>,
+    pub provider: Option<String>,
+    pub created_at: Option<String>,
+    /// Chunks with a vector for this model
+    pub embedded_chunks: usize,
+    /// All chunks in the canon
+    pub total_chunks: usize,
+}
+
+/// The canon's vectors were made by other embedding models than the one preferences select,
+/// so searches with the preferred model would come back empty
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingModelMismatch {
+    /// The model recorded when the canon was first embedded
+    pub canon_model: String,
+    pub preferred_model: String,
+    /// Every model the canon holds vectors for
+    pub indexed_models: Vec<String>,
+}
+
+impl fmt::Display for EmbeddingModelMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "This canon was embedded with {} but preferences select {}. Choose one of [{}] or re-embed the canon.",
+            self.canon_model,
+            self.preferred_model,
+            self.indexed_models.join(", ")
+        )
+    }
+}
+
+/// One chunk returned by `DocumentStore::search`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub doc_id: i64,
+    pub doc_name: String,
+    pub chunk_id: usize,
+    pub chunk: String,
+    /// Cosine similarity between the query and the chunk embedding
+    pub similarity: f32,
+    /// BM25 relevance from the FTS5 index (higher is better); None when the chunk had no keyword match
+    pub lexical_score: Option<f32>,
+    /// Reciprocal rank fusion score; set in hybrid mode only
+    pub fused_score: Option<f32>,
+    /// The canon the chunk came from
+    #[serde(default)]
+    pub canon_name: String,
+    /// Weight of that canon when searching several at once
+    #[serde(default = "default_canon_weight")]
+    pub canon_weight: f32,
+    /// The document's own retrieval weight
+    #[serde(default = "default_canon_weight")]
+    pub doc_weight: f32,
+    /// The document is pinned, so this chunk was included whatever its score
+    #[serde(default)]
+    pub pinned: bool,
+    /// Where the chunk sits in its document
+    #[serde(default)]
+    pub provenance: ChunkProvenance,
+    /// "Author, Title, Section, p. N", leaving out whatever isn't known
+    #[serde(default)]
+    pub citation: String,
+}
+
+fn default_canon_weight() -> f32 {
+    1.0
+}
+
+/// "Raymond Chandler, The Big Sleep, Chapter 3, p. 41"
+fn citation(authors: &[String], title: &str, provenance: &ChunkProvenance) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if !authors.is_empty() {
+        parts.push(authors.join(" & "));
+    }
+    parts.push(title.to_string());
+    if let Some(section) = provenance.section.as_ref().filter(|section| !section.is_empty() && section.as_str() != title) {
+        parts.push(section.clone());
+    }
+    if let Some(page) = provenance.page {
+        parts.push(format!("p. {}", page));
+    }
+    parts.join(", ")
+}
+
+impl SearchHit {
+    /// The score results are ordered by. Weights scale the ranking only; the
+    /// similarity threshold still applies to the unweighted similarity.
+    pub fn rank_score(&self) -> f32 {
+        self.fused_score.unwrap_or(self.similarity) * self.doc_weight * self.canon_weight
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DocumentStore {
-    conn: Arc<Mutex<Connection>>, // Change to tokio Mutex
+    pub(crate) conn: Arc<Mutex<Connection>>, // Change to tokio Mutex
     ingestors: Vec<Arc<Box<dyn DocumentIngestor>>>,
     next_id: usize,
     //embedding_generator: Arc<EmbeddingGenerator>,
     canon_name: String,
     canon_path: String,
+    embedding_batch_config: EmbeddingBatchConfig,
+    chunking_config: ChunkingConfig,
 }
 
 
 impl DocumentStore {
-    pub const DEFAULT_CHUNK_SIZE: usize = 1024;
-    pub const DEFAULT_CHUNK_OVERLAP: usize = 200;
+    /// KNN candidates fetched per requested result, to leave room for paused docs and de-duplication
+    pub const KNN_OVERFETCH: usize = 8;
+    pub const KNN_MIN_CANDIDATES: usize = 32;
+    /// sqlite-vec's upper bound on k
+    pub const KNN_MAX_K: usize = 4096;
+    /// Damping constant for reciprocal rank fusion; 60 is the usual choice
+    pub const RRF_K: f32 = 60.0;
+    /// Query terms passed to FTS5, to keep long contexts from building huge MATCH expressions
+    pub const FTS_MAX_TERMS: usize = 64;
+    /// How far a vector's length may stray from 1.0 and still count as normalized
+    pub const UNIT_LENGTH_TOLERANCE: f32 = 1e-3;
     
     pub fn new(
         store_path: PathBuf,
@@ -112,11 +396,13 @@ impl DocumentStore {
             //embedding_generator,
             canon_path,
             canon_name,
+            embedding_batch_config: EmbeddingBatchConfig::default(),
+            chunking_config: ChunkingConfig::default(),
         };        
         
         doc_store.register_ingestor(Box::new(MdxIngestor));
         
-        doc_store.register_ingestor(Box::new(PdfIngestor));
+        doc_store.register_ingestor(Box::new(PdfIngestor::default()));
         doc_store.register_ingestor(Box::new(MarkdownIngestor));
         doc_store.register_ingestor(Box::new(EpubIngestor));
         doc_store.register_ingestor(Box::new(TextIngestor));
@@ -127,6 +413,54 @@ impl DocumentStore {
         Ok(doc_store)
     }
     
+    /// Opens an existing canon read-only, for searching alongside the primary canon.
+    /// Nothing is migrated: older schemas are searched as they are, with whatever they
+    /// predate (tags, weights, chunk positions, source metadata) treated as empty.
+    pub fn open_read_only(store_path: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        register_sqlite_vec();
+        
+        let db_path = Self::resolve_database_path(&store_path);
+        if !db_path.is_file() {
+            return Err(format!("No canon found at {}", db_path.display()).into());
+        }
+        let conn = Connection::open_with_flags(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        let version = migrations::check_readable(&conn)?;
+        if version < migrations::latest_version() {
+            log::info!("Reading {} at schema version {} without upgrading it", db_path.display(), version);
+        }
+        
+        let canon_path = db_path.to_string_lossy().to_string();
+        let canon_name = db_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "UnknownDB".to_string());
+        
+        Ok(DocumentStore {
+            conn: Arc::new(Mutex::new(conn)),
+            ingestors: Vec::new(),
+            next_id: 0,
+            canon_path,
+            canon_name,
+            embedding_batch_config: EmbeddingBatchConfig::default(),
+            chunking_config: ChunkingConfig::default(),
+        })
+    }
+    
+    /// A read-only connection of its own to a canon file, for long analyses that
+    /// shouldn't hold the store's connection. Nothing is migrated.
+    pub(crate) fn open_side_connection(db_path: &Path) -> Result<Connection, rusqlite::Error> {
+        register_sqlite_vec();
+        let conn = Connection::open_with_flags(
+            db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        conn.busy_timeout(std::time::Duration::from_secs(30))?;
+        Ok(conn)
+    }
+    
     pub async fn set_database_path(
         &mut self,
         store_path: PathBuf,
@@ -141,7 +475,35 @@ impl DocumentStore {
         Ok(())
     }
     
-    fn resolve_database_path(store_path: &PathBuf) -> PathBuf {
+    pub fn set_embedding_batch_config(&mut self, config: EmbeddingBatchConfig) {
+        self.embedding_batch_config = config;
+    }
+    
+    pub fn get_embedding_batch_config(&self) -> &EmbeddingBatchConfig {
+        &self.embedding_batch_config
+    }
+    
+    /// Chunking used for documents ingested from now on; each document records its own
+    pub fn set_chunking_config(&mut self, config: ChunkingConfig) {
+        self.chunking_config = config;
+    }
+    
+    pub fn get_chunking_config(&self) -> &ChunkingConfig {
+        &self.chunking_config
+    }
+    
+    /// Swaps the registered PDF ingestor for one using `engine`. Jobs already holding
+    /// the old one finish with it.
+    pub fn set_pdf_engine(&mut self, engine: PdfEngine) {
+        for ingestor in self.ingestors.iter_mut() {
+            let current = ingestor.as_any().downcast_ref::<PdfIngestor>().map(PdfIngestor::engine);
+            if current.map_or(false, |current| current != engine) {
+                *ingestor = Arc::new(Box::new(PdfIngestor::new(engine)));
+            }
+        }
+    }
+    
+    pub(crate) fn resolve_database_path(store_path: &PathBuf) -> PathBuf {
         if store_path.is_file() {
             // If it's a file, use it directly
             store_path.to_path_buf()
@@ -155,6 +517,9 @@ impl DocumentStore {
         store_path: &PathBuf,
     ) -> Result<(Connection, String, String, usize), Box<dyn std::error::Error>> {
         
+        // sqlite-vec has to be registered before the connection is opened
+        register_sqlite_vec();
+        
         if store_path.is_dir() || !store_path.exists() {
             std::fs::create_dir_all(store_path)?;
         }
@@ -166,7 +531,7 @@ impl DocumentStore {
         
         
         
-        let conn = Connection::open(&db_path).map_err(|e| {
+        let mut conn = Connection::open(&db_path).map_err(|e| {
             let error_msg = format!(
                 "Failed to open SQLite database at {:?}: {}. Check permissions and disk space.", 
                 db_path, 
@@ -197,64 +562,541 @@ impl DocumentStore {
         .unwrap_or_else(|| "UnknownDB".to_string());
         log::debug!("7. conn: {:?}", conn);
         
-        // Create tables if they don't exist
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS documents 
-            (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            title TEXT,
-            authors JSON,
-            created_at TEXT NOT NULL,
-            file_path TEXT NOT NULL,
-            paused BOOLEAN DEFAULT 0,
-            embedding_model_name TEXT DEFAULT 'unknown',
-            notes TEXT DEFFAULT '',
-            UNIQUE(file_path, embedding_model_name)
-            )",
+        // Bring the schema up to date, refusing canons from a newer release
+        migrations::migrate(&mut conn)?;
+        ingestion_queue::requeue_interrupted_jobs(&conn)?;
+        
+        // Get the highest ID for our next_id counter
+        let next_id: usize = conn
+        .query_row(
+            "SELECT COALESCE(MAX(id) + 1, 0) FROM documents",
             [],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
+        
+        Ok((conn, canon_path, canon_name, next_id))
+    }
+    
+    /// Converts a canon written before sqlite-vec support, moving every JSON vector
+    /// into the vec0 table for its model and dropping the JSON column.
+    pub(crate) fn migrate_json_embeddings(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+        let has_json_column: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('embeddings') WHERE name='embedding'",
+            [],
+            |row| row.get(0),
         )?;
+        if has_json_column == 0 {
+            return Ok(());
+        }
         
-        // Replace the existing CREATE TABLE statement for embeddings
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS embeddings 
+        // Runs inside the migration's transaction, so a failure leaves the canon untouched
+        log::info!("Migrating JSON embeddings to sqlite-vec tables");
+        let mut migrated = 0;
+        {
+            let mut stmt = conn.prepare("SELECT id, embedding, embedding_model_name FROM embeddings")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })?;
+            
+            for row in rows {
+                let (id, embedding_json, model_name) = row?;
+                let vector: Vec<f32> = match serde_json::from_str(&embedding_json) {
+                    Ok(vector) => vector,
+                    Err(e) => {
+                        log::warn!("Skipping unreadable embedding {} during migration: {}", id, e);
+                        continue;
+                    }
+                };
+                let model_name = model_name.unwrap_or_else(|| "unknown".to_string());
+                let table_name = match Self::ensure_vector_index(conn, &model_name, &vector) {
+                    Ok(table_name) => table_name,
+                    Err(e) => {
+                        log::warn!("Skipping embedding {} during migration: {}", id, e);
+                        continue;
+                    }
+                };
+                conn.execute(
+                    &format!("INSERT INTO {} (rowid, embedding) VALUES (?1, ?2)", table_name),
+                    params![id, vector_to_blob(&vector)],
+                )?;
+                migrated += 1;
+            }
+        }
+        
+        // SQLite can't drop a NOT NULL column in place, so rebuild the table
+        conn.execute_batch(
+            "CREATE TABLE embeddings_migrated 
             (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             doc_id INTEGER NOT NULL,
             chunk TEXT NOT NULL, 
-            embedding JSON NOT NULL,
             embedding_model_name TEXT DEFAULT 'unknown',
             FOREIGN KEY(doc_id) REFERENCES documents(id)
-            )",
+            );
+            INSERT INTO embeddings_migrated (id, doc_id, chunk, embedding_model_name)
+            SELECT id, doc_id, chunk, embedding_model_name FROM embeddings;
+            DROP TABLE embeddings;
+            ALTER TABLE embeddings_migrated RENAME TO embeddings;",
+        )?;
+        
+        log::info!("Migrated {} embeddings to sqlite-vec", migrated);
+        Ok(())
+    }
+    
+    /// Creates the FTS5 index over embeddings.chunk and the triggers that keep it in
+    /// step with inserts, edits and deletes. Existing chunks are indexed on first creation.
+    pub(crate) fn initialize_chunk_fts(conn: &Connection) -> Result<(), rusqlite::Error> {
+        let exists: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'embeddings_fts'",
             [],
+            |row| row.get(0),
+        )?;
+
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS embeddings_fts USING fts5(
+                chunk,
+                content='embeddings',
+                content_rowid='id',
+                tokenize='unicode61 remove_diacritics 2'
+            );
+            CREATE TRIGGER IF NOT EXISTS embeddings_fts_insert AFTER INSERT ON embeddings BEGIN
+                INSERT INTO embeddings_fts (rowid, chunk) VALUES (new.id, new.chunk);
+            END;
+            CREATE TRIGGER IF NOT EXISTS embeddings_fts_delete AFTER DELETE ON embeddings BEGIN
+                INSERT INTO embeddings_fts (embeddings_fts, rowid, chunk) VALUES ('delete', old.id, old.chunk);
+            END;
+            CREATE TRIGGER IF NOT EXISTS embeddings_fts_update AFTER UPDATE OF chunk ON embeddings BEGIN
+                INSERT INTO embeddings_fts (embeddings_fts, rowid, chunk) VALUES ('delete', old.id, old.chunk);
+                INSERT INTO embeddings_fts (rowid, chunk) VALUES (new.id, new.chunk);
+            END;",
+        )?;
+
+        if exists == 0 {
+            log::info!("Building keyword index for existing chunks");
+            conn.execute("INSERT INTO embeddings_fts (embeddings_fts) VALUES ('rebuild')", [])?;
+        }
+        Ok(())
+    }
+
+    /// Returns what's registered for an embedding model, if anything
+    fn vector_index_for_model(
+        conn: &Connection,
+        embedding_model_name: &str,
+    ) -> Result<Option<EmbeddingModelInfo>, rusqlite::Error> {
+        conn.query_row(
+            "SELECT embedding_model_name, table_name, dimension, normalized, provider, created_at
+            FROM vector_indexes WHERE embedding_model_name = ?1",
+            params![embedding_model_name],
+            row_to_embedding_model_info,
+        )
+        .optional()
+    }
+    
+    /// Every embedding model this canon holds vectors for, by name
+    pub(crate) fn vector_indexes(conn: &Connection) -> Result<Vec<EmbeddingModelInfo>, rusqlite::Error> {
+        let mut stmt = conn.prepare(
+            "SELECT embedding_model_name, table_name, dimension, normalized, provider, created_at
+            FROM vector_indexes ORDER BY embedding_model_name",
         )?;
+        let rows = stmt.query_map([], row_to_embedding_model_info)?;
+        rows.collect()
+    }
+    
+    /// Looks up a model's index and checks a query vector against its dimension, so a
+    /// query from a differently sized model fails loudly instead of scoring garbage
+    fn vector_index_for_query(
+        conn: &Connection,
+        embedding_model_name: &str,
+        query_vector: &[f32],
+    ) -> Result<Option<EmbeddingModelInfo>, Box<dyn std::error::Error>> {
+        let index = match Self::vector_index_for_model(conn, embedding_model_name)? {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        if index.dimension != query_vector.len() {
+            return Err(Box::new(EmbeddingMetadataError::DimensionMismatch {
+                embedding_model_name: embedding_model_name.to_string(),
+                expected: index.dimension,
+                actual: query_vector.len(),
+            }));
+        }
+        Ok(Some(index))
+    }
+    
+    /// Returns the vec0 table for an embedding model, creating it on first use.
+    /// A model's dimension and normalization are fixed by the first vector stored for it;
+    /// later vectors that don't match are rejected.
+    fn ensure_vector_index(
+        conn: &Connection,
+        embedding_model_name: &str,
+        vector: &[f32],
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let dimension = vector.len();
+        if let Some(index) = Self::vector_index_for_model(conn, embedding_model_name)? {
+            if index.dimension != dimension {
+                return Err(Box::new(EmbeddingMetadataError::DimensionMismatch {
+                    embedding_model_name: embedding_model_name.to_string(),
+                    expected: index.dimension,
+                    actual: dimension,
+                }));
+            }
+            match index.normalized {
+                Some(true) if !is_unit_length(vector) => {
+                    return Err(Box::new(EmbeddingMetadataError::NormalizationMismatch {
+                        embedding_model_name: embedding_model_name.to_string(),
+                        norm: vector_norm(vector),
+                    }));
+                }
+                Some(_) => {}
+                None => {
+                    conn.execute(
+                        "UPDATE vector_indexes SET normalized = ?1 WHERE embedding_model_name = ?2",
+                        params![is_unit_length(vector), embedding_model_name],
+                    )?;
+                }
+            }
+            return Ok(index.table_name);
+        }
+        
+        let base_name = format!("vec_{}", sanitize_table_suffix(embedding_model_name));
+        let mut table_name = base_name.clone();
+        let mut suffix = 1;
+        while conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name = ?1",
+            params![table_name],
+            |row| row.get::<_, i64>(0),
+        )? > 0 {
+            table_name = format!("{}_{}", base_name, suffix);
+            suffix += 1;
+        }
         
-        // Add the new canon table
+        conn.execute_batch(&format!(
+            "CREATE VIRTUAL TABLE {} USING vec0(embedding float[{}] distance_metric=cosine)",
+            table_name, dimension
+        ))?;
         conn.execute(
-            "CREATE TABLE IF NOT EXISTS canon 
-            (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            owner TEXT NOT NULL,
-            created_at TEXT NOT NULL,
-            modified_at TEXT NOT NULL,
-            notes TEXT NOT NULL
-            )",
-            [],
+            "INSERT INTO vector_indexes (embedding_model_name, table_name, dimension, normalized, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![embedding_model_name, table_name, dimension as i64, is_unit_length(vector), Local::now().to_rfc3339()],
         )?;
+        log::info!("Created vector index {} for {} ({} dimensions)", table_name, embedding_model_name, dimension);
         
-        // Get the highest ID for our next_id counter
-        let next_id: usize = conn
-        .query_row(
-            "SELECT COALESCE(MAX(id) + 1, 0) FROM documents",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
+        Ok(table_name)
+    }
+    
+    /// Notes which provider produced a model's vectors, the first time it's known
+    pub(crate) fn record_index_provider(
+        conn: &Connection,
+        embedding_model_name: &str,
+        provider_name: &str,
+    ) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "UPDATE vector_indexes SET provider = ?1 WHERE embedding_model_name = ?2 AND provider IS NULL",
+            params![provider_name, embedding_model_name],
+        )?;
+        Ok(())
+    }
+    
+    /// Indexes registered before normalization was recorded get it from one of their vectors
+    pub(crate) fn backfill_normalization(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+        for index in Self::vector_indexes(conn)? {
+            if index.normalized.is_some() {
+                continue;
+            }
+            let sample: Option<Vec<u8>> = conn
+            .query_row(&format!("SELECT embedding FROM {} LIMIT 1", index.table_name), [], |row| row.get(0))
+            .optional()?;
+            if let Some(sample) = sample {
+                conn.execute(
+                    "UPDATE vector_indexes SET normalized = ?1 WHERE embedding_model_name = ?2",
+                    params![is_unit_length(&blob_to_vector(&sample)), index.embedding_model_name],
+                )?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Records the embedding model a canon was built with. The first model to embed
+    /// anything wins; re-embedding with another model adds vectors without changing it.
+    pub(crate) fn record_canon_embedding_model(
+        conn: &Connection,
+        canon_name: &str,
+        embedding_model_name: &str,
+    ) -> Result<(), rusqlite::Error> {
+        let now = Local::now().to_rfc3339();
+        let updated = conn.execute(
+            "UPDATE canon SET embedding_model_name = ?1, modified_at = ?2 WHERE embedding_model_name IS NULL",
+            params![embedding_model_name, now],
+        )?;
+        let has_canon_row: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM canon)", [], |row| row.get(0))?;
+        if updated == 0 && !has_canon_row {
+            conn.execute(
+                "INSERT INTO canon (name, owner, created_at, modified_at, notes, embedding_model_name)
+                VALUES (?1, '', ?2, ?2, '', ?3)",
+                params![canon_name, now, embedding_model_name],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Stores one chunk and its vector, returning the new embeddings row id
+    pub(crate) fn insert_chunk_embedding(
+        conn: &Connection,
+        doc_id: i64,
+        chunk: &str,
+        vector: &[f32],
+        embedding_model_name: &str,
+    ) -> Result<i64, Box<dyn std::error::Error>> {
+        // Create (or check) the index before adding the chunk row
+        Self::ensure_vector_index(conn, embedding_model_name, vector)?;
+        conn.execute(
+            "INSERT INTO embeddings (doc_id, chunk, embedding_model_name) VALUES (?1, ?2, ?3)",
+            params![doc_id, chunk, embedding_model_name],
+        )?;
+        let embedding_id = conn.last_insert_rowid();
+        Self::insert_vector(conn, embedding_id, vector, embedding_model_name)?;
+        Ok(embedding_id)
+    }
+    
+    /// Stores a vector for an existing chunk in the embedding model's index. A chunk can
+    /// have one vector per model, which is how a canon holds several models at once.
+    pub(crate) fn insert_vector(
+        conn: &Connection,
+        chunk_id: i64,
+        vector: &[f32],
+        embedding_model_name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let table_name = Self::ensure_vector_index(conn, embedding_model_name, vector)?;
+        conn.execute(
+            &format!("INSERT INTO {} (rowid, embedding) VALUES (?1, ?2)", table_name),
+            params![chunk_id, vector_to_blob(vector)],
+        )?;
+        Ok(())
+    }
+    
+    /// Matches a document's new chunks against the chunks stored for it by content hash.
+    /// Repeated text is matched one for one, so a paragraph that appears twice needs two stored copies.
+    fn plan_chunk_updates(
+        conn: &Connection,
+        doc_id: i64,
+        embedding_model_name: &str,
+        chunks: &[String],
+    ) -> Result<ChunkPlan, Box<dyn std::error::Error>> {
+        let has_vector = match Self::vector_index_for_model(conn, embedding_model_name)? {
+            Some(index) => format!("id IN (SELECT rowid FROM {})", index.table_name),
+            None => "0".to_string(),
+        };
+        let mut stored: std::collections::HashMap<String, std::collections::VecDeque<(i64, bool)>> =
+        std::collections::HashMap::new();
+        {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT id, chunk, content_hash, {} FROM embeddings WHERE doc_id = ?1 ORDER BY chunk_index, id",
+                has_vector
+            ))?;
+            let rows = stmt.query_map(params![doc_id], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, bool>(3)?,
+                ))
+            })?;
+            for row in rows {
+                // Chunks stored before hashing was added are hashed from their text
+                let (chunk_id, chunk, hash, has_vector) = row?;
+                let hash = hash.unwrap_or_else(|| content_hash(&chunk));
+                stored.entry(hash).or_default().push_back((chunk_id, has_vector));
+            }
+        }
         
-        Ok((conn, canon_path, canon_name, next_id))
+        let mut plan = ChunkPlan::default();
+        for (index, chunk) in chunks.iter().enumerate() {
+            match stored.get_mut(&content_hash(chunk)).and_then(|rows| rows.pop_front()) {
+                Some((chunk_id, true)) => plan.reused.push((index, chunk_id)),
+                Some((chunk_id, false)) => plan.to_embed.push((index, Some(chunk_id))),
+                None => plan.to_embed.push((index, None)),
+            }
+        }
+        plan.stale = stored.into_values().flatten().map(|(chunk_id, _)| chunk_id).collect();
+        plan.stale.sort_unstable();
+        Ok(plan)
     }
     
+    /// Records where a chunk sits in its document and the hash of its text
+    pub(crate) fn set_chunk_position(
+        conn: &Connection,
+        chunk_id: i64,
+        chunk_index: usize,
+        chunk: &str,
+        provenance: &ChunkProvenance,
+    ) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "UPDATE embeddings SET chunk_index = ?1, content_hash = ?2, start_offset = ?3, end_offset = ?4, page = ?5, section = ?6 WHERE id = ?7",
+            params![
+                chunk_index as i64,
+                content_hash(chunk),
+                provenance.start_offset.map(|offset| offset as i64),
+                provenance.end_offset.map(|offset| offset as i64),
+                provenance.page,
+                provenance.section,
+                chunk_id
+            ],
+        )?;
+        Ok(())
+    }
+    
+    /// Deletes chunks along with their vectors in every model's index
+    pub(crate) fn delete_chunks(conn: &Connection, chunk_ids: &[i64]) -> Result<(), Box<dyn std::error::Error>> {
+        let table_names: Vec<String> = Self::vector_indexes(conn)?
+        .into_iter()
+        .map(|index| index.table_name)
+        .collect();
+        for &chunk_id in chunk_ids {
+            for table_name in &table_names {
+                conn.execute(&format!("DELETE FROM {} WHERE rowid = ?1", table_name), params![chunk_id])?;
+            }
+            conn.execute("DELETE FROM embeddings WHERE id = ?1", params![chunk_id])?;
+        }
+        Ok(())
+    }
+    
+    /// K-nearest-neighbour search over active (unpaused) documents for one model.
+    /// Returns (doc_id, doc_name, chunk_id, chunk, similarity) ordered by similarity.
+    /// vec0 can't skip paused documents itself, so when they crowd the nearest `k` out
+    /// the search is widened and run again, and past `KNN_MAX_K` it turns into an
+    /// exact scan of the active chunks.
+    pub(crate) fn knn_search(
+        conn: &Connection,
+        embedding_model_name: &str,
+        query_vector: &[f32],
+        k: usize,
+    ) -> Result<Vec<(i64, String, usize, String, f32)>, Box<dyn std::error::Error>> {
+        let table_name = match Self::vector_index_for_query(conn, embedding_model_name, query_vector)? {
+            Some(index) => index.table_name,
+            None => return Ok(Vec::new()),
+        };
+        let query_blob = vector_to_blob(query_vector);
+        fn to_hit(row: &rusqlite::Row) -> rusqlite::Result<(i64, String, usize, String, f32)> {
+            let distance: f32 = row.get(4)?;
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)? as usize,
+                row.get::<_, String>(3)?,
+                // vec0 reports cosine distance
+                1.0 - distance,
+            ))
+        }
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT d.id, d.name, e.id, e.chunk, v.distance, COALESCE(d.paused, 0)
+            FROM (
+                SELECT rowid, distance FROM {}
+                WHERE embedding MATCH ?1 AND k = ?2
+            ) v
+            JOIN embeddings e ON e.id = v.rowid
+            JOIN documents d ON d.id = e.doc_id
+            ORDER BY v.distance",
+            table_name
+        ))?;
+        let mut fetch = k.clamp(1, Self::KNN_MAX_K);
+        loop {
+            let mut fetched = 0;
+            let mut results = Vec::new();
+            let mut rows = stmt.query(params![query_blob, fetch as i64])?;
+            while let Some(row) = rows.next()? {
+                fetched += 1;
+                if !row.get::<_, bool>(5)? && results.len() < k {
+                    results.push(to_hit(row)?);
+                }
+            }
+            // Enough active hits, or the whole index has been seen
+            if results.len() >= k || fetched < fetch {
+                return Ok(results);
+            }
+            if fetch == Self::KNN_MAX_K {
+                break;
+            }
+            fetch = (fetch * Self::KNN_OVERFETCH).min(Self::KNN_MAX_K);
+        }
+        
+        log::debug!("Paused documents fill the top {} neighbours; scanning active chunks exactly", Self::KNN_MAX_K);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT d.id, d.name, e.id, e.chunk, vec_distance_cosine(v.embedding, ?1) AS distance
+            FROM embeddings e
+            JOIN {} v ON v.rowid = e.id
+            JOIN documents d ON d.id = e.doc_id
+            WHERE (d.paused = 0 OR d.paused IS NULL)
+            ORDER BY distance
+            LIMIT ?2",
+            table_name
+        ))?;
+        let rows = stmt.query_map(params![query_blob, k as i64], to_hit)?;
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+
+    /// Exact nearest neighbours among the chunks of `doc_ids` only, for tag and metadata
+    /// filtered searches. Ranking the whole canon first and filtering afterwards would
+    /// lose a small collection whose chunks fall outside the KNN's top `KNN_MAX_K`.
+    /// Same result shape as `knn_search`.
+    pub(crate) fn knn_search_within(
+        conn: &Connection,
+        embedding_model_name: &str,
+        query_vector: &[f32],
+        k: usize,
+        doc_ids: &std::collections::HashSet<i64>,
+    ) -> Result<Vec<(i64, String, usize, String, f32)>, Box<dyn std::error::Error>> {
+        let table_name = match Self::vector_index_for_query(conn, embedding_model_name, query_vector)? {
+            Some(index) => index.table_name,
+            None => return Ok(Vec::new()),
+        };
+        if doc_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT d.id, d.name, e.id, e.chunk, vec_distance_cosine(v.embedding, ?1) AS distance
+            FROM embeddings e
+            JOIN {} v ON v.rowid = e.id
+            JOIN documents d ON d.id = e.doc_id
+            WHERE e.doc_id IN (SELECT value FROM json_each(?2))
+            AND (d.paused = 0 OR d.paused IS NULL)
+            ORDER BY distance
+            LIMIT ?3",
+            table_name
+        ))?;
+
+        let doc_ids: Vec<i64> = doc_ids.iter().copied().collect();
+        let rows = stmt.query_map(
+            params![vector_to_blob(query_vector), serde_json::to_string(&doc_ids)?, k as i64],
+            |row| {
+                let distance: f32 = row.get(4)?;
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)? as usize,
+                    row.get::<_, String>(3)?,
+                    1.0 - distance,
+                ))
+            },
+        )?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+
     pub async fn add_document(
         &mut self,
         mut document: Document,
@@ -278,13 +1120,204 @@ impl DocumentStore {
         Ok(())
     }
     
+    /// FTS5 keyword search over active documents' chunks for one model.
+    /// Returns (doc_id, doc_name, chunk_id, chunk, bm25) best first, with bm25 negated so higher is better.
+    /// With `doc_ids`, only those documents' chunks are searched.
+    fn lexical_search(
+        conn: &Connection,
+        embedding_model_name: &str,
+        query_text: &str,
+        k: usize,
+        doc_ids: Option<&std::collections::HashSet<i64>>,
+    ) -> Result<Vec<(i64, String, usize, String, f32)>, Box<dyn std::error::Error>> {
+        let fts_query = match fts_query(query_text, Self::FTS_MAX_TERMS) {
+            Some(fts_query) => fts_query,
+            None => return Ok(Vec::new()),
+        };
+        // Only chunks the model has vectors for, so keyword hits can be scored against the query
+        let table_name = match Self::vector_index_for_model(conn, embedding_model_name)? {
+            Some(index) => index.table_name,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT d.id, d.name, e.id, e.chunk, bm25(embeddings_fts)
+            FROM embeddings_fts
+            JOIN embeddings e ON e.id = embeddings_fts.rowid
+            JOIN documents d ON d.id = e.doc_id
+            WHERE embeddings_fts MATCH ?1
+            AND e.id IN (SELECT rowid FROM {})
+            AND (d.paused = 0 OR d.paused IS NULL)
+            AND (?3 IS NULL OR e.doc_id IN (SELECT value FROM json_each(?3)))
+            ORDER BY bm25(embeddings_fts)
+            LIMIT ?2",
+            table_name
+        ))?;
+
+        let doc_ids = match doc_ids {
+            Some(doc_ids) => Some(serde_json::to_string(&doc_ids.iter().collect::<Vec<_>>())?),
+            None => None,
+        };
+        let rows = stmt.query_map(params![fts_query, k as i64, doc_ids], |row| {
+            let bm25: f64 = row.get(4)?;
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)? as usize,
+                row.get::<_, String>(3)?,
+                -bm25 as f32,
+            ))
+        })?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+
+    /// Cosine similarity between the query and one stored chunk, for keyword hits
+    /// that didn't come back from the KNN search
+    fn chunk_similarity(
+        conn: &Connection,
+        embedding_model_name: &str,
+        query_vector: &[f32],
+        chunk_id: usize,
+    ) -> Result<Option<f32>, Box<dyn std::error::Error>> {
+        let table_name = match Self::vector_index_for_query(conn, embedding_model_name, query_vector)? {
+            Some(index) => index.table_name,
+            None => return Ok(None),
+        };
+        let distance: Option<f64> = conn
+        .query_row(
+            &format!("SELECT vec_distance_cosine(embedding, ?1) FROM {} WHERE rowid = ?2", table_name),
+            params![vector_to_blob(query_vector), chunk_id as i64],
+            |row| row.get(0),
+        )
+        .optional()?;
+        Ok(distance.map(|distance| 1.0 - distance as f32))
+    }
+
+    /// Retrieval weights of documents weighted other than 1.0, by document id
+    fn document_weights(conn: &Connection) -> Result<std::collections::HashMap<i64, f32>, rusqlite::Error> {
+        if migrations::current_version(conn)? < migrations::RETRIEVAL_WEIGHT_VERSION {
+            return Ok(std::collections::HashMap::new());
+        }
+        let mut stmt = conn.prepare("SELECT id, retrieval_weight FROM documents WHERE retrieval_weight IS NOT NULL AND retrieval_weight != 1.0")?;
+        let weights = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, f64>(1)? as f32)))?;
+        weights.collect()
+    }
+
+    /// The best chunk of each active pinned document, whatever the similarity threshold.
+    /// A document already among `ranked` contributes its top-ranked hit; otherwise the
+    /// chunk closest to the query is fetched.
+    fn pinned_hits(
+        &self,
+        conn: &Connection,
+        embedding_model_name: &str,
+        query_vector: &[f32],
+        ranked: &[SearchHit],
+        in_collection: &dyn Fn(i64) -> bool,
+    ) -> Result<Vec<SearchHit>, Box<dyn std::error::Error>> {
+        if migrations::current_version(conn)? < migrations::RETRIEVAL_WEIGHT_VERSION {
+            return Ok(Vec::new());
+        }
+        let table_name = match Self::vector_index_for_query(conn, embedding_model_name, query_vector)? {
+            Some(index) => index.table_name,
+            None => return Ok(Vec::new()),
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT d.id, d.name, e.id, e.chunk, vec_distance_cosine(v.embedding, ?1) AS distance, d.retrieval_weight
+            FROM documents d
+            JOIN embeddings e ON e.doc_id = d.id
+            JOIN {} v ON v.rowid = e.id
+            WHERE d.pinned = 1 AND (d.paused = 0 OR d.paused IS NULL)
+            ORDER BY d.id, distance",
+            table_name
+        ))?;
+        let rows = stmt.query_map(params![vector_to_blob(query_vector)], |row| {
+            let distance: f64 = row.get(4)?;
+            Ok(SearchHit {
+                doc_id: row.get(0)?,
+                doc_name: row.get(1)?,
+                chunk_id: row.get::<_, i64>(2)? as usize,
+                chunk: row.get(3)?,
+                similarity: 1.0 - distance as f32,
+                lexical_score: None,
+                fused_score: None,
+                canon_name: self.canon_name.clone(),
+                canon_weight: 1.0,
+                doc_weight: row.get::<_, Option<f64>>(5)?.map_or(1.0, |weight| weight as f32),
+                pinned: true,
+                provenance: ChunkProvenance::default(),
+                citation: String::new(),
+            })
+        })?;
+
+        let mut pinned: Vec<SearchHit> = Vec::new();
+        for row in rows {
+            let closest = row?;
+            if !in_collection(closest.doc_id) || pinned.last().map_or(false, |hit| hit.doc_id == closest.doc_id) {
+                continue;
+            }
+            let hit = match ranked.iter().find(|hit| hit.doc_id == closest.doc_id) {
+                Some(hit) => SearchHit { pinned: true, ..hit.clone() },
+                None => closest,
+            };
+            pinned.push(hit);
+        }
+        pinned.sort_by(|a, b| b.rank_score().partial_cmp(&a.rank_score()).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(pinned)
+    }
+
+    /// The chunking strategy and parameters recorded for a document, if any
+    fn document_chunking_config(
+        conn: &Connection,
+        doc_id: i64,
+    ) -> Result<Option<ChunkingConfig>, rusqlite::Error> {
+        let recorded: Option<(Option<String>, Option<String>)> = conn
+        .query_row(
+            "SELECT chunking_strategy, chunking_params FROM documents WHERE id = ?1",
+            params![doc_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+        Ok(recorded.and_then(|(strategy, params)| parse_chunking_config(strategy, params)))
+    }
+    
+    /// Stored vectors for a set of chunks, keyed by chunk id
+    fn chunk_vectors(
+        conn: &Connection,
+        embedding_model_name: &str,
+        chunk_ids: &[usize],
+    ) -> Result<std::collections::HashMap<usize, Vec<f32>>, Box<dyn std::error::Error>> {
+        let mut vectors = std::collections::HashMap::new();
+        let table_name = match Self::vector_index_for_model(conn, embedding_model_name)? {
+            Some(index) => index.table_name,
+            None => return Ok(vectors),
+        };
+        
+        let mut stmt = conn.prepare(&format!("SELECT embedding FROM {} WHERE rowid = ?1", table_name))?;
+        for &chunk_id in chunk_ids {
+            let blob: Option<Vec<u8>> = stmt
+            .query_row(params![chunk_id as i64], |row| row.get(0))
+            .optional()?;
+            if let Some(blob) = blob {
+                vectors.insert(chunk_id, blob_to_vector(&blob));
+            }
+        }
+        Ok(vectors)
+    }
+    
     pub async fn search(
         &self,
+        query_text: &str,
         query_embedding_result: &Result<Vec<ai::models::Embedding>, AIProviderError>,
         provider: &Provider,
         similar_docs_count: usize,
         similarity_threshold: f32,
-    ) -> Result<Vec<(i64, String, usize, String, f32)>, Box<dyn std::error::Error>> {
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchHit>, Box<dyn std::error::Error>> {
         // Handle the Result type for query_embedding
         let query_embedding = match query_embedding_result {
             Ok(embeddings) => {
@@ -307,86 +1340,170 @@ impl DocumentStore {
         };
         let embedding_model_name = provider.get_preferred_embedding_model();
         let conn = self.conn.lock().await;
-        let mut stmt = conn.prepare(
-            "SELECT d.id, d.name, d.file_path, d.created_at, e.id, e.chunk, e.embedding 
-            FROM documents d 
-            JOIN (
-                SELECT id, doc_id, chunk, embedding, embedding_model_name 
-                FROM embeddings
-                GROUP BY id, doc_id, chunk, embedding, embedding_model_name
-            ) e ON d.id = e.doc_id
-            WHERE (d.paused = 0 OR d.paused IS NULL) 
-            AND e.embedding_model_name = ?1"
-        )?;  // Use ? directly for rusqlite::Error
-        
-        let mut similarities = Vec::new();
-        
-        let rows = stmt.query_map(params![embedding_model_name], |row| {
-            let doc_id: i64 = row.get(0)?;  // Extract the document id
-            let name: String = row.get(1)?;  // Use ? directly for rusqlite errors
-            let chunk_id: usize = row.get(4)?;
-            let chunk: String = row.get(5)?;
-            let embedding_json: String = row.get(6)?;
-            
-            let chunk_embedding: Vec<f32> = serde_json::from_str(&embedding_json)
-            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(
-                6,
-                rusqlite::types::Type::Text,
-                Box::new(e)
-            ))?;
-            
-            let similarity = cosine_similarity(query_embedding, &chunk_embedding);
-            
-            Ok((doc_id, name, chunk_id, chunk, similarity))
-        })?;  // Use ? directly for rusqlite::Error
-        
-        
-        for row in rows {
-            similarities.push(row?);
+
+        // Nearest neighbours come back from sqlite-vec already ranked; over-fetch so
+        // the threshold and de-duplication below still have enough to choose from
+        let candidate_count = (similar_docs_count * Self::KNN_OVERFETCH).max(Self::KNN_MIN_CANDIDATES);
+        // Tag and metadata filters restrict both searches to the matching documents' chunks
+        // (attached canons may predate tags or metadata; nothing in them matches then)
+        let schema_version = migrations::current_version(&conn)?;
+        let mut allowed_doc_ids: Option<std::collections::HashSet<i64>> = None;
+        if !options.tags.is_empty() {
+            allowed_doc_ids = Some(if schema_version < migrations::TAGS_VERSION {
+                std::collections::HashSet::new()
+            } else {
+                collections::documents_tagged(&conn, &options.tags)?.into_iter().collect()
+            });
         }
-        
-        // Filter by min_score and sort by similarity score in descending order
-        similarities.retain(|&(doc_id, ref name, _, _, similarity)| {
-            similarity >= similarity_threshold
-        });
-        
+        if !options.metadata.is_empty() {
+            let matching = if schema_version < migrations::DOCUMENT_METADATA_VERSION {
+                std::collections::HashSet::new()
+            } else {
+                document_metadata::documents_matching(&conn, &options.metadata)?
+            };
+            allowed_doc_ids = Some(match allowed_doc_ids {
+                Some(tagged) => tagged.intersection(&matching).copied().collect(),
+                None => matching,
+            });
+        }
+        let in_collection = |doc_id: i64| allowed_doc_ids.as_ref().map_or(true, |doc_ids| doc_ids.contains(&doc_id));
+        let vector_candidates = match &allowed_doc_ids {
+            Some(doc_ids) => Self::knn_search_within(&conn, &embedding_model_name, query_embedding, candidate_count, doc_ids)?,
+            None => Self::knn_search(&conn, &embedding_model_name, query_embedding, candidate_count)?,
+        };
+
+        // Filter by min_score
+        let vector_hits: Vec<SearchHit> = vector_candidates
+        .iter()
+        .filter(|&&(_, _, _, _, similarity)| similarity >= similarity_threshold)
+        .map(|(doc_id, doc_name, chunk_id, chunk, similarity)| SearchHit {
+            doc_id: *doc_id,
+            doc_name: doc_name.clone(),
+            chunk_id: *chunk_id,
+            chunk: chunk.clone(),
+            similarity: *similarity,
+            lexical_score: None,
+            fused_score: None,
+            canon_name: self.canon_name.clone(),
+            canon_weight: 1.0,
+            doc_weight: 1.0,
+            pinned: false,
+            provenance: ChunkProvenance::default(),
+            citation: String::new(),
+        })
+        .collect();
+
+        let mut similarities = match options.mode {
+            SearchMode::Vector => vector_hits,
+            SearchMode::Hybrid => {
+                // Keyword matches skip the similarity threshold: an exact name is
+                // relevant even when the surrounding prose embeds far from the query
+                let lexical_candidates =
+                Self::lexical_search(&conn, &embedding_model_name, query_text, candidate_count, allowed_doc_ids.as_ref())?;
+                let mut lexical_hits = Vec::with_capacity(lexical_candidates.len());
+                for (doc_id, doc_name, chunk_id, chunk, bm25) in lexical_candidates {
+                    let similarity = match vector_candidates.iter().find(|candidate| candidate.2 == chunk_id) {
+                        Some(candidate) => candidate.4,
+                        None => Self::chunk_similarity(&conn, &embedding_model_name, query_embedding, chunk_id)?
+                        .unwrap_or(0.0),
+                    };
+                    lexical_hits.push(SearchHit {
+                        doc_id,
+                        doc_name,
+                        chunk_id,
+                        chunk,
+                        similarity,
+                        lexical_score: Some(bm25),
+                        fused_score: None,
+                        canon_name: self.canon_name.clone(),
+                        canon_weight: 1.0,
+                        doc_weight: 1.0,
+                        pinned: false,
+                        provenance: ChunkProvenance::default(),
+                        citation: String::new(),
+                    });
+                }
+                reciprocal_rank_fusion(vector_hits, lexical_hits, options.lexical_weight, Self::RRF_K)
+            }
+        };
+
+        let weights = Self::document_weights(&conn)?;
+        for hit in similarities.iter_mut() {
+            if let Some(&weight) = weights.get(&hit.doc_id) {
+                hit.doc_weight = weight;
+            }
+        }
+
+        // Sort by score in descending order
         similarities.sort_by(|a, b| {
-            b.4.partial_cmp(&a.4)  // Changed from .3 to .4 to access similarity
+            b.rank_score().partial_cmp(&a.rank_score())
             .unwrap_or(std::cmp::Ordering::Equal)
         });
         
-        // Collect top results with unique doc_id and unique chunk_id
-        let mut unique_results = Vec::new();
-        let mut seen_doc_ids = std::collections::HashSet::new();
-        let mut seen_chunk_ids = std::collections::HashSet::new();
-        
-        for result in &similarities {
-            if seen_doc_ids.len() >= similar_docs_count {
-                break;
-            }
-            if seen_doc_ids.insert(result.0) && seen_chunk_ids.insert(result.2) {
-                unique_results.push(result.clone());
-            }
+        // Pinned documents lead the results and take their share of the count
+        let pinned_hits = self.pinned_hits(&conn, &embedding_model_name, query_embedding, &similarities, &in_collection)?;
+
+        // Keep each document's best hit with a unique chunk_id, leaving out pinned ones
+        let mut seen_doc_ids: std::collections::HashSet<i64> = pinned_hits.iter().map(|hit| hit.doc_id).collect();
+        let mut seen_chunk_ids: std::collections::HashSet<usize> = pinned_hits.iter().map(|hit| hit.chunk_id).collect();
+        let remaining_count = similar_docs_count.saturating_sub(seen_doc_ids.len());
+        similarities.retain(|hit| seen_doc_ids.insert(hit.doc_id) && seen_chunk_ids.insert(hit.chunk_id));
+
+        // Overlapping chunk windows tend to come back as near-paraphrases; re-rank the
+        // de-duplicated candidates so the truncation below keeps more distinct material
+        if options.mmr_lambda < 1.0 && similarities.len() > 1 {
+            let chunk_ids: Vec<usize> = similarities.iter().map(|hit| hit.chunk_id).collect();
+            let vectors = Self::chunk_vectors(&conn, &embedding_model_name, &chunk_ids)?;
+            similarities = mmr_rerank(similarities, &vectors, options.mmr_lambda, remaining_count);
         }
-        
-        // If we have fewer than similar_docs_count unique results, add more entries from the remaining items
-        if unique_results.len() < similar_docs_count {
-            for result in &similarities {
-                if unique_results.len() >= similar_docs_count {
-                    break;
-                }
-                if seen_doc_ids.insert(result.0) && seen_chunk_ids.insert(result.2) {
-                    unique_results.push(result.clone());
-                }
+
+        let mut unique_results = pinned_hits;
+        unique_results.extend(similarities.into_iter().take(remaining_count));
+
+        Self::cite_hits(&conn, &mut unique_results)?;
+        Ok(unique_results)
+    }
+
+    /// Fills in each hit's provenance and citation from its chunk and document
+    fn cite_hits(conn: &Connection, hits: &mut [SearchHit]) -> Result<(), rusqlite::Error> {
+        let positions = if migrations::current_version(conn)? < migrations::CHUNK_PROVENANCE_VERSION {
+            "NULL, NULL, NULL, NULL"
+        } else {
+            "e.start_offset, e.end_offset, e.page, e.section"
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, COALESCE(NULLIF(d.title, ''), d.name), d.authors
+            FROM embeddings e
+            JOIN documents d ON d.id = e.doc_id
+            WHERE e.id = ?1",
+            positions
+        ))?;
+        for hit in hits.iter_mut() {
+            let found = stmt
+            .query_row(params![hit.chunk_id as i64], |row| {
+                let provenance = ChunkProvenance {
+                    start_offset: row.get::<_, Option<i64>>(0)?.map(|offset| offset as usize),
+                    end_offset: row.get::<_, Option<i64>>(1)?.map(|offset| offset as usize),
+                    page: row.get(2)?,
+                    section: row.get(3)?,
+                };
+                let title: Option<String> = row.get(4)?;
+                let authors: Option<String> = row.get(5)?;
+                Ok((provenance, title, authors))
+            })
+            .optional()?;
+            if let Some((provenance, title, authors)) = found {
+                let authors: Vec<String> = authors.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default();
+                hit.citation = citation(&authors, title.as_deref().unwrap_or(&hit.doc_name), &provenance);
+                hit.provenance = provenance;
             }
         }
-        
-        Ok(unique_results)
+        Ok(())
     }
-    
+
     pub async fn fetch_documents(&self) -> Result<DocumentListing, Box<dyn std::error::Error>> {
         let conn = self.conn.lock().await;
-        let mut stmt = conn.prepare("SELECT id, name, file_path, created_at, paused, embedding_model_name, notes, authors FROM documents")?;
+        let mut stmt = conn.prepare("SELECT id, name, file_path, created_at, paused, embedding_model_name, notes, authors, chunking_strategy, chunking_params, retrieval_weight, pinned FROM documents")?;
         
         let rows = stmt.query_map([], |row| {
             // Parse authors from JSON string to Vec<String>
@@ -405,10 +1522,21 @@ impl DocumentStore {
                 embedding_model_name: row.get(5).unwrap_or("unknown".to_string()),
                 notes: row.get(6).unwrap_or("".to_string()),
                 authors, // A Vec<String> parsed from JSON
+                chunking: parse_chunking_config(row.get(8)?, row.get(9)?),
+                tags: Vec::new(),
+                weight: row.get::<_, Option<f64>>(10)?.map_or(1.0, |weight| weight as f32),
+                pinned: row.get::<_, Option<bool>>(11)?.unwrap_or(false),
+                metadata: None,
             })
         })?;
         
-        let documents: Vec<DocumentInfo> = rows.collect::<Result<_, _>>()?;
+        let mut documents: Vec<DocumentInfo> = rows.collect::<Result<_, _>>()?;
+        let mut tags = collections::tags_by_document(&conn)?;
+        let mut metadata = document_metadata::metadata_by_document(&conn)?;
+        for document in documents.iter_mut() {
+            document.tags = tags.remove(&document.id).unwrap_or_default();
+            document.metadata = metadata.remove(&document.id);
+        }
         
         // Get the database file path from the connection
         let db_path = conn.path().unwrap_or_default();
@@ -434,6 +1562,26 @@ impl DocumentStore {
         self.ingestors.push(Arc::new(ingestor));
     }
     
+    pub(crate) fn ingestors(&self) -> Vec<Arc<Box<dyn DocumentIngestor>>> {
+        self.ingestors.clone()
+    }
+    
+    /// Runs a resource through the first registered ingestor that can handle it
+    pub(crate) async fn ingest_resource(
+        &self,
+        resource: &Resource,
+    ) -> Result<IngestedDocument, Box<dyn std::error::Error>> {
+        let ingestor = self.ingestors.iter().find(|i| i.can_handle(resource)).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, format!("No suitable ingestor found for {:?}", resource))
+        })?;
+        Ok(ingestor.ingest(resource).await?)
+    }
+    
+    /// Extraction version of the ingestor that reads this resource, if any does
+    pub(crate) fn extraction_version(&self, resource: &Resource) -> Option<u32> {
+        self.ingestors.iter().find(|i| i.can_handle(resource)).map(|ingestor| ingestor.extraction_version())
+    }
+    
     pub async fn save_document_to_file(
         &self,
         resource: &Resource,
@@ -515,7 +1663,10 @@ impl DocumentStore {
         
         let doc_id_result = {
             let conn = store.conn.lock().await;
-            store.add_document_internal(&conn, document.clone())
+            store.add_document_internal(&conn, document.clone()).and_then(|doc_id| {
+                Self::record_document_metadata(&conn, doc_id, &ingested.title, &ingested.metadata)?;
+                Ok(doc_id)
+            })
         };
         let doc_id = match doc_id_result {
             Ok(id) => {
@@ -560,7 +1711,7 @@ impl DocumentStore {
         // let name = file_name.clone();
         match store
         .process_embeddings(doc_id
-            , ingested.content.clone(), file_name, &provider, app_handle.clone())
+            , ingested.content.clone(), &ingested.segments, file_name, &provider, app_handle.clone(), None)
             .await
             {
                 Ok(_) => {
@@ -656,10 +1807,13 @@ impl DocumentStore {
                         "level": "error"
                     }))?;
                     
-                    // Add specific handling for PDF-related errors
-                    if path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("pdf")) {
-                        let pdf_help = "This might be due to missing PDFium library. \
-                Make sure the application resources include libpdfium for your platform.";
+                    // The error names the engine that failed; only a forced PDFium engine
+                    // has a library the user may need to install
+                    let forced_pdfium = ingestor.as_any().downcast_ref::<PdfIngestor>()
+                    .map_or(false, |pdf_ingestor| pdf_ingestor.engine() == PdfEngine::Pdfium);
+                    if forced_pdfium {
+                        let pdf_help = "The PDF engine is set to PDFium. If its library is missing, make sure the \
+                application resources include libpdfium for your platform, or set the PDF engine to auto.";
                         
                         app_handle.emit("simple-log-message", json!({
                             "message": pdf_help,
@@ -678,10 +1832,10 @@ impl DocumentStore {
             //println!("Ingested document: {:?}", ingested);
             let document = Document {
                 id: 0,
-                name: ingested.title,
+                name: ingested.title.clone(),
                 created_at: chrono::Local::now().to_rfc3339(),
-                file_path: ingested.metadata.source_path,
-                embedding_model_name: "dk".to_string(),
+                file_path: ingested.metadata.source_path.clone(),
+                embedding_model_name: provider.get_preferred_embedding_model(),
                 notes: "".to_string(),
                 //embedding: vec![],
             };
@@ -691,7 +1845,10 @@ impl DocumentStore {
             
             let doc_id_result = {
                 let conn = store.conn.lock().await;
-                store.add_document_internal(&conn, document)
+                store.add_document_internal(&conn, document).and_then(|doc_id| {
+                    Self::record_document_metadata(&conn, doc_id, &ingested.title, &ingested.metadata)?;
+                    Ok(doc_id)
+                })
             };
             let doc_id = match doc_id_result {
                 Ok(id) => {
@@ -726,7 +1883,7 @@ impl DocumentStore {
             /****************************************/
             /****************************************/
             match store
-            .process_embeddings(doc_id, ingested.content, file_name, &provider, app_handle.clone())
+            .process_embeddings(doc_id, ingested.content, &ingested.segments, file_name, &provider, app_handle.clone(), None)
             .await
             {
                 Ok(_) => {
@@ -755,17 +1912,149 @@ impl DocumentStore {
             Ok(())
         }
         
+        /// The document already ingested from `file_path`, with whichever embedding models.
+        /// A canon holds one document per source; a model it lacks is added to its chunks.
+        pub(crate) async fn find_document_by_source(
+            &self,
+            file_path: &str,
+        ) -> Result<Option<StoredSource>, Box<dyn std::error::Error>> {
+            let conn = self.conn.lock().await;
+            let source = conn
+            .query_row(
+                "SELECT id, file_path, content_hash, source_modified_at, extraction_version FROM documents
+                WHERE file_path = ?1
+                ORDER BY id LIMIT 1",
+                params![file_path],
+                row_to_stored_source,
+            )
+            .optional()?;
+            Ok(source)
+        }
+        
+        /// A fully embedded document with exactly this content, wherever it was ingested
+        /// from and whichever models embedded it
+        pub(crate) async fn find_document_by_hash(
+            &self,
+            content_hash: &str,
+        ) -> Result<Option<StoredSource>, Box<dyn std::error::Error>> {
+            let conn = self.conn.lock().await;
+            let source = conn
+            .query_row(
+                "SELECT id, file_path, content_hash, source_modified_at, extraction_version FROM documents
+                WHERE content_hash = ?1
+                ORDER BY id LIMIT 1",
+                params![content_hash],
+                row_to_stored_source,
+            )
+            .optional()?;
+            Ok(source)
+        }
+        
+        /// Stored chunks without a vector for the model, as (chunk id, text) in id order;
+        /// only one document's with `doc_id`
+        fn chunks_missing_vectors(
+            conn: &Connection,
+            embedding_model_name: &str,
+            doc_id: Option<i64>,
+        ) -> Result<Vec<(i64, String)>, Box<dyn std::error::Error>> {
+            let missing = match Self::vector_index_for_model(conn, embedding_model_name)? {
+                Some(index) => format!("id NOT IN (SELECT rowid FROM {})", index.table_name),
+                None => "1".to_string(),
+            };
+            let mut stmt = conn.prepare(&format!(
+                "SELECT id, chunk FROM embeddings WHERE {} AND (?1 IS NULL OR doc_id = ?1) ORDER BY id",
+                missing
+            ))?;
+            let rows = stmt.query_map(params![doc_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            Ok(rows.collect::<Result<_, _>>()?)
+        }
+        
+        /// Embeds stored chunks with the provider's model in batches, committing each batch
+        /// as it arrives. `on_batch` hears how many are done and the batch's last chunk.
+        async fn embed_stored_chunks(
+            &self,
+            pending: &[(i64, String)],
+            provider: &Provider,
+            mut on_batch: impl FnMut(usize, &str) -> Result<(), Box<dyn std::error::Error>>,
+        ) -> Result<usize, Box<dyn std::error::Error>> {
+            let embedding_model = provider.get_preferred_embedding_model();
+            // Same batching as ingestion; `buffered` keeps batches in order
+            let batch_config = &self.embedding_batch_config;
+            let model_name = embedding_model.as_str();
+            let mut embedded_batches = stream::iter(pending.chunks(batch_config.batch_size.max(1)))
+            .map(|batch| async move {
+                let texts: Vec<String> = batch.iter().map(|(_, chunk)| chunk.clone()).collect();
+                let vectors = Self::embed_batch_with_retry(provider, model_name, &texts, batch_config).await;
+                (batch, vectors)
+            })
+            .buffered(batch_config.max_concurrent_batches.max(1));
+            
+            let mut embedded_count = 0;
+            while let Some((batch, vectors)) = embedded_batches.next().await {
+                let vectors = vectors?;
+                {
+                    let mut conn = self.conn.lock().await;
+                    let tx = conn.transaction()?;
+                    for ((chunk_id, _), vector) in batch.iter().zip(vectors.iter()) {
+                        Self::insert_vector(&tx, *chunk_id, vector, &embedding_model)?;
+                    }
+                    Self::record_index_provider(&tx, &embedding_model, &provider.get_provider_name())?;
+                    tx.commit()?;
+                }
+                embedded_count += batch.len();
+                on_batch(embedded_count, batch.last().map(|(_, chunk)| chunk.as_str()).unwrap_or_default())?;
+            }
+            Ok(embedded_count)
+        }
+        
+        /// Adds the provider's model's vectors to a document's chunks that lack them, from
+        /// the text already stored. Returns how many chunks were embedded.
+        pub(crate) async fn embed_missing_vectors(
+            &self,
+            doc_id: i64,
+            provider: &Provider,
+        ) -> Result<usize, Box<dyn std::error::Error>> {
+            let embedding_model = provider.get_preferred_embedding_model();
+            let pending = {
+                let conn = self.conn.lock().await;
+                Self::chunks_missing_vectors(&conn, &embedding_model, Some(doc_id))?
+            };
+            if pending.is_empty() {
+                return Ok(0);
+            }
+            let embedded = self.embed_stored_chunks(&pending, provider, |_, _| Ok(())).await?;
+            {
+                let conn = self.conn.lock().await;
+                Self::record_canon_embedding_model(&conn, &self.canon_name, &embedding_model)?;
+            }
+            log::info!("Embedded {} stored chunks of document {} with {}", embedded, doc_id, embedding_model);
+            Ok(embedded)
+        }
+        
+        /// Stamps a document with the hash and modification time of the content it was
+        /// embedded from. Only done once embedding finishes, so a half-embedded document
+        /// never looks up to date.
+        pub(crate) async fn record_document_source(
+            &self,
+            doc_id: i64,
+            content_hash: &str,
+            source_modified_at: Option<&str>,
+            extraction_version: Option<u32>,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            let conn = self.conn.lock().await;
+            conn.execute(
+                "UPDATE documents SET content_hash = ?1, source_modified_at = ?2, extraction_version = ?3 WHERE id = ?4",
+                params![content_hash, source_modified_at, extraction_version, doc_id],
+            )?;
+            Ok(())
+        }
+        
         pub async fn delete_document(&self, doc_id: i64) -> Result<(), Box<dyn std::error::Error>> {
             let mut conn = self.conn.lock().await;
             
             // Start a transaction to ensure atomicity
             let tx = conn.transaction()?;
-            
-            // Delete embeddings associated with the document
-            tx.execute("DELETE FROM embeddings WHERE doc_id = ?1", params![doc_id])?;
-            
-            // Delete the document itself
-            tx.execute("DELETE FROM documents WHERE id = ?1", params![doc_id])?;
+            Self::delete_document_rows(&tx, doc_id)?;
             
             // Commit the transaction
             tx.commit()?;
@@ -773,6 +2062,30 @@ impl DocumentStore {
             Ok(())
         }
         
+        /// Deletes a document with its chunks and their vectors in every model's index
+        pub(crate) fn delete_document_rows(conn: &Connection, doc_id: i64) -> Result<(), rusqlite::Error> {
+            let table_names: Vec<String> = {
+                let mut stmt = conn.prepare("SELECT table_name FROM vector_indexes")?;
+                let rows = stmt.query_map([], |row| row.get(0))?;
+                rows.collect::<Result<_, _>>()?
+            };
+            for table_name in table_names {
+                conn.execute(
+                    &format!("DELETE FROM {} WHERE rowid IN (SELECT id FROM embeddings WHERE doc_id = ?1)", table_name),
+                    params![doc_id],
+                )?;
+            }
+            
+            // Delete embeddings associated with the document
+            conn.execute("DELETE FROM embeddings WHERE doc_id = ?1", params![doc_id])?;
+            conn.execute("DELETE FROM document_tags WHERE doc_id = ?1", params![doc_id])?;
+            conn.execute("DELETE FROM document_metadata WHERE doc_id = ?1", params![doc_id])?;
+            
+            // Delete the document itself
+            conn.execute("DELETE FROM documents WHERE id = ?1", params![doc_id])?;
+            Ok(())
+        }
+        
         pub fn get_database_path(&self) -> &str {
             &self.canon_path  // ✅ Already stored, just return it
         }
@@ -818,85 +2131,148 @@ impl DocumentStore {
         }
         
         
-        async fn process_embeddings(
+        /// Chunks and embeds a document's content. Chunks whose text is already stored for
+        /// the document are kept rather than embedded again, and stored chunks that no longer
+        /// appear are removed. When run from an ingestion job the checkpoint advances with
+        /// each committed batch. `segments` are the pages and sections the ingestor found,
+        /// recorded against each chunk that falls in them.
+        pub(crate) async fn process_embeddings(
             &self, 
             doc_id: i64, 
             content: String,
+            segments: &[DocumentSegment],
             file_name: String,
             provider: &Provider,
             //embedding_generator: &EmbeddingGenerator,
             app_handle: tauri::AppHandle,
+            checkpoint: Option<&JobCheckpoint>,
         ) -> Result<(), Box<dyn std::error::Error>> {
-            //println!("app_handle: {:?}", app_handle);
-            
-            
-            let conn = self.conn.lock().await;
             let embedding_model = provider.get_preferred_embedding_model();
             
-            // Update the embedding_model_name column in the documents table
-            conn.execute(
-                "UPDATE documents SET embedding_model_name = ?1 WHERE id = ?2",
-                params![embedding_model, doc_id],
-            )?;
+            // A resumed job must chunk exactly as before for its checkpoint to line up,
+            // so it keeps the chunking recorded when it started
+            let chunking_config = {
+                let conn = self.conn.lock().await;
+                let resuming = checkpoint.map(|c| c.completed_chunks > 0).unwrap_or(false);
+                let recorded = if resuming { Self::document_chunking_config(&conn, doc_id)? } else { None };
+                let chunking_config = recorded.unwrap_or(self.chunking_config);
+                
+                // Record the embedding model and chunking used for this document
+                conn.execute(
+                    "UPDATE documents SET embedding_model_name = ?1, chunking_strategy = ?2, chunking_params = ?3 WHERE id = ?4",
+                    params![embedding_model, chunking_config.strategy.as_str(), chunking_config.params_json(), doc_id],
+                )?;
+                Self::record_canon_embedding_model(&conn, &self.canon_name, &embedding_model)?;
+                chunking_config
+            };
             // Chunk the content
-            let chunks = chunk_text(&content, Self::DEFAULT_CHUNK_SIZE, Self::DEFAULT_CHUNK_OVERLAP); // adjust size/overlap as needed
+            let chunks = chunking_config.chunker().chunk(&content);
+            let provenance = chunking::chunk_provenance(&content, &chunks, segments);
+            
+            // Chunks already stored for this document keep their rows and vectors when their
+            // text is unchanged. That makes re-ingesting an edited file cheap, and it's also
+            // how a resumed job skips the batches it committed before it stopped.
+            let plan = {
+                let mut conn = self.conn.lock().await;
+                let tx = conn.transaction()?;
+                let plan = Self::plan_chunk_updates(&tx, doc_id, &embedding_model, &chunks)?;
+                for &(index, chunk_id) in &plan.reused {
+                    Self::set_chunk_position(&tx, chunk_id, index, &chunks[index], &provenance[index])?;
+                }
+                if let Some(checkpoint) = checkpoint {
+                    tx.execute(
+                        "UPDATE jobs SET total_chunks = ?1, completed_chunks = ?2, updated_at = ?3 WHERE id = ?4",
+                        params![chunks.len() as i64, plan.reused.len() as i64, Local::now().to_rfc3339(), checkpoint.job_id],
+                    )?;
+                }
+                tx.commit()?;
+                plan
+            };
+            let reused_count = plan.reused.len();
             
             // Emit progress update
             app_handle.emit("progress-indicator-load", json!({
                 "progress_id": format!("embedding_doc_id_{}",doc_id),
-                "current_step": 0,
+                "current_step": reused_count,
                 "total_steps": chunks.len() + 1,
                 "current_file": file_name,
                 "meta": content.chars().take(50).collect::<String>(),
             }))?;
             
-            // Get embeddings for each chunk
-            for (count, chunk) in chunks.iter().enumerate() {
-                let count = count + 1;
-                app_handle.emit("progress-indicator-update", json!({
-                    "progress_id": format!("embedding_doc_id_{}", doc_id),
-                    "current_step": count+1,
-                    "total_steps": chunks.len() + 1,
-                    "current_file": file_name,
-                    "meta": chunk,
-                }))?;
-                
-                
-                
-                //let embedding = embedding_generator.generate_embedding(app_handle.clone(), &chunk).await?;
-                let embedding_request = EmbeddingRequest {
-                    model: embedding_model.to_string(),
-                    input: vec![chunk.clone()],
-                };
-                
-                let embedding_response = provider.create_embeddings(embedding_request).await?;
-                let embedding = embedding_response.into_iter().next().ok_or_else(|| {
-                    std::io::Error::new(std::io::ErrorKind::Other, "No embedding data received")
-                })?;
-                
-                // Deserialize the embedding JSON to a serde_json::Value
-                let embedding_value: serde_json::Value = serde_json::to_value(&embedding)?;
-                
-                // Extract the vector field
-                let vector_value = embedding_value.get("vector").ok_or_else(|| {
-                    std::io::Error::new(std::io::ErrorKind::Other, "No vector field in embedding data")
-                })?;
-                
-                // Serialize the vector field back to a string
-                let vector_string = serde_json::to_string(vector_value)?;
-                
-                
+            // Send chunks to the provider in batches with a few requests in flight.
+            // `buffered` yields batches in order, so chunk order in the canon is preserved.
+            let batch_config = &self.embedding_batch_config;
+            let batches: Vec<Vec<(usize, Option<i64>)>> = plan
+            .to_embed
+            .chunks(batch_config.batch_size.max(1))
+            .map(|batch| batch.to_vec())
+            .collect();
+            let model_name = embedding_model.as_str();
+            let chunks_ref = &chunks;
+            let mut embedded_batches = stream::iter(batches)
+            .map(|batch| async move {
+                let texts: Vec<String> = batch.iter().map(|&(index, _)| chunks_ref[index].clone()).collect();
+                let vectors = Self::embed_batch_with_retry(provider, model_name, &texts, batch_config).await;
+                (batch, vectors)
+            })
+            .buffered(batch_config.max_concurrent_batches.max(1));
+            
+            let mut embedded_count = reused_count;
+            while let Some((batch, vectors)) = embedded_batches.next().await {
+                if let Some(checkpoint) = checkpoint {
+                    if checkpoint.is_cancelled() {
+                        return Err(Box::new(IngestionJobError::Cancelled(checkpoint.job_id)));
+                    }
+                }
+                let vectors = vectors?;
+                embedded_count += batch.len();
                 
-                // Convert embedding to JSON string
-                let embedding_json = serde_json::to_string(&embedding)?;
-                //println!("Embedding: {}", embedding_json);
-                // Store in database
-                conn.execute(
-                    "INSERT INTO embeddings (doc_id, chunk, embedding, embedding_model_name) VALUES (?1, ?2, ?3, ?4)",
-                    params![doc_id, chunk, vector_string, embedding_model],
-                )?;
+                // Only hold the connection for the insert transaction; the job checkpoint
+                // commits together with the chunks it covers
+                {
+                    let mut conn = self.conn.lock().await;
+                    let tx = conn.transaction()?;
+                    for (&(index, existing_chunk_id), vector) in batch.iter().zip(vectors.iter()) {
+                        let chunk_id = match existing_chunk_id {
+                            Some(chunk_id) => {
+                                Self::insert_vector(&tx, chunk_id, vector, &embedding_model)?;
+                                chunk_id
+                            }
+                            None => Self::insert_chunk_embedding(&tx, doc_id, &chunks[index], vector, &embedding_model)?,
+                        };
+                        Self::set_chunk_position(&tx, chunk_id, index, &chunks[index], &provenance[index])?;
+                    }
+                    Self::record_index_provider(&tx, &embedding_model, &provider.get_provider_name())?;
+                    if let Some(checkpoint) = checkpoint {
+                        tx.execute(
+                            "UPDATE jobs SET completed_chunks = ?1, updated_at = ?2 WHERE id = ?3",
+                            params![embedded_count as i64, Local::now().to_rfc3339(), checkpoint.job_id],
+                        )?;
+                    }
+                    tx.commit()?;
+                }
                 
+                app_handle.emit("progress-indicator-update", json!({
+                    "progress_id": format!("embedding_doc_id_{}", doc_id),
+                    "current_step": embedded_count + 1,
+                    "total_steps": chunks.len() + 1,
+                    "current_file": file_name,
+                    "meta": batch.last().map(|&(index, _)| chunks[index].clone()).unwrap_or_default(),
+                }))?;
             }
+            
+            // Text that was edited out of the document goes only once its replacement is in
+            if !plan.stale.is_empty() {
+                let mut conn = self.conn.lock().await;
+                let tx = conn.transaction()?;
+                Self::delete_chunks(&tx, &plan.stale)?;
+                tx.commit()?;
+            }
+            log::info!(
+                "{}: kept {} unchanged chunks, embedded {}, removed {}",
+                file_name, reused_count, plan.to_embed.len(), plan.stale.len()
+            );
+            
             // Emit final progress update
             app_handle.emit("progress-update", json!({
                 "progress_id": "document-processing",
@@ -917,8 +2293,210 @@ impl DocumentStore {
             Ok(())
         }
         
+        /// Embeds every stored chunk that doesn't yet have a vector for the provider's
+        /// embedding model, leaving vectors for other models in place. Sources are not
+        /// re-read; the chunk text already in the canon is what gets embedded. Chunks
+        /// embedded earlier are skipped, so an interrupted run picks up where it stopped.
+        pub async fn reembed_canon(
+            &self,
+            provider: &Provider,
+            app_handle: tauri::AppHandle,
+        ) -> Result<Vec<EmbeddingCoverage>, Box<dyn std::error::Error>> {
+            let embedding_model = provider.get_preferred_embedding_model();
+            let progress_id = format!("reembed_{}", sanitize_table_suffix(&embedding_model));
+            
+            let pending = {
+                let conn = self.conn.lock().await;
+                Self::chunks_missing_vectors(&conn, &embedding_model, None)?
+            };
+            
+            log::info!("Re-embedding {} chunks with {}", pending.len(), embedding_model);
+            app_handle.emit("progress-indicator-load", json!({
+                "progress_id": progress_id,
+                "current_step": 0,
+                "total_steps": pending.len(),
+                "current_file": format!("Re-embedding with {}", embedding_model),
+                "meta": "",
+            }))?;
+            
+            let embedded_count = self
+            .embed_stored_chunks(&pending, provider, |embedded_count, last_chunk| {
+                app_handle.emit("progress-indicator-update", json!({
+                    "progress_id": progress_id,
+                    "current_step": embedded_count,
+                    "total_steps": pending.len(),
+                    "current_file": format!("Re-embedding with {}", embedding_model),
+                    "meta": last_chunk.chars().take(50).collect::<String>(),
+                }))?;
+                Ok(())
+            })
+            .await?;
+            
+            app_handle.emit("simple-log-message", json!({
+                "message": format!("Re-embedded {} chunks with {}", embedded_count, embedding_model),
+                "timestamp": chrono::Local::now().to_rfc3339(),
+                "level": "info"
+            }))?;
+            
+            self.embedding_coverage().await
+        }
+        
+        /// How many of the canon's chunks have a vector for each embedding model
+        pub async fn embedding_coverage(&self) -> Result<Vec<EmbeddingCoverage>, Box<dyn std::error::Error>> {
+            let conn = self.conn.lock().await;
+            let total_chunks: i64 = conn.query_row("SELECT COUNT(*) FROM embeddings", [], |row| row.get(0))?;
+            
+            let indexes = Self::vector_indexes(&conn)?;
+            
+            let mut coverage = Vec::with_capacity(indexes.len());
+            for index in indexes {
+                let embedded_chunks: i64 = conn.query_row(
+                    &format!("SELECT COUNT(*) FROM embeddings WHERE id IN (SELECT rowid FROM {})", index.table_name),
+                    [],
+                    |row| row.get(0),
+                )?;
+                coverage.push(EmbeddingCoverage {
+                    embedding_model_name: index.embedding_model_name,
+                    dimension: index.dimension,
+                    normalized: index.normalized,
+                    provider: index.provider,
+                    created_at: index.created_at,
+                    embedded_chunks: embedded_chunks as usize,
+                    total_chunks: total_chunks as usize,
+                });
+            }
+            Ok(coverage)
+        }
+        
+        /// The embedding model recorded for this canon. Canons from before the model was
+        /// recorded fall back to whichever model holds the most vectors.
+        pub async fn canon_embedding_model(&self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+            let conn = self.conn.lock().await;
+            let recorded: Option<String> = conn
+            .query_row(
+                "SELECT embedding_model_name FROM canon WHERE embedding_model_name IS NOT NULL ORDER BY id LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+            if recorded.is_some() {
+                return Ok(recorded);
+            }
+            
+            let model = conn
+            .query_row(
+                "SELECT embedding_model_name FROM embeddings GROUP BY embedding_model_name ORDER BY COUNT(*) DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+            Ok(model)
+        }
+        
+        /// Compares the preferred embedding model with the models this canon holds vectors for.
+        /// An empty canon, or one that already has vectors for the preferred model, is fine.
+        pub async fn check_embedding_model(
+            &self,
+            preferred_model: &str,
+        ) -> Result<Option<EmbeddingModelMismatch>, Box<dyn std::error::Error>> {
+            let indexed_models: Vec<String> = {
+                let conn = self.conn.lock().await;
+                Self::vector_indexes(&conn)?
+                .into_iter()
+                .map(|index| index.embedding_model_name)
+                .collect()
+            };
+            if indexed_models.is_empty() || indexed_models.iter().any(|model| model == preferred_model) {
+                return Ok(None);
+            }
+            
+            let canon_model = self
+            .canon_embedding_model()
+            .await?
+            .unwrap_or_else(|| indexed_models[0].clone());
+            Ok(Some(EmbeddingModelMismatch {
+                canon_model,
+                preferred_model: preferred_model.to_string(),
+                indexed_models,
+            }))
+        }
+        
+        /// Embeds one batch of chunks, backing off and retrying when the provider rate limits us.
+        /// Vectors are returned in the same order as `batch`.
+        pub(crate) async fn embed_batch_with_retry<P: EmbeddingProvider + Sync + ?Sized>(
+            provider: &P,
+            embedding_model: &str,
+            batch: &[String],
+            config: &EmbeddingBatchConfig,
+        ) -> Result<Vec<Vec<f32>>, AIProviderError> {
+            Self::embed_batch_with_backoff(provider, embedding_model, batch, config, tokio::time::sleep).await
+        }
+        
+        /// `embed_batch_with_retry` waiting out each backoff with `sleep`
+        async fn embed_batch_with_backoff<P, S, F>(
+            provider: &P,
+            embedding_model: &str,
+            batch: &[String],
+            config: &EmbeddingBatchConfig,
+            mut sleep: S,
+        ) -> Result<Vec<Vec<f32>>, AIProviderError>
+        where
+            P: EmbeddingProvider + Sync + ?Sized,
+            S: FnMut(Duration) -> F,
+            F: std::future::Future<Output = ()>,
+        {
+            let mut delay = config.initial_backoff;
+            let mut attempt = 0;
+            loop {
+                let embedding_request = EmbeddingRequest {
+                    model: embedding_model.to_string(),
+                    input: batch.to_vec(),
+                };
+                match provider.create_embeddings(embedding_request).await {
+                    Ok(mut embeddings) => {
+                        if embeddings.len() != batch.len() {
+                            return Err(AIProviderError::Other(format!(
+                                "Expected {} embeddings but received {}", batch.len(), embeddings.len()
+                            )));
+                        }
+                        embeddings.sort_by_key(|embedding| embedding.index);
+                        return Ok(embeddings.into_iter().map(|embedding| embedding.vector).collect());
+                    }
+                    Err(AIProviderError::RateLimitExceeded) if attempt < config.max_retries => {
+                        attempt += 1;
+                        log::warn!(
+                            "Rate limited while embedding {} chunks, retrying in {}ms (attempt {}/{})",
+                            batch.len(), delay.as_millis(), attempt, config.max_retries
+                        );
+                        sleep(delay).await;
+                        delay *= 2;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        
+        /// Stores what the ingestor learned about a document: its title and source metadata
+        /// always, and its authors and tags when the source declares any, so hand edits
+        /// aren't wiped by sources that say nothing
+        pub(crate) fn record_document_metadata(
+            conn: &Connection,
+            doc_id: i64,
+            title: &str,
+            metadata: &DocumentMetadata,
+        ) -> Result<(), rusqlite::Error> {
+            conn.execute("UPDATE documents SET title = ?1 WHERE id = ?2", params![title, doc_id])?;
+            if !metadata.authors.is_empty() {
+                let authors_json = serde_json::to_string(&metadata.authors).unwrap_or_else(|_| "[]".to_string());
+                conn.execute("UPDATE documents SET authors = ?1 WHERE id = ?2", params![authors_json, doc_id])?;
+            }
+            collections::tag_document(conn, doc_id, &metadata.tags)?;
+            document_metadata::set_metadata(conn, doc_id, &SourceMetadata::from(metadata))?;
+            Ok(())
+        }
+        
         // Private helper function for database operations
-        fn add_document_internal(&self, conn: &Connection, document: Document) -> Result<i64, Box<dyn std::error::Error>> {
+        pub(crate) fn add_document_internal(&self, conn: &Connection, document: Document) -> Result<i64, Box<dyn std::error::Error>> {
             match conn.execute(
                 "INSERT INTO documents (name, created_at, file_path, embedding_model_name) VALUES (?1, ?2, ?3, ?4)",
                 params![
@@ -944,24 +2522,6 @@ impl DocumentStore {
         pub async fn update_document_pause_state(&self, doc_id: i64, paused: bool) -> Result<(), Box<dyn std::error::Error>> {
             let conn = self.conn.lock().await;
             
-            // Check if paused column exists in documents table
-            let has_paused_column = conn
-            .query_row(
-                "SELECT COUNT(*) FROM pragma_table_info('documents') WHERE name='paused'",
-                [],
-                |row| row.get::<_, i64>(0)
-            )?;
-            
-            // Add the column if it doesn't exist (handles migration for older databases)
-            if has_paused_column == 0 {
-                log::info!("Adding paused column to documents table");
-                conn.execute(
-                    "ALTER TABLE documents ADD COLUMN paused BOOLEAN DEFAULT 0",
-                    [],
-                )?;
-            }
-            
-            // Now we can safely update the pause state
             conn.execute(
                 "UPDATE documents SET paused = ?1 WHERE id = ?2",
                 params![paused, doc_id],
@@ -975,20 +2535,6 @@ impl DocumentStore {
         pub async fn is_document_paused(&self, doc_id: i64) -> Result<bool, Box<dyn std::error::Error>> {
             let conn = self.conn.lock().await;
             
-            // Check if paused column exists (just to be safe)
-            let has_paused_column = conn
-            .query_row(
-                "SELECT COUNT(*) FROM pragma_table_info('documents') WHERE name='paused'",
-                [],
-                |row| row.get::<_, i64>(0)
-            )?;
-            
-            if has_paused_column == 0 {
-                // Column doesn't exist, so nothing is paused
-                return Ok(false);
-            }
-            
-            // Get the paused state
             let paused: bool = conn.query_row(
                 "SELECT paused FROM documents WHERE id = ?1",
                 params![doc_id],
@@ -1032,7 +2578,8 @@ impl DocumentStore {
             let mut stmt = conn.prepare(
                 "SELECT id, doc_id, chunk, embedding_model_name 
                  FROM embeddings 
-                 WHERE doc_id = ?1"
+                 WHERE doc_id = ?1
+                 ORDER BY chunk_index, id"
             )?;
             
             let rows = stmt.query_map(params![doc_id], |row| {
@@ -1113,17 +2660,25 @@ impl DocumentStore {
             name: String,
             notes: String,
             authors_json: String,
+            weight: Option<f32>,
+            pinned: Option<bool>,
         ) -> Result<(), Box<dyn std::error::Error>> {
+            if let Some(weight) = weight {
+                if !weight.is_finite() || weight <= 0.0 {
+                    return Err(format!("Retrieval weight must be greater than zero, not {}", weight).into());
+                }
+            }
             let conn = self.conn.lock().await;
             
-            // Update the document details in the database
+            // Update the document details in the database; weight and pinning are left alone when not given
             conn.execute(
-                "UPDATE documents SET name = ?1, notes = ?2, authors = ?3 WHERE id = ?4",
-                params![name, notes, authors_json, doc_id],
+                "UPDATE documents SET name = ?1, notes = ?2, authors = ?3,
+                retrieval_weight = COALESCE(?4, retrieval_weight), pinned = COALESCE(?5, pinned) WHERE id = ?6",
+                params![name, notes, authors_json, weight.map(f64::from), pinned, doc_id],
             )?;
             
-            log::info!("Updated document details for document {}: name = {}, notes length = {}, authors = {}", 
-            doc_id, name, notes.len(), authors_json);
+            log::info!("Updated document details for document {}: name = {}, notes length = {}, authors = {}, weight = {:?}, pinned = {:?}", 
+            doc_id, name, notes.len(), authors_json, weight, pinned);
             
             Ok(())
         }
@@ -1151,7 +2706,7 @@ impl DocumentStore {
                 "SELECT id, doc_id, chunk, embedding_model_name 
                  FROM embeddings 
                  WHERE doc_id = ?1
-                 ORDER BY id
+                 ORDER BY chunk_index, id
                  LIMIT 1 OFFSET ?2",
                 params![doc_id, index as i64],
                 |row| {
@@ -1175,7 +2730,7 @@ impl DocumentStore {
             let id: i64 = conn.query_row(
                 "SELECT id FROM embeddings 
                  WHERE doc_id = ?1
-                 ORDER BY id
+                 ORDER BY chunk_index, id
                  LIMIT 1 OFFSET ?2",
                 params![doc_id, index as i64],
                 |row| row.get(0)
@@ -1201,55 +2756,739 @@ impl DocumentStore {
         }
     }
     
-    // Helper function for cosine similarity
-    fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    /// Registers sqlite-vec as an auto extension so every new connection gets vec0
+    fn register_sqlite_vec() {
+        static REGISTER: std::sync::Once = std::sync::Once::new();
+        REGISTER.call_once(|| unsafe {
+            rusqlite::ffi::sqlite3_auto_extension(Some(std::mem::transmute(
+                sqlite_vec::sqlite3_vec_init as *const (),
+            )));
+        });
+    }
+    
+    /// Packs a vector as the little-endian f32 blob sqlite-vec expects
+    fn vector_to_blob(vector: &[f32]) -> Vec<u8> {
+        vector.iter().flat_map(|value| value.to_le_bytes()).collect()
+    }
+    
+    /// Adds a column to an existing table when an older canon doesn't have it yet
+    pub(crate) fn add_column_if_missing(
+        conn: &Connection,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> Result<(), rusqlite::Error> {
+        let exists: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = ?1", table),
+            params![column],
+            |row| row.get(0),
+        )?;
+        if exists == 0 {
+            log::info!("Adding {} column to {} table", column, table);
+            conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+        }
+        Ok(())
+    }
+    
+    fn row_to_embedding_model_info(row: &rusqlite::Row) -> rusqlite::Result<EmbeddingModelInfo> {
+        Ok(EmbeddingModelInfo {
+            embedding_model_name: row.get(0)?,
+            table_name: row.get(1)?,
+            dimension: row.get::<_, i64>(2)? as usize,
+            normalized: row.get(3)?,
+            provider: row.get(4)?,
+            created_at: row.get(5)?,
+        })
+    }
+    
+    fn row_to_stored_source(row: &rusqlite::Row) -> rusqlite::Result<StoredSource> {
+        Ok(StoredSource {
+            id: row.get(0)?,
+            file_path: row.get(1)?,
+            content_hash: row.get(2)?,
+            source_modified_at: row.get(3)?,
+            extraction_version: row.get(4)?,
+        })
+    }
+    
+    /// Hex SHA-256 of a document's or chunk's text, used to spot unchanged content
+    pub(crate) fn content_hash(text: &str) -> String {
+        format!("{:x}", Sha256::digest(text.as_bytes()))
+    }
+    
+    /// Rebuilds a ChunkingConfig from the documents.chunking_strategy and chunking_params columns
+    fn parse_chunking_config(strategy: Option<String>, params: Option<String>) -> Option<ChunkingConfig> {
+        let strategy = ChunkingStrategy::from_name(&strategy?)?;
+        let params: serde_json::Value = serde_json::from_str(&params?).ok()?;
+        Some(ChunkingConfig::new(
+            strategy,
+            params.get("max_size")?.as_u64()? as usize,
+            params.get("overlap")?.as_u64()? as usize,
+        ))
+    }
+    
+    /// Unpacks a little-endian f32 blob read back from sqlite-vec
+    pub(crate) fn blob_to_vector(blob: &[u8]) -> Vec<f32> {
+        blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+    }
+    
+    /// Turns an embedding model name into something usable in a table name
+    fn sanitize_table_suffix(embedding_model_name: &str) -> String {
+        embedding_model_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect()
+    }
+    
+    /// Turns free text into an FTS5 query that ORs its distinct words together. Each word
+    /// is quoted so punctuation and FTS5 operators in the writer's text can't break the query.
+    fn fts_query(text: &str, max_terms: usize) -> Option<String> {
+        let mut seen = std::collections::HashSet::new();
+        let terms: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+        .map(|word| word.to_lowercase())
+        .filter(|word| seen.insert(word.clone()))
+        .take(max_terms)
+        .map(|word| format!("\"{}\"", word))
+        .collect();
+    
+        if terms.is_empty() {
+            None
+        } else {
+            Some(terms.join(" OR "))
+        }
+    }
+    
+    /// Weighted reciprocal rank fusion of two best-first result lists. A chunk's fused score is
+    /// (1 - w) / (k + vector rank) + w / (k + keyword rank); a list it's missing from adds nothing.
+    fn reciprocal_rank_fusion(
+        vector_hits: Vec<SearchHit>,
+        lexical_hits: Vec<SearchHit>,
+        lexical_weight: f32,
+        k: f32,
+    ) -> Vec<SearchHit> {
+        let mut fused: Vec<SearchHit> = Vec::with_capacity(vector_hits.len() + lexical_hits.len());
+        let mut positions: std::collections::HashMap<usize, usize> = std::collections::HashMap::new();
+    
+        for (rank, mut hit) in vector_hits.into_iter().enumerate() {
+            hit.fused_score = Some((1.0 - lexical_weight) / (k + rank as f32 + 1.0));
+            positions.insert(hit.chunk_id, fused.len());
+            fused.push(hit);
+        }
+    
+        for (rank, hit) in lexical_hits.into_iter().enumerate() {
+            let contribution = lexical_weight / (k + rank as f32 + 1.0);
+            match positions.get(&hit.chunk_id) {
+                Some(&position) => {
+                    fused[position].fused_score = Some(fused[position].fused_score.unwrap_or(0.0) + contribution);
+                    fused[position].lexical_score = hit.lexical_score;
+                }
+                None => {
+                    positions.insert(hit.chunk_id, fused.len());
+                    fused.push(SearchHit { fused_score: Some(contribution), ..hit });
+                }
+            }
+        }
+    
+        fused
+    }
+    
+    /// Maximal marginal relevance ordering of best-first hits. Each of the first `count`
+    /// picks maximises lambda * relevance - (1 - lambda) * (highest similarity to an
+    /// earlier pick), with relevance scaled so the top hit is 1.0 and fused and cosine
+    /// scores compare alike. The hits not picked follow in their original order.
+    /// Each candidate keeps its highest similarity so far, so this is O(n * count).
+    fn mmr_rerank(
+        hits: Vec<SearchHit>,
+        vectors: &std::collections::HashMap<usize, Vec<f32>>,
+        lambda: f32,
+        count: usize,
+    ) -> Vec<SearchHit> {
+        let max_score = hits.iter().map(|hit| hit.rank_score()).fold(f32::MIN, f32::max);
+        if max_score <= 0.0 {
+            return hits;
+        }
+        
+        let mut remaining: Vec<(SearchHit, f32)> = hits.into_iter().map(|hit| (hit, 0.0)).collect();
+        let mut selected: Vec<SearchHit> = Vec::with_capacity(remaining.len());
+        while selected.len() < count && !remaining.is_empty() {
+            let mut best_index = 0;
+            let mut best_score = f32::MIN;
+            for (index, (candidate, redundancy)) in remaining.iter().enumerate() {
+                let relevance = candidate.rank_score() / max_score;
+                let score = lambda * relevance - (1.0 - lambda) * redundancy;
+                if score > best_score {
+                    best_score = score;
+                    best_index = index;
+                }
+            }
+            let (picked, _) = remaining.remove(best_index);
+            if let Some(picked_vector) = vectors.get(&picked.chunk_id) {
+                for (candidate, redundancy) in remaining.iter_mut() {
+                    let similarity = vectors
+                    .get(&candidate.chunk_id)
+                    .and_then(|candidate_vector| cosine_similarity(candidate_vector, picked_vector));
+                    if let Some(similarity) = similarity {
+                        *redundancy = redundancy.max(similarity);
+                    }
+                }
+            }
+            selected.push(picked);
+        }
+        selected.extend(remaining.into_iter().map(|(hit, _)| hit));
+        selected
+    }
+    
+    // Helper function for cosine similarity. Vectors of different lengths come from
+    // different models and have no meaningful similarity, so there's no score for them.
+    fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+        if a.len() != b.len() {
+            return None;
+        }
         let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-        let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-        let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let norm_a = vector_norm(a);
+        let norm_b = vector_norm(b);
         
         if norm_a == 0.0 || norm_b == 0.0 {
-            0.0
+            Some(0.0)
         } else {
-            dot_product / (norm_a * norm_b)
+            Some(dot_product / (norm_a * norm_b))
         }
     }
     
-    /// Chunks text into segments with optional overlap
-    /// 
-    /// * `text` - The text to chunk
-    /// * `chunk_size` - Maximum size of each chunk in characters
-    /// * `overlap` - Number of characters to overlap between chunks
-    fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
-        let mut chunks = Vec::new();
-        let words: Vec<&str> = text.split_whitespace().collect();
-        let mut i = 0;
-        
-        while i < words.len() {
-            let mut chunk = String::new();
-            let mut j = i;
-            
-            // Build chunk up to chunk_size
-            while j < words.len() && (chunk.len() + words[j].len() + 1) <= chunk_size {
-                if !chunk.is_empty() {
-                    chunk.push(' ');
+    fn vector_norm(vector: &[f32]) -> f32 {
+        vector.iter().map(|x| x * x).sum::<f32>().sqrt()
+    }
+    
+    /// Whether a vector is unit length, allowing for the rounding models leave in f32 output
+    fn is_unit_length(vector: &[f32]) -> bool {
+        (vector_norm(vector) - 1.0).abs() <= DocumentStore::UNIT_LENGTH_TOLERANCE
+    }
+    
+    /// A bare document row for tests to hang chunks off, named after its file
+    #[cfg(test)]
+    pub(crate) fn insert_test_document(conn: &Connection, id: i64, file_path: &str, embedding_model: &str) {
+        let name = Path::new(file_path).file_name().map_or(file_path.to_string(), |name| name.to_string_lossy().into_owned());
+        conn.execute(
+            "INSERT INTO documents (id, name, created_at, file_path, embedding_model_name) VALUES (?1, ?2, 'now', ?3, ?4)",
+            params![id, name, file_path, embedding_model],
+        ).unwrap();
+    }
+    
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        
+        const LEGACY_SCHEMA: &str = "
+            CREATE TABLE documents (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            title TEXT,
+            authors JSON,
+            created_at TEXT NOT NULL,
+            file_path TEXT NOT NULL,
+            paused BOOLEAN DEFAULT 0,
+            embedding_model_name TEXT DEFAULT 'unknown',
+            notes TEXT DEFFAULT '',
+            UNIQUE(file_path, embedding_model_name)
+            );
+            CREATE TABLE embeddings (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            doc_id INTEGER NOT NULL,
+            chunk TEXT NOT NULL,
+            embedding JSON NOT NULL,
+            embedding_model_name TEXT DEFAULT 'unknown',
+            FOREIGN KEY(doc_id) REFERENCES documents(id)
+            );";
+        
+        #[test]
+        fn test_json_embeddings_migrate_to_sqlite_vec() {
+            let dir = tempfile::tempdir().unwrap();
+            let canon_path = dir.path().join("legacy.canon");
+            {
+                let conn = Connection::open(&canon_path).unwrap();
+                conn.execute_batch(LEGACY_SCHEMA).unwrap();
+                insert_test_document(&conn, 1, "/tmp/noir.md", "test-model");
+                conn.execute(
+                    "INSERT INTO embeddings (doc_id, chunk, embedding, embedding_model_name) VALUES (1, 'rain on the window', '[1.0, 0.0, 0.0]', 'test-model')",
+                    [],
+                ).unwrap();
+                conn.execute(
+                    "INSERT INTO embeddings (doc_id, chunk, embedding, embedding_model_name) VALUES (1, 'a gun in the drawer', '[0.0, 1.0, 0.0]', 'test-model')",
+                    [],
+                ).unwrap();
+            }
+            
+            let store = DocumentStore::new(canon_path.clone()).unwrap();
+            let conn = store.conn.blocking_lock();
+            
+            let json_columns: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('embeddings') WHERE name='embedding'",
+                [],
+                |row| row.get(0),
+            ).unwrap();
+            assert_eq!(json_columns, 0, "JSON embedding column should be dropped");
+            
+            let index = DocumentStore::vector_index_for_model(&conn, "test-model").unwrap().unwrap();
+            assert_eq!(index.dimension, 3);
+            
+            let results = DocumentStore::knn_search(&conn, "test-model", &[0.9, 0.1, 0.0], 2).unwrap();
+            assert_eq!(results.len(), 2);
+            assert_eq!(results[0].3, "rain on the window");
+            assert!(results[0].4 > results[1].4);
+        }
+        
+        #[test]
+        fn test_vector_index_rejects_dimension_change() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = DocumentStore::new(dir.path().join("fresh.canon")).unwrap();
+            let conn = store.conn.blocking_lock();
+            
+            DocumentStore::ensure_vector_index(&conn, "test-model", &[1.0, 0.0, 0.0]).unwrap();
+            let error = DocumentStore::ensure_vector_index(&conn, "test-model", &[0.5, 0.5, 0.5, 0.5]).unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<EmbeddingMetadataError>(),
+                Some(EmbeddingMetadataError::DimensionMismatch { expected: 3, actual: 4, .. })
+            ));
+        }
+        
+        #[test]
+        fn test_knn_search_looks_past_paused_documents() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = DocumentStore::new(dir.path().join("paused.canon")).unwrap();
+            let conn = store.conn.blocking_lock();
+            insert_test_document(&conn, 1, "/tmp/drafts.md", "test-model");
+            insert_test_document(&conn, 2, "/tmp/noir.md", "test-model");
+            conn.execute("UPDATE documents SET paused = 1 WHERE id = 1", []).unwrap();
+            
+            // More paused chunks than the first and second rounds fetch, all nearer the query
+            for index in 0..40 {
+                DocumentStore::insert_chunk_embedding(&conn, 1, &format!("draft {}", index), &[1.0, 0.0, 0.0], "test-model").unwrap();
+            }
+            DocumentStore::insert_chunk_embedding(&conn, 2, "rain on the window", &[0.6, 0.8, 0.0], "test-model").unwrap();
+            
+            let results = DocumentStore::knn_search(&conn, "test-model", &[1.0, 0.0, 0.0], 1).unwrap();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].0, 2);
+            assert_eq!(results[0].3, "rain on the window");
+        }
+        
+        #[test]
+        fn test_vector_index_records_and_enforces_normalization() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = DocumentStore::new(dir.path().join("normalized.canon")).unwrap();
+            let conn = store.conn.blocking_lock();
+            
+            DocumentStore::ensure_vector_index(&conn, "unit-model", &[0.6, 0.8]).unwrap();
+            DocumentStore::ensure_vector_index(&conn, "raw-model", &[3.0, 4.0]).unwrap();
+            DocumentStore::record_index_provider(&conn, "unit-model", "ollama").unwrap();
+            
+            let unit = DocumentStore::vector_index_for_model(&conn, "unit-model").unwrap().unwrap();
+            assert_eq!(unit.normalized, Some(true));
+            assert_eq!(unit.provider.as_deref(), Some("ollama"));
+            assert!(unit.created_at.is_some());
+            assert_eq!(DocumentStore::vector_index_for_model(&conn, "raw-model").unwrap().unwrap().normalized, Some(false));
+            
+            // A same-named model that doesn't normalize is caught before it's stored
+            let error = DocumentStore::ensure_vector_index(&conn, "unit-model", &[3.0, 4.0]).unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<EmbeddingMetadataError>(),
+                Some(EmbeddingMetadataError::NormalizationMismatch { .. })
+            ));
+            DocumentStore::ensure_vector_index(&conn, "raw-model", &[0.6, 0.8]).unwrap();
+        }
+        
+        #[test]
+        fn test_search_rejects_query_of_wrong_dimension() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = DocumentStore::new(dir.path().join("query.canon")).unwrap();
+            let conn = store.conn.blocking_lock();
+            insert_test_document(&conn, 1, "/tmp/noir.md", "test-model");
+            DocumentStore::insert_chunk_embedding(&conn, 1, "rain on the window", &[1.0, 0.0, 0.0], "test-model").unwrap();
+            
+            let error = DocumentStore::knn_search(&conn, "test-model", &[1.0, 0.0], 5).unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<EmbeddingMetadataError>(),
+                Some(EmbeddingMetadataError::DimensionMismatch { expected: 3, actual: 2, .. })
+            ));
+            assert!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0, 0.0]).is_none());
+        }
+
+        #[test]
+        fn test_pinned_documents_contribute_their_best_chunk() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = DocumentStore::new(dir.path().join("pinned.canon")).unwrap();
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            {
+                let conn = store.conn.blocking_lock();
+                for (id, name) in [(1, "style-bible.md"), (2, "noir.md")] {
+                    insert_test_document(&conn, id, name, "test-model");
                 }
-                chunk.push_str(words[j]);
-                j += 1;
+                DocumentStore::insert_chunk_embedding(&conn, 1, "short sentences", &[0.0, 1.0, 0.0], "test-model").unwrap();
+                DocumentStore::insert_chunk_embedding(&conn, 1, "no adverbs", &[0.0, 0.6, 0.8], "test-model").unwrap();
+                DocumentStore::insert_chunk_embedding(&conn, 2, "rain on the window", &[1.0, 0.0, 0.0], "test-model").unwrap();
+            }
+            runtime.block_on(store.update_document_details(1, "style-bible.md".to_string(), String::new(), "[]".to_string(), Some(2.0), Some(true))).unwrap();
+            assert!(runtime.block_on(store.update_document_details(2, "noir.md".to_string(), String::new(), "[]".to_string(), Some(0.0), None)).is_err());
+            
+            let conn = store.conn.blocking_lock();
+            assert_eq!(DocumentStore::document_weights(&conn).unwrap().get(&1), Some(&2.0));
+            // Far below any threshold, but pinned: the closer of its two chunks comes back
+            let pinned = store.pinned_hits(&conn, "test-model", &[0.0, 0.0, 1.0], &[], &|_| true).unwrap();
+            assert_eq!(pinned.len(), 1);
+            assert_eq!(pinned[0].chunk, "no adverbs");
+            assert!(pinned[0].pinned);
+            assert_eq!(pinned[0].rank_score(), pinned[0].similarity * 2.0);
+            assert!(store.pinned_hits(&conn, "test-model", &[0.0, 0.0, 1.0], &[], &|doc_id| doc_id != 1).unwrap().is_empty());
+        }
+
+        #[test]
+        fn test_keyword_index_follows_inserts_and_deletes() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = DocumentStore::new(dir.path().join("fts.canon")).unwrap();
+            let conn = store.conn.blocking_lock();
+            insert_test_document(&conn, 1, "/tmp/neo.md", "test-model");
+            let oracle_id = DocumentStore::insert_chunk_embedding(&conn, 1, "The oraculators hummed in the temple.", &[1.0, 0.0, 0.0], "test-model").unwrap();
+            DocumentStore::insert_chunk_embedding(&conn, 1, "Rain fell on the megalopolis.", &[0.0, 1.0, 0.0], "test-model").unwrap();
+
+            let results = DocumentStore::lexical_search(&conn, "test-model", "what did the Oraculators say?", 10, None).unwrap();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].2, oracle_id as usize);
+            assert!(results[0].4 > 0.0);
+
+            conn.execute("DELETE FROM embeddings WHERE id = ?1", params![oracle_id]).unwrap();
+            let results = DocumentStore::lexical_search(&conn, "test-model", "oraculators", 10, None).unwrap();
+            assert!(results.is_empty());
+        }
+
+        #[test]
+        fn test_filtered_search_reaches_documents_ranked_below_the_knn_cap() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = DocumentStore::new(dir.path().join("filtered.canon")).unwrap();
+            let conn = store.conn.blocking_lock();
+            for (id, name) in [(1, "noir.md"), (2, "style-bible.md")] {
+                insert_test_document(&conn, id, name, "test-model");
+            }
+            conn.execute_batch("BEGIN").unwrap();
+            for i in 0..DocumentStore::KNN_MAX_K + 10 {
+                DocumentStore::insert_chunk_embedding(&conn, 1, &format!("rain on the window {}", i), &[1.0, 0.1, 0.0], "test-model").unwrap();
+            }
+            conn.execute_batch("COMMIT").unwrap();
+            let tagged_id = DocumentStore::insert_chunk_embedding(&conn, 2, "no adverbs in the rain", &[0.0, 1.0, 0.0], "test-model").unwrap();
+
+            // Every one of the KNN's neighbours belongs to the other document
+            let unfiltered = DocumentStore::knn_search(&conn, "test-model", &[1.0, 0.0, 0.0], DocumentStore::KNN_MAX_K).unwrap();
+            assert!(unfiltered.iter().all(|candidate| candidate.0 == 1));
+
+            let style_bible = std::collections::HashSet::from([2]);
+            let results = DocumentStore::knn_search_within(&conn, "test-model", &[1.0, 0.0, 0.0], 5, &style_bible).unwrap();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].2, tagged_id as usize);
+            assert!(results[0].4.abs() < 1e-6);
+
+            let results = DocumentStore::lexical_search(&conn, "test-model", "rain", 5, Some(&style_bible)).unwrap();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].2, tagged_id as usize);
+            assert!(DocumentStore::knn_search_within(&conn, "test-model", &[1.0, 0.0, 0.0], 5, &Default::default()).unwrap().is_empty());
+        }
+
+        #[test]
+        fn test_canon_holds_vectors_for_several_models() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = DocumentStore::new(dir.path().join("models.canon")).unwrap();
+            {
+                let conn = store.conn.blocking_lock();
+                insert_test_document(&conn, 1, "/tmp/noir.md", "model-a");
+                let first = DocumentStore::insert_chunk_embedding(&conn, 1, "rain on the window", &[1.0, 0.0, 0.0], "model-a").unwrap();
+                DocumentStore::insert_chunk_embedding(&conn, 1, "a gun in the drawer", &[0.0, 1.0, 0.0], "model-a").unwrap();
+                
+                // Re-embedding adds a second model's vector to an existing chunk
+                DocumentStore::insert_vector(&conn, first, &[0.6, 0.8], "model-b").unwrap();
+                let results = DocumentStore::knn_search(&conn, "model-b", &[0.6, 0.8], 5).unwrap();
+                assert_eq!(results.len(), 1);
+                assert_eq!(results[0].3, "rain on the window");
+                assert_eq!(DocumentStore::knn_search(&conn, "model-a", &[1.0, 0.0, 0.0], 5).unwrap().len(), 2);
             }
             
-            chunks.push(chunk);
+            let coverage = tokio::runtime::Runtime::new().unwrap().block_on(store.embedding_coverage()).unwrap();
+            assert_eq!(coverage.len(), 2);
+            assert_eq!((coverage[0].embedding_model_name.as_str(), coverage[0].embedded_chunks, coverage[0].total_chunks), ("model-a", 2, 2));
+            assert_eq!((coverage[1].embedding_model_name.as_str(), coverage[1].embedded_chunks, coverage[1].dimension), ("model-b", 1, 2));
+        }
+        
+        #[test]
+        fn test_canon_records_its_embedding_model() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = DocumentStore::new(dir.path().join("recorded.canon")).unwrap();
+            let runtime = tokio::runtime::Runtime::new().unwrap();
             
-            // Move forward by chunk_size - overlap words for next iteration
-            let advance = if j > i {
-                ((j - i) as f32 * (1.0 - (overlap as f32 / chunk_size as f32))) as usize
-            } else {
-                1
+            // Nothing embedded yet, so any model will do
+            assert!(runtime.block_on(store.check_embedding_model("model-b")).unwrap().is_none());
+            {
+                let conn = store.conn.blocking_lock();
+                insert_test_document(&conn, 1, "/tmp/noir.md", "model-a");
+                let chunk = DocumentStore::insert_chunk_embedding(&conn, 1, "rain on the window", &[1.0, 0.0], "model-a").unwrap();
+                DocumentStore::record_canon_embedding_model(&conn, "recorded.canon", "model-a").unwrap();
+                
+                // A later model adds vectors but doesn't replace the canon's model
+                DocumentStore::insert_vector(&conn, chunk, &[0.0, 1.0, 0.0], "model-b").unwrap();
+                DocumentStore::record_canon_embedding_model(&conn, "recorded.canon", "model-b").unwrap();
+                let canon_rows: i64 = conn.query_row("SELECT COUNT(*) FROM canon", [], |row| row.get(0)).unwrap();
+                assert_eq!(canon_rows, 1);
+            }
+            
+            assert_eq!(runtime.block_on(store.canon_embedding_model()).unwrap().as_deref(), Some("model-a"));
+            assert!(runtime.block_on(store.check_embedding_model("model-a")).unwrap().is_none());
+            assert!(runtime.block_on(store.check_embedding_model("model-b")).unwrap().is_none());
+            
+            let mismatch = runtime.block_on(store.check_embedding_model("model-c")).unwrap().unwrap();
+            assert_eq!(mismatch.canon_model, "model-a");
+            assert_eq!(mismatch.indexed_models, vec!["model-a".to_string(), "model-b".to_string()]);
+        }
+        
+        #[test]
+        fn test_reingest_plan_keeps_unchanged_chunks() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = DocumentStore::new(dir.path().join("incremental.canon")).unwrap();
+            let conn = store.conn.blocking_lock();
+            insert_test_document(&conn, 1, "/tmp/noir.md", "test-model");
+            let old_chunks = ["rain on the window", "a gun in the drawer", "the phone rang twice"];
+            let mut ids = Vec::new();
+            for (index, chunk) in old_chunks.iter().enumerate() {
+                let id = DocumentStore::insert_chunk_embedding(&conn, 1, chunk, &[1.0, 0.0, 0.0], "test-model").unwrap();
+                DocumentStore::set_chunk_position(&conn, id, index, chunk, &ChunkProvenance::default()).unwrap();
+                ids.push(id);
+            }
+            
+            // The middle chunk was edited and a new one appended
+            let new_chunks: Vec<String> = ["rain on the window", "a knife in the drawer", "the phone rang twice", "she didn't answer"]
+            .iter()
+            .map(|chunk| chunk.to_string())
+            .collect();
+            let plan = DocumentStore::plan_chunk_updates(&conn, 1, "test-model", &new_chunks).unwrap();
+            assert_eq!(plan.reused, vec![(0, ids[0]), (2, ids[2])]);
+            assert_eq!(plan.to_embed, vec![(1, None), (3, None)]);
+            assert_eq!(plan.stale, vec![ids[1]]);
+            
+            // Under another model nothing has a vector yet, but the rows can still be reused
+            let plan = DocumentStore::plan_chunk_updates(&conn, 1, "other-model", &new_chunks).unwrap();
+            assert!(plan.reused.is_empty());
+            assert_eq!(plan.to_embed[0], (0, Some(ids[0])));
+            
+            DocumentStore::delete_chunks(&conn, &[ids[1]]).unwrap();
+            let remaining: i64 = conn.query_row("SELECT COUNT(*) FROM embeddings", [], |row| row.get(0)).unwrap();
+            assert_eq!(remaining, 2);
+            assert_eq!(DocumentStore::knn_search(&conn, "test-model", &[1.0, 0.0, 0.0], 5).unwrap().len(), 2);
+        }
+        
+        #[test]
+        fn test_identical_content_is_found_by_hash() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = DocumentStore::new(dir.path().join("dedupe.canon")).unwrap();
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            {
+                let conn = store.conn.blocking_lock();
+                insert_test_document(&conn, 1, "/tmp/noir.md", "test-model");
+            }
+            let hash = content_hash("It was a dark and stormy night.");
+            assert_eq!(hash.len(), 64);
+            assert_ne!(hash, content_hash("It was a dark and stormy night!"));
+            
+            // Not a duplicate until its embedding has finished and the hash is recorded
+            assert!(runtime.block_on(store.find_document_by_hash(&hash)).unwrap().is_none());
+            runtime.block_on(store.record_document_source(1, &hash, Some("2026-01-01T00:00:00+00:00"), Some(2))).unwrap();
+            
+            let duplicate = runtime.block_on(store.find_document_by_hash(&hash)).unwrap().unwrap();
+            assert_eq!((duplicate.id, duplicate.file_path.as_str()), (1, "/tmp/noir.md"));
+            let source = runtime.block_on(store.find_document_by_source("/tmp/noir.md")).unwrap().unwrap();
+            assert_eq!(source.source_modified_at.as_deref(), Some("2026-01-01T00:00:00+00:00"));
+            assert_eq!(source.extraction_version, Some(2));
+            
+            // Each ingestor reports its own extraction version
+            assert_eq!(store.extraction_version(&Resource::FilePath(PathBuf::from("/tmp/sleep.pdf"))), Some(2));
+            assert_eq!(store.extraction_version(&Resource::FilePath(PathBuf::from("/tmp/noir.md"))), Some(1));
+        }
+        
+        #[test]
+        fn test_switching_models_finds_the_document_and_its_unembedded_chunks() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = DocumentStore::new(dir.path().join("switch.canon")).unwrap();
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let (rain, gun) = {
+                let conn = store.conn.blocking_lock();
+                insert_test_document(&conn, 1, "/tmp/noir.md", "model-a");
+                let rain = DocumentStore::insert_chunk_embedding(&conn, 1, "rain on the window", &[1.0, 0.0, 0.0], "model-a").unwrap();
+                let gun = DocumentStore::insert_chunk_embedding(&conn, 1, "a gun in the drawer", &[0.0, 1.0, 0.0], "model-a").unwrap();
+                (rain, gun)
+            };
+            let hash = content_hash("rain on the window\n\na gun in the drawer");
+            runtime.block_on(store.record_document_source(1, &hash, None, None)).unwrap();
+            
+            // The source and its content are the same document whichever model asks
+            assert_eq!(runtime.block_on(store.find_document_by_source("/tmp/noir.md")).unwrap().unwrap().id, 1);
+            assert_eq!(runtime.block_on(store.find_document_by_hash(&hash)).unwrap().unwrap().id, 1);
+            
+            let conn = store.conn.blocking_lock();
+            let missing = DocumentStore::chunks_missing_vectors(&conn, "model-b", Some(1)).unwrap();
+            assert_eq!(missing.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![rain, gun]);
+            DocumentStore::insert_vector(&conn, rain, &[0.6, 0.8], "model-b").unwrap();
+            let missing = DocumentStore::chunks_missing_vectors(&conn, "model-b", Some(1)).unwrap();
+            assert_eq!(missing, vec![(gun, "a gun in the drawer".to_string())]);
+            assert!(DocumentStore::chunks_missing_vectors(&conn, "model-a", None).unwrap().is_empty());
+            assert!(DocumentStore::chunks_missing_vectors(&conn, "model-b", Some(2)).unwrap().is_empty());
+        }
+        
+        #[test]
+        fn test_hits_are_cited_with_page_and_section() {
+            let dir = tempfile::tempdir().unwrap();
+            let store = DocumentStore::new(dir.path().join("cited.canon")).unwrap();
+            let conn = store.conn.blocking_lock();
+            insert_test_document(&conn, 1, "/tmp/big_sleep.pdf", "test-model");
+            conn.execute(
+                "UPDATE documents SET title = 'The Big Sleep', authors = '[\"Raymond Chandler\"]' WHERE id = 1",
+                [],
+            ).unwrap();
+            let id = DocumentStore::insert_chunk_embedding(&conn, 1, "a gun in the drawer", &[1.0, 0.0, 0.0], "test-model").unwrap();
+            let provenance = ChunkProvenance {
+                start_offset: Some(120),
+                end_offset: Some(139),
+                page: Some(41),
+                section: Some("Chapter 3".to_string()),
+            };
+            DocumentStore::set_chunk_position(&conn, id, 0, "a gun in the drawer", &provenance).unwrap();
+
+            let mut hits = vec![SearchHit { doc_id: 1, chunk_id: id as usize, ..hit(0, None) }];
+            DocumentStore::cite_hits(&conn, &mut hits).unwrap();
+            assert_eq!(hits[0].provenance, provenance);
+            assert_eq!(hits[0].citation, "Raymond Chandler, The Big Sleep, Chapter 3, p. 41");
+            assert_eq!(citation(&[], "notes.txt", &ChunkProvenance::default()), "notes.txt");
+        }
+
+        fn hit(chunk_id: usize, lexical_score: Option<f32>) -> SearchHit {
+            SearchHit {
+                doc_id: chunk_id as i64,
+                doc_name: format!("doc {}", chunk_id),
+                chunk_id,
+                chunk: String::new(),
+                similarity: 0.5,
+                lexical_score,
+                fused_score: None,
+                canon_name: "test.canon".to_string(),
+                canon_weight: 1.0,
+                doc_weight: 1.0,
+                pinned: false,
+                provenance: ChunkProvenance::default(),
+                citation: String::new(),
+            }
+        }
+
+        #[test]
+        fn test_reciprocal_rank_fusion_rewards_agreement() {
+            let vector_hits = vec![hit(1, None), hit(2, None)];
+            let lexical_hits = vec![hit(2, Some(4.0)), hit(3, Some(2.0))];
+
+            let mut fused = reciprocal_rank_fusion(vector_hits, lexical_hits, 0.5, DocumentStore::RRF_K);
+            fused.sort_by(|a, b| b.rank_score().partial_cmp(&a.rank_score()).unwrap());
+
+            assert_eq!(fused.len(), 3);
+            assert_eq!(fused[0].chunk_id, 2, "a chunk found by both lists should rank first");
+            assert_eq!(fused[0].lexical_score, Some(4.0));
+
+            // All the weight on vectors leaves keyword-only hits with nothing
+            let fused = reciprocal_rank_fusion(vec![hit(1, None)], vec![hit(3, Some(2.0))], 0.0, DocumentStore::RRF_K);
+            let keyword_only = fused.iter().find(|hit| hit.chunk_id == 3).unwrap();
+            assert_eq!(keyword_only.fused_score, Some(0.0));
+        }
+
+        #[test]
+        fn test_mmr_rerank_demotes_near_duplicates() {
+            let mut hits = vec![hit(1, None), hit(2, None), hit(3, None)];
+            hits[0].similarity = 0.95;
+            hits[1].similarity = 0.94;
+            hits[2].similarity = 0.80;
+            let mut vectors = std::collections::HashMap::new();
+            vectors.insert(1, vec![1.0, 0.0, 0.0]);
+            vectors.insert(2, vec![0.99, 0.1, 0.0]);
+            vectors.insert(3, vec![0.0, 1.0, 0.0]);
+            
+            let reranked = mmr_rerank(hits.clone(), &vectors, 0.5, 3);
+            let order: Vec<usize> = reranked.iter().map(|hit| hit.chunk_id).collect();
+            assert_eq!(order, vec![1, 3, 2], "the paraphrase of chunk 1 should drop behind distinct material");
+            
+            // Only the first pick is chosen for diversity; the rest keep their ranking
+            let reranked = mmr_rerank(hits.clone(), &vectors, 0.5, 1);
+            let order: Vec<usize> = reranked.iter().map(|hit| hit.chunk_id).collect();
+            assert_eq!(order, vec![1, 2, 3]);
+            
+            let unchanged = mmr_rerank(hits, &vectors, 1.0, 3);
+            let order: Vec<usize> = unchanged.iter().map(|hit| hit.chunk_id).collect();
+            assert_eq!(order, vec![1, 2, 3]);
+        }
+        
+        /// Rate limits the first `failures` requests, then embeds each text as its index
+        struct RateLimitedProvider {
+            failures: u32,
+            calls: std::sync::atomic::AtomicU32,
+        }
+        
+        #[async_trait]
+        impl EmbeddingProvider for RateLimitedProvider {
+            async fn create_embeddings(&self, request: EmbeddingRequest) -> Result<Vec<ai::models::Embedding>, AIProviderError> {
+                let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                if call < self.failures {
+                    return Err(AIProviderError::RateLimitExceeded);
+                }
+                // Out of order, as some providers return them
+                Ok((0..request.input.len())
+                .rev()
+                .map(|index| ai::models::Embedding { vector: vec![index as f32], index, model_name: None })
+                .collect())
+            }
+        }
+        
+        #[test]
+        fn test_embedding_batches_back_off_and_retry_when_rate_limited() {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let config = EmbeddingBatchConfig {
+                max_retries: 3,
+                initial_backoff: Duration::from_millis(5),
+                ..Default::default()
             };
-            i += advance.max(1);
+            let batch = vec!["rain".to_string(), "gun".to_string()];
+            
+            let provider = RateLimitedProvider { failures: 2, calls: Default::default() };
+            let mut delays = Vec::new();
+            let vectors = runtime.block_on(DocumentStore::embed_batch_with_backoff(&provider, "test-model", &batch, &config, |delay| {
+                delays.push(delay);
+                std::future::ready(())
+            }))
+            .unwrap();
+            assert_eq!(vectors, vec![vec![0.0], vec![1.0]]);
+            assert_eq!(provider.calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+            // 5ms, then double that
+            assert_eq!(delays, vec![Duration::from_millis(5), Duration::from_millis(10)]);
+            
+            let provider = RateLimitedProvider { failures: 10, calls: Default::default() };
+            let mut delays = Vec::new();
+            let error = runtime.block_on(DocumentStore::embed_batch_with_backoff(&provider, "test-model", &batch, &config, |delay| {
+                delays.push(delay);
+                std::future::ready(())
+            }))
+            .unwrap_err();
+            assert!(matches!(error, AIProviderError::RateLimitExceeded));
+            assert_eq!(provider.calls.load(std::sync::atomic::Ordering::SeqCst), 4, "one try and three retries");
+            assert_eq!(delays, vec![Duration::from_millis(5), Duration::from_millis(10), Duration::from_millis(20)]);
         }
         
-        chunks
+        #[test]
+        fn test_fts_query_quotes_terms() {
+            assert_eq!(
+                fts_query("Oraculators AND \"daemon\" -processes", 10).unwrap(),
+                "\"oraculators\" OR \"and\" OR \"daemon\" OR \"processes\""
+            );
+            assert!(fts_query("a ? !", 10).is_none());
+        }
     }
-    
-    
-    
\ No newline at end of file
@@ -13,6 +13,7 @@ use tokio::time::{timeout, sleep};
 use std::time::Duration;
 use serde_json::json;
 use tauri::Emitter;
+use crate::chunking::{Chunker, WindowChunker};
 
 #[derive(Debug, Clone)]
 pub struct EmbeddingGenerator {
@@ -59,35 +60,7 @@ impl EmbeddingGenerator {
     /// * `chunk_size` - Maximum size of each chunk in characters
     /// * `overlap` - Number of characters to overlap between chunks
     pub fn chunk_text(&self, text: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
-        let mut chunks = Vec::new();
-        let words: Vec<&str> = text.split_whitespace().collect();
-        let mut i = 0;
-        
-        while i < words.len() {
-            let mut chunk = String::new();
-            let mut j = i;
-            
-            // Build chunk up to chunk_size
-            while j < words.len() && (chunk.len() + words[j].len() + 1) <= chunk_size {
-                if !chunk.is_empty() {
-                    chunk.push(' ');
-                }
-                chunk.push_str(words[j]);
-                j += 1;
-            }
-            
-            chunks.push(chunk);
-            
-            // Move forward by chunk_size - overlap words for next iteration
-            let advance = if j > i {
-                ((j - i) as f32 * (1.0 - (overlap as f32 / chunk_size as f32))) as usize
-            } else {
-                1
-            };
-            i += advance.max(1);
-        }
-        
-        chunks
+        WindowChunker::new(chunk_size, overlap).chunk(text)
     }
     
     pub async fn generate_embeddings(
@@ -197,10 +170,11 @@ mod tests {
         assert!(chunks.len() > 1);
         assert!(chunks[0].len() <= 10);
         
-        // Check overlap
+        // Check overlap: whole trailing words of up to `overlap` characters carry over
         if chunks.len() > 1 {
-            let overlap_text = &chunks[0][chunks[0].len()-2..];
-            assert!(chunks[1].starts_with(overlap_text));
+            let overlap_word = chunks[0].split_whitespace().last().unwrap();
+            assert!(overlap_word.len() <= 2);
+            assert!(chunks[1].starts_with(overlap_word));
         }
     }
     
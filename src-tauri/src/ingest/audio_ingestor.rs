@@ -64,10 +64,13 @@ impl AudioIngestor {
                 source_type: "audio".to_string(),
                 source_path: path.to_string_lossy().to_string(),
                 author: None,
+                authors: Vec::new(),
                 created_date: Some(Utc::now().naive_utc().to_string()),  // Set created_date to the current date
                 modified_date: Some(Utc::now().naive_utc().to_string()),  // Set created_date to the current date
                 frontmatter: HashMap::new(),
-            }
+                tags: Vec::new(),
+            },
+            segments: Vec::new(),
         })
     }
 }
\ No newline at end of file
@@ -26,17 +26,51 @@ pub struct IngestedDocument {
     pub title: String,
     pub content: String,
     pub metadata: DocumentMetadata,
+    /// Pages, chapters or sections of `content`, for ingestors that know them
+    pub segments: Vec<DocumentSegment>,
+}
+
+/// A structural part of a document's content. `start` and `end` are character offsets
+/// into `IngestedDocument::content`. Segments of different kinds may overlap, say a
+/// PDF's pages and the chapters running across them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentSegment {
+    pub start: usize,
+    pub end: usize,
+    /// 1-based page number
+    pub page: Option<u32>,
+    /// Chapter or heading title
+    pub section: Option<String>,
+}
+
+impl DocumentSegment {
+    pub fn page(start: usize, end: usize, page: u32) -> Self {
+        Self { start, end, page: Some(page), section: None }
+    }
+
+    pub fn section(start: usize, end: usize, title: impl Into<String>) -> Self {
+        Self { start, end, page: None, section: Some(title.into()) }
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
 }
 
 #[derive(Debug)]
 pub struct DocumentMetadata {
     pub source_type: String,
     pub source_path: String,
+    /// All authors on one line
     pub author: Option<String>,
+    /// Each author, for the document's `authors` column
+    pub authors: Vec<String>,
     pub created_date: Option<String>,
     pub modified_date: Option<String>,
     // Change to Pod to handle complex YAML structures
     pub frontmatter: HashMap<String, Pod>,
+    /// Tags the source declares, added to the document's tags on ingest
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -104,6 +138,11 @@ pub trait DocumentIngestor: Send + Sync + std::fmt::Debug {
     
     /// Process a single resource and return its content
     async fn ingest(&self, resource: &Resource) -> Result<IngestedDocument, IngestError>;
+    /// Bumped whenever the text this ingestor produces for an unchanged source changes,
+    /// so documents read by an older version are known to be re-embedded for that reason
+    fn extraction_version(&self) -> u32 {
+        1
+    }
     // Add this method to enable downcasting
     fn as_any(&self) -> &dyn Any;
 }
\ No newline at end of file
@@ -1,18 +1,40 @@
 use async_trait::async_trait;
-use epub::doc::EpubDoc;
-use regex::Regex;
-use std::path::Path;
+use epub::doc::{EpubDoc, NavPoint};
+use std::path::{Path, PathBuf};
 use std::collections::HashMap;
 use super::document_ingestor::{
     DocumentIngestor,
     IngestedDocument,
     DocumentMetadata,
+    DocumentSegment,
     IngestError,
     Resource
 };
+use super::html_text::html_to_text;
 use std::any::Any;
 use gray_matter::Pod;
 
+/// 2: chapters read from XHTML in spine order, front matter skipped
+const EXTRACTION_VERSION: u32 = 2;
+
+/// `epub:type` values that mark a page as boilerplate front matter or navigation. The
+/// generic "frontmatter" isn't one of them: prefaces, forewords and epigraphs carry it too.
+const FRONT_MATTER_TYPES: [&str; 11] = [
+    "cover", "titlepage", "halftitlepage", "toc", "landmarks", "loi", "lot", "copyright-page", "imprint", "dedication", "colophon",
+];
+
+/// Words in a page's manifest id or file name that mark it as front matter or navigation
+const FRONT_MATTER_NAMES: [&str; 9] = ["cover", "title", "titlepage", "halftitle", "toc", "nav", "contents", "copyright", "imprint"];
+
+/// Table of contents entries that name front matter rather than a chapter
+const FRONT_MATTER_LABELS: [&str; 6] = ["cover", "title page", "contents", "table of contents", "copyright", "copyright page"];
+
+struct EpubChapter {
+    title: Option<String>,
+    text: String,
+    skip: bool,
+}
+
 #[derive(Debug)]
 pub struct EpubIngestor;
 
@@ -41,6 +63,9 @@ impl DocumentIngestor for EpubIngestor {
             )),
         }
     }
+    fn extraction_version(&self) -> u32 {
+        EXTRACTION_VERSION
+    }
     fn as_any(&self) -> &dyn Any {
         self // This returns a reference to self as a type-erased &dyn Any
     }
@@ -60,16 +85,47 @@ impl EpubIngestor {
                 .to_string()
         });
 
-        let mut content = String::new();
-        let num_pages = book.get_num_pages();
-        let re = Regex::new(r"<[^>]*>").unwrap(); // Regex to match HTML tags
-        for i in 0..num_pages {
+        let chapter_titles = toc_titles(&book.toc);
+        let cover_id = book.get_cover_id();
+        // Pages follow the spine, the reading order the book declares
+        let mut chapters = Vec::new();
+        for i in 0..book.get_num_pages() {
             book.set_current_page(i);
-            if let Some((chapter_content, _)) = book.get_current_str() {
-                // Strip HTML tags from chapter_content
-                let stripped_content = re.replace_all(&chapter_content, "");
-                content.push_str(&stripped_content);
+            let id = book.get_current_id().unwrap_or_default();
+            let chapter_path = book.get_current_path();
+            let Some((chapter_content, _)) = book.get_current_str() else {
+                continue;
+            };
+            let page = html_to_text(&chapter_content);
+            if page.text.is_empty() {
+                continue;
+            }
+            let toc_title = chapter_path.as_ref().and_then(|chapter_path| chapter_titles.get(chapter_path).cloned());
+            let skip = cover_id.as_deref() == Some(id.as_str())
+                || is_front_matter(&id, chapter_path.as_deref(), toc_title.as_deref(), &page.epub_types);
+            // The table of contents names the chapter; failing that, its first heading
+            let title = toc_title.or(page.first_heading).filter(|title| !title.is_empty());
+            chapters.push(EpubChapter { title, text: page.text, skip });
+        }
+        // A book whose every page looks like front matter has been misjudged; keep it all
+        if chapters.iter().all(|chapter| chapter.skip) {
+            chapters.iter_mut().for_each(|chapter| chapter.skip = false);
+        }
+
+        let mut content = String::new();
+        let mut segments = Vec::new();
+        let mut offset = 0;
+        for chapter in chapters.into_iter().filter(|chapter| !chapter.skip) {
+            if !content.is_empty() {
+                content.push_str("\n\n");
+                offset += 2;
+            }
+            let length = chapter.text.chars().count();
+            content.push_str(&chapter.text);
+            if let Some(title) = chapter.title {
+                segments.push(DocumentSegment::section(offset, offset + length, title));
             }
+            offset += length;
         }
 
         let mut frontmatter: HashMap<String, Pod> = HashMap::new();
@@ -84,10 +140,68 @@ impl EpubIngestor {
                 source_type: "epub".to_string(),
                 source_path: path.to_string_lossy().to_string(),
                 author: book.mdata("creator"),
+                authors: book.mdata("creator").into_iter().collect(),
                 created_date: book.mdata("date"),
                 modified_date: None,
                 frontmatter,
-            }
+                tags: Vec::new(),
+            },
+            segments,
         })
     }
-}
\ No newline at end of file
+}
+
+/// Chapter titles from the table of contents, keyed by the chapter's file. Where
+/// several entries point into one file, the first one names it.
+fn toc_titles(points: &[NavPoint]) -> HashMap<PathBuf, String> {
+    let mut titles = HashMap::new();
+    fn collect(points: &[NavPoint], titles: &mut HashMap<PathBuf, String>) {
+        for point in points {
+            let content = point.content.to_string_lossy();
+            let file = content.split('#').next().unwrap_or_default();
+            titles.entry(PathBuf::from(file)).or_insert_with(|| point.label.trim().to_string());
+            collect(&point.children, titles);
+        }
+    }
+    collect(points, &mut titles);
+    titles
+}
+
+/// Whether a spine page is a cover, title page, copyright page or table of contents,
+/// judged by its `epub:type`, its manifest id and file name, or its TOC entry
+fn is_front_matter(id: &str, path: Option<&Path>, toc_label: Option<&str>, epub_types: &[String]) -> bool {
+    if epub_types.iter().any(|epub_type| FRONT_MATTER_TYPES.contains(&epub_type.as_str())) {
+        return true;
+    }
+    let stem = path.and_then(|path| path.file_stem()).map(|stem| stem.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let id = id.to_lowercase();
+    let named = [id.as_str(), stem.as_str()]
+    .iter()
+    .flat_map(|name| name.split(|c: char| !c.is_ascii_alphabetic()))
+    .any(|word| FRONT_MATTER_NAMES.contains(&word));
+    if named {
+        return true;
+    }
+    toc_label.map_or(false, |label| FRONT_MATTER_LABELS.contains(&label.trim().to_lowercase().as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_front_matter_is_recognised() {
+        let chapter = Path::new("OEBPS/Text/chapter01.xhtml");
+        assert!(!is_front_matter("ch01", Some(chapter), Some("Chapter 1"), &["bodymatter".to_string(), "chapter".to_string()]));
+        assert!(is_front_matter("ch01", Some(chapter), Some("Chapter 1"), &["toc".to_string()]));
+        // A foreword is front matter worth keeping; a dedication isn't
+        let foreword = Path::new("OEBPS/Text/foreword.xhtml");
+        assert!(!is_front_matter("fw", Some(foreword), Some("Foreword"), &["frontmatter".to_string(), "foreword".to_string()]));
+        assert!(is_front_matter("fw", Some(foreword), None, &["frontmatter".to_string(), "dedication".to_string()]));
+        assert!(is_front_matter("item3", Some(Path::new("OEBPS/Text/copyright.xhtml")), None, &[]));
+        assert!(is_front_matter("nav", Some(Path::new("OEBPS/nav.xhtml")), None, &[]));
+        assert!(is_front_matter("id7", Some(Path::new("OEBPS/part0002.html")), Some("Table of Contents"), &[]));
+        // "title" only as a whole word, not inside one
+        assert!(!is_front_matter("subtitles", Some(Path::new("OEBPS/entitled.xhtml")), None, &[]));
+    }
+}
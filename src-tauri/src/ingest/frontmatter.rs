@@ -0,0 +1,122 @@
+// src/ingest/frontmatter.rs
+//
+// YAML frontmatter shared by the Markdown and MDX ingestors: split off before the text
+// is chunked, and the common fields read out of it for the document's metadata.
+
+use std::collections::HashMap;
+
+use gray_matter::{engine::YAML, Matter, Pod};
+
+/// Keys read as the document's title, authors, date and tags, in order of preference
+const TITLE_KEYS: [&str; 1] = ["title"];
+const AUTHOR_KEYS: [&str; 2] = ["authors", "author"];
+const DATE_KEYS: [&str; 4] = ["date", "created", "created_date", "created_at"];
+const TAG_KEYS: [&str; 2] = ["tags", "keywords"];
+
+/// The fields of a frontmatter block that map onto document metadata
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrontmatterFields {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub created_date: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Splits a leading `---` YAML block from the text. Text without one, or with a block
+/// that isn't a mapping, comes back unchanged with an empty map.
+pub fn split_frontmatter(raw: &str) -> (String, HashMap<String, Pod>) {
+    let parsed = Matter::<YAML>::new().parse(raw);
+    match parsed.data {
+        Some(Pod::Hash(map)) => (parsed.content, map),
+        _ => (raw.to_string(), HashMap::new()),
+    }
+}
+
+/// A frontmatter value as JSON, for storing in the canon
+pub fn pod_to_json(pod: &Pod) -> serde_json::Value {
+    match pod {
+        Pod::Null => serde_json::Value::Null,
+        Pod::String(value) => serde_json::Value::String(value.clone()),
+        Pod::Integer(value) => serde_json::Value::from(*value),
+        Pod::Float(value) => serde_json::Number::from_f64(*value).map_or(serde_json::Value::Null, serde_json::Value::Number),
+        Pod::Boolean(value) => serde_json::Value::Bool(*value),
+        Pod::Array(items) => serde_json::Value::Array(items.iter().map(pod_to_json).collect()),
+        Pod::Hash(map) => serde_json::Value::Object(map.iter().map(|(key, value)| (key.clone(), pod_to_json(value))).collect()),
+    }
+}
+
+fn scalar(pod: &Pod) -> Option<String> {
+    let text = match pod {
+        Pod::String(value) => value.trim().to_string(),
+        Pod::Integer(value) => value.to_string(),
+        Pod::Float(value) => value.to_string(),
+        // `author: {name: ...}`, as some site generators write it
+        Pod::Hash(map) => return map.get("name").and_then(scalar),
+        _ => return None,
+    };
+    Some(text).filter(|text| !text.is_empty())
+}
+
+fn first_scalar(frontmatter: &HashMap<String, Pod>, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| frontmatter.get(*key).and_then(scalar))
+}
+
+/// A list under the first key present; a plain string is split on commas when
+/// `split_commas` is set, which suits tags but not names
+fn first_list(frontmatter: &HashMap<String, Pod>, keys: &[&str], split_commas: bool) -> Vec<String> {
+    let pod = match keys.iter().find_map(|key| frontmatter.get(*key)) {
+        Some(pod) => pod,
+        None => return Vec::new(),
+    };
+    let values: Vec<String> = match pod {
+        Pod::Array(items) => items.iter().filter_map(scalar).collect(),
+        Pod::String(value) if split_commas => value.split(',').map(|item| item.trim().to_string()).collect(),
+        other => scalar(other).into_iter().collect(),
+    };
+    values.into_iter().filter(|value| !value.is_empty()).collect()
+}
+
+impl FrontmatterFields {
+    pub fn from_frontmatter(frontmatter: &HashMap<String, Pod>) -> Self {
+        Self {
+            title: first_scalar(frontmatter, &TITLE_KEYS),
+            authors: first_list(frontmatter, &AUTHOR_KEYS, false),
+            created_date: first_scalar(frontmatter, &DATE_KEYS),
+            tags: first_list(frontmatter, &TAG_KEYS, true),
+        }
+    }
+
+    /// The authors as one line, for `DocumentMetadata::author`
+    pub fn author(&self) -> Option<String> {
+        Some(self.authors.join(", ")).filter(|author| !author.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frontmatter_is_split_off_and_mapped() {
+        let raw = "---\ntitle: The Big Sleep\nauthor:\n  - Raymond Chandler\ndate: 1939-02-06\ntags: noir, detective\n---\n# Chapter 1\nRain.\n";
+        let (content, frontmatter) = split_frontmatter(raw);
+        assert!(content.trim_start().starts_with("# Chapter 1"));
+        assert!(!content.contains("title:"));
+
+        let fields = FrontmatterFields::from_frontmatter(&frontmatter);
+        assert_eq!(fields.title.as_deref(), Some("The Big Sleep"));
+        assert_eq!(fields.authors, vec!["Raymond Chandler".to_string()]);
+        assert_eq!(fields.author().as_deref(), Some("Raymond Chandler"));
+        assert_eq!(fields.created_date.as_deref(), Some("1939-02-06"));
+        assert_eq!(fields.tags, vec!["noir".to_string(), "detective".to_string()]);
+    }
+
+    #[test]
+    fn test_text_without_frontmatter_is_unchanged() {
+        let raw = "# Notes\n\n---\n\nA rule, not frontmatter.";
+        let (content, frontmatter) = split_frontmatter(raw);
+        assert_eq!(content, raw);
+        assert!(frontmatter.is_empty());
+        assert_eq!(FrontmatterFields::from_frontmatter(&frontmatter), FrontmatterFields::default());
+    }
+}
@@ -0,0 +1,324 @@
+// src/ingest/html_text.rs
+//
+// Plain text from (X)HTML as found in EPUB chapters: block elements become paragraph
+// breaks, `<br>` a line break, runs of whitespace (no-break spaces included) a single
+// space, and character references are decoded. Head, script, style and nav contents
+// are dropped. This is a forgiving scanner rather than a parser; malformed markup
+// loses tags, not text.
+
+/// What a page of HTML says, as text
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HtmlText {
+    pub text: String,
+    /// Text of the first h1–h3, for naming a chapter the table of contents doesn't
+    pub first_heading: Option<String>,
+    /// The `epub:type` values of the page's `<body>` and its outermost `<section>`s,
+    /// lower-cased, such as "frontmatter" or "chapter". Types on anything nested deeper
+    /// (a "toc" nav in a chapter, a "footnote" aside) describe that part, not the page.
+    pub epub_types: Vec<String>,
+}
+
+/// Elements whose contents are never text to keep
+const SKIPPED: [&str; 6] = ["head", "script", "style", "nav", "svg", "template"];
+
+const BLOCKS: [&str; 30] = [
+    "address", "article", "aside", "blockquote", "body", "caption", "dd", "div", "dl", "dt",
+    "figcaption", "figure", "footer", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li",
+    "ol", "p", "pre", "section", "table", "td", "tr", "ul",
+];
+
+enum Break {
+    Line,
+    Paragraph,
+}
+
+#[derive(Default)]
+struct TextBuilder {
+    text: String,
+    pending_break: Option<Break>,
+    pending_space: bool,
+}
+
+impl TextBuilder {
+    fn push_str(&mut self, text: &str) {
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.pending_space = true;
+                continue;
+            }
+            if !self.text.is_empty() {
+                match self.pending_break.take() {
+                    Some(Break::Paragraph) => self.text.push_str("\n\n"),
+                    Some(Break::Line) => self.text.push('\n'),
+                    None if self.pending_space => self.text.push(' '),
+                    None => {}
+                }
+            }
+            self.pending_break = None;
+            self.pending_space = false;
+            self.text.push(c);
+        }
+    }
+
+    fn line_break(&mut self) {
+        if self.pending_break.is_none() {
+            self.pending_break = Some(Break::Line);
+        }
+    }
+
+    fn paragraph_break(&mut self) {
+        self.pending_break = Some(Break::Paragraph);
+    }
+}
+
+/// Longest entity reference between '&' and ';' that's decoded; "#x10FFFF" is eight
+const MAX_REFERENCE_LEN: usize = 10;
+
+fn named_entity(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "amp" => "&",
+        "lt" => "<",
+        "gt" => ">",
+        "quot" => "\"",
+        "apos" => "'",
+        "nbsp" => "\u{a0}",
+        "shy" => "",
+        "mdash" => "—",
+        "ndash" => "–",
+        "hellip" => "…",
+        "lsquo" => "‘",
+        "rsquo" => "’",
+        "ldquo" => "“",
+        "rdquo" => "”",
+        "laquo" => "«",
+        "raquo" => "»",
+        "copy" => "©",
+        "reg" => "®",
+        "trade" => "™",
+        "deg" => "°",
+        "middot" => "·",
+        "bull" => "•",
+        "sect" => "§",
+        "eacute" => "é",
+        "egrave" => "è",
+        "agrave" => "à",
+        "ccedil" => "ç",
+        "uuml" => "ü",
+        "ouml" => "ö",
+        "auml" => "ä",
+        "szlig" => "ß",
+        _ => return None,
+    })
+}
+
+/// Decodes `&name;`, `&#123;` and `&#x7b;`. Unknown references are left as written.
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        // Look for the ';' no further than the longest reference, so text full of bare
+        // ampersands stays linear
+        let reference = rest.as_bytes()[1..]
+        .iter()
+        .take(MAX_REFERENCE_LEN + 1)
+        .position(|&byte| byte == b';')
+        .filter(|&end| end > 0)
+        .map(|end| &rest[1..end + 1]);
+        let replacement = reference.and_then(|reference| match reference.strip_prefix('#') {
+            Some(number) => {
+                let code = match number.strip_prefix('x').or_else(|| number.strip_prefix('X')) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => number.parse().ok(),
+                };
+                code.and_then(char::from_u32).map(String::from)
+            }
+            None => named_entity(reference).map(String::from),
+        });
+        match (reference, replacement) {
+            (Some(reference), Some(replacement)) => {
+                decoded.push_str(&replacement);
+                rest = &rest[reference.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// The value of an attribute in a tag's source, quoted either way
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let lower = tag.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(found) = lower[from..].find(name) {
+        let start = from + found;
+        let preceded_by_space = lower[..start].ends_with(char::is_whitespace);
+        let after = lower[start + name.len()..].trim_start();
+        if preceded_by_space && after.starts_with('=') {
+            let value_start = tag.len() - after.len() + 1;
+            let value = tag[value_start..].trim_start();
+            let quote = value.chars().next()?;
+            if quote == '"' || quote == '\'' {
+                return value[1..].find(quote).map(|end| &value[1..end + 1]);
+            }
+            return value.split(|c: char| c.is_whitespace() || c == '/').next();
+        }
+        from = start + name.len();
+    }
+    None
+}
+
+pub fn html_to_text(html: &str) -> HtmlText {
+    // ASCII lower-casing keeps byte offsets, so tags can be searched for in `lower`
+    let lower = html.to_ascii_lowercase();
+    let mut builder = TextBuilder::default();
+    let mut first_heading: Option<String> = None;
+    let mut heading: Option<TextBuilder> = None;
+    let mut epub_types: Vec<String> = Vec::new();
+    let mut section_depth = 0usize;
+
+    let mut i = 0;
+    while i < html.len() {
+        let rest = &html[i..];
+        if rest.starts_with("<!--") {
+            i = lower[i..].find("-->").map_or(html.len(), |end| i + end + 3);
+            continue;
+        }
+        if rest.starts_with("<![CDATA[") {
+            let end = lower[i..].find("]]>").map_or(html.len(), |end| i + end);
+            builder.push_str(&html[i + 9..end]);
+            i = (end + 3).min(html.len());
+            continue;
+        }
+        if !rest.starts_with('<') {
+            let end = rest.find('<').map_or(html.len(), |end| i + end);
+            let text = decode_entities(&html[i..end]);
+            builder.push_str(&text);
+            if let Some(heading) = heading.as_mut() {
+                heading.push_str(&text);
+            }
+            i = end;
+            continue;
+        }
+
+        let end = match rest.find('>') {
+            Some(end) => i + end,
+            None => break,
+        };
+        let tag = &html[i + 1..end];
+        i = end + 1;
+        let closing = tag.starts_with('/');
+        let name: String = tag
+        .trim_start_matches('/')
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == ':' || *c == '-')
+        .collect::<String>()
+        .to_ascii_lowercase();
+        if name.is_empty() {
+            // <!DOCTYPE>, <?xml?> and stray angle brackets
+            continue;
+        }
+        let self_closing = tag.ends_with('/');
+
+        let describes_page = name == "body" || (name == "section" && section_depth == 0);
+        if !closing && describes_page {
+            if let Some(types) = attribute(tag, "epub:type") {
+                epub_types.extend(types.split_whitespace().map(|value| value.to_ascii_lowercase()));
+            }
+        }
+        if name == "section" && !self_closing {
+            if closing {
+                section_depth = section_depth.saturating_sub(1);
+            } else {
+                section_depth += 1;
+            }
+        }
+        if !closing && !self_closing && SKIPPED.contains(&name.as_str()) {
+            // Skip to the matching close tag, or to the end of a page that never closes it
+            let close = format!("</{}", name);
+            i = lower[i..].find(&close).map_or(html.len(), |found| {
+                let close_start = i + found;
+                lower[close_start..].find('>').map_or(html.len(), |end| close_start + end + 1)
+            });
+            continue;
+        }
+
+        let is_heading = matches!(name.as_str(), "h1" | "h2" | "h3");
+        if is_heading && closing {
+            if let Some(text) = heading.take().map(|heading| heading.text) {
+                if first_heading.is_none() && !text.is_empty() {
+                    first_heading = Some(text);
+                }
+            }
+        }
+        if name == "br" {
+            builder.line_break();
+        } else if BLOCKS.contains(&name.as_str()) {
+            builder.paragraph_break();
+        }
+        if is_heading && !closing && !self_closing && first_heading.is_none() {
+            heading = Some(TextBuilder::default());
+        }
+    }
+
+    HtmlText { text: builder.text, first_heading, epub_types }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_become_paragraphs_and_entities_decode() {
+        let html = r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE html>
+<html xmlns:epub="http://www.idpf.org/2007/ops">
+<head><title>Chapter 3</title><style>p { margin: 0 }</style></head>
+<body epub:type="bodymatter">
+  <h2 class="chapter">Chapter&nbsp;3</h2>
+  <p>The General&rsquo;s orchids were   <em>wet</em>.</p><p>Rain on the window&#8212;a gun
+  in the drawer.<br/>The phone rang&#x2026;</p>
+  <!-- <p>hidden</p> -->
+  <script>var x = "<p>";</script>
+  <p>Ben &amp; Jerry &unknown; &amp</p>
+</body>
+</html>"#;
+        let page = html_to_text(html);
+        assert_eq!(
+            page.text,
+            "Chapter 3\n\nThe General’s orchids were wet.\n\nRain on the window—a gun in the drawer.\nThe phone rang…\n\nBen & Jerry &unknown; &amp"
+        );
+        assert_eq!(page.first_heading.as_deref(), Some("Chapter 3"));
+        assert_eq!(page.epub_types, vec!["bodymatter".to_string()]);
+    }
+
+    #[test]
+    fn test_bare_ampersands_stay_as_written() {
+        assert_eq!(decode_entities("AT&T, R&D; &eacute;t&eacute;"), "AT&T, R&D; été");
+        assert_eq!(decode_entities("&notanentityatall; &#x10FFFF;"), "&notanentityatall; \u{10FFFF}");
+    }
+
+    #[test]
+    fn test_nav_is_dropped_and_its_type_reported() {
+        let html = "<body><section epub:type='toc'><nav id=\"toc\"><ol><li><a href=\"c1.xhtml\">One</a></li></ol></nav><p>After</p></section></body>";
+        let page = html_to_text(html);
+        assert_eq!(page.text, "After");
+        assert_eq!(page.epub_types, vec!["toc".to_string()]);
+        assert!(page.first_heading.is_none());
+    }
+
+    #[test]
+    fn test_types_of_nested_elements_are_ignored() {
+        // A chapter with a contents nav, a nested "preface" section and a footnote
+        let html = r#"<body epub:type="bodymatter"><section epub:type="chapter"><nav epub:type="toc"><a href="n1">1</a></nav>
+<section epub:type="preface"><p>Quoted preface.</p></section><aside epub:type="footnote">A note.</aside></section>
+<section epub:type="chapter"><p>Next.</p></section></body>"#;
+        let page = html_to_text(html);
+        assert_eq!(page.epub_types, vec!["bodymatter".to_string(), "chapter".to_string(), "chapter".to_string()]);
+    }
+}
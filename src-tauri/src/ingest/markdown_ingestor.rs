@@ -10,6 +10,8 @@ use std::any::Any;
 use async_trait::async_trait;
 use tokio::fs::metadata;
 
+use super::frontmatter::{split_frontmatter, FrontmatterFields};
+
 #[derive(Debug)]
 pub struct MarkdownIngestor;
 
@@ -26,22 +28,31 @@ impl DocumentIngestor for MarkdownIngestor {
     async fn ingest(&self, resource: &Resource) -> Result<IngestedDocument, IngestError> {
         match resource {
             Resource::FilePath(path) => {
-                let content = fs::read_to_string(path).map_err(IngestError::Io)?;
+                let raw = fs::read_to_string(path).map_err(IngestError::Io)?;
+                // Frontmatter is metadata, not prose to embed
+                let (content, frontmatter) = split_frontmatter(&raw);
+                let fields = FrontmatterFields::from_frontmatter(&frontmatter);
+                let segments = heading_segments(&content);
                 
                 Ok(IngestedDocument {
-                    title: path.file_name()
+                    title: fields.title.clone().unwrap_or_else(|| {
+                        path.file_name()
                         .unwrap_or_default()
                         .to_string_lossy()
-                        .to_string(),
+                        .to_string()
+                    }),
                     content,
                     metadata: DocumentMetadata {
                         source_type: "markdown".to_string(),
                         source_path: path.to_string_lossy().to_string(),
-                        author: None,
-                        created_date: None,
+                        author: fields.author(),
+                        authors: fields.authors,
+                        created_date: fields.created_date,
                         modified_date: None,
-                        frontmatter: HashMap::new(),
-                    }
+                        frontmatter,
+                        tags: fields.tags,
+                    },
+                    segments,
                 })
             },
             Resource::Url(url) => Err(IngestError::UnsupportedFormat(format!(
@@ -55,4 +66,63 @@ impl DocumentIngestor for MarkdownIngestor {
     fn as_any(&self) -> &dyn Any {
         self // This returns a reference to self as a type-erased &dyn Any
     }
-}
\ No newline at end of file
+}
+
+/// One segment per Markdown heading, running to the next heading of any level. Lines
+/// in fenced code blocks aren't headings, and text before the first heading has none.
+pub fn heading_segments(text: &str) -> Vec<DocumentSegment> {
+    let mut segments: Vec<DocumentSegment> = Vec::new();
+    let mut offset = 0;
+    let mut in_fence = false;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        } else if !in_fence && line.starts_with('#') {
+            let level = line.chars().take_while(|c| *c == '#').count();
+            let title = line[level..].trim().trim_end_matches('#').trim();
+            if level <= 6 && line[level..].starts_with(char::is_whitespace) && !title.is_empty() {
+                if let Some(previous) = segments.last_mut() {
+                    previous.end = offset;
+                }
+                segments.push(DocumentSegment::section(offset, offset, title));
+            }
+        }
+        offset += line.chars().count();
+    }
+    if let Some(last) = segments.last_mut() {
+        last.end = offset;
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heading_segments_follow_headings_outside_code() {
+        let text = "Preface\n# The Big Sleep\nRain.\n```\n# not a heading\n```\n## Chapter 3 ##\nOrchids.\n";
+        let segments = heading_segments(text);
+        let titles: Vec<&str> = segments.iter().filter_map(|segment| segment.section.as_deref()).collect();
+        assert_eq!(titles, vec!["The Big Sleep", "Chapter 3"]);
+        assert_eq!(segments[0].start, "Preface\n".len());
+        assert_eq!(segments[0].end, segments[1].start);
+        assert_eq!(segments[1].end, text.chars().count());
+    }
+
+    #[test]
+    fn test_frontmatter_becomes_metadata_not_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sleep.md");
+        fs::write(&path, "---\ntitle: The Big Sleep\nauthors: [Raymond Chandler]\ntags: [noir]\n---\n# Chapter 1\nRain.\n").unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let ingested = runtime.block_on(MarkdownIngestor.ingest(&Resource::FilePath(path))).unwrap();
+        assert_eq!(ingested.title, "The Big Sleep");
+        assert!(!ingested.content.contains("authors:"));
+        assert_eq!(ingested.metadata.authors, vec!["Raymond Chandler".to_string()]);
+        assert_eq!(ingested.metadata.tags, vec!["noir".to_string()]);
+        assert_eq!(ingested.segments[0].section.as_deref(), Some("Chapter 1"));
+    }
+}
@@ -9,6 +9,7 @@ use gray_matter::{Matter, engine::YAML, Pod};
 use std::any::Any;
 
 use super::document_ingestor::{DocumentIngestor, IngestedDocument, DocumentMetadata, IngestError, Resource};
+use super::frontmatter::{split_frontmatter, FrontmatterFields};
 
 #[derive(Debug)]
 pub struct MdxIngestor;
@@ -63,27 +64,21 @@ impl DocumentIngestor for MdxIngestor {
 impl MdxIngestor {
     // Keep the existing ingest_file implementation
     async fn ingest_file(&self, path: &Path) -> Result<IngestedDocument, IngestError> {
-        let content = fs::read_to_string(path)
+        let raw = fs::read_to_string(path)
             .map_err(IngestError::Io)?;
 
-        let matter = Matter::<YAML>::new();
-        let result = matter.parse(&content);
-        
-        let (content, frontmatter) = match result.data {
-            Some(data) => (result.content, data),
-            None => (content, Pod::Hash(HashMap::new())),
-        };
+        let (content, frontmatter) = split_frontmatter(&raw);
+        let fields = FrontmatterFields::from_frontmatter(&frontmatter);
 
         let metadata = DocumentMetadata {
             source_type: "mdx".to_string(),
             source_path: path.to_string_lossy().to_string(),
-            author: None, 
-            created_date: None,
+            author: fields.author(),
+            authors: fields.authors,
+            created_date: fields.created_date,
             modified_date: None,
-            frontmatter: match frontmatter {
-                Pod::Hash(map) => map,
-                _ => HashMap::new(),
-            },
+            frontmatter,
+            tags: fields.tags,
         };
 
         if let Some(Pod::Hash(content_metadata)) = metadata.frontmatter.get("contentMetadata") {
@@ -96,12 +91,15 @@ impl MdxIngestor {
         }
 
         Ok(IngestedDocument {
-            title: path.file_name()
+            title: fields.title.unwrap_or_else(|| {
+                path.file_name()
                 .unwrap_or_default()
                 .to_string_lossy()
-                .to_string(),
+                .to_string()
+            }),
             content,
             metadata,
+            segments: Vec::new(),
         })
     }
 }
\ No newline at end of file
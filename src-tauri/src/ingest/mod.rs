@@ -2,6 +2,7 @@ pub mod document_ingestor;
 pub use document_ingestor::*;
 
 pub mod pdf_ingestor;
+pub mod pdf_cleanup;
 pub mod mdx_ingestor;
 pub mod markdown_ingestor;
 pub mod epub_ingestor;
@@ -9,13 +10,16 @@ pub mod text_ingestor;
 pub mod url_ingestor;
 pub mod mongodb_ingestor;
 pub mod audio_ingestor;
+pub mod frontmatter;
+pub mod html_text;
 
-pub use pdf_ingestor::PdfIngestor;
+pub use pdf_ingestor::{PdfEngine, PdfIngestor};
 pub use mdx_ingestor::MdxIngestor; 
 pub use markdown_ingestor::MarkdownIngestor;
 pub use epub_ingestor::EpubIngestor;
 pub use text_ingestor::TextIngestor;
 pub use audio_ingestor::AudioIngestor;
 pub use url_ingestor::UrlDocumentIngestor;
-pub use document_ingestor::{DocumentIngestor, Resource, IngestedDocument, DocumentMetadata, IngestError};
-pub use mongodb_ingestor::{MongoDocumentIngestor, MongoConfig};
\ No newline at end of file
+pub use document_ingestor::{DocumentIngestor, Resource, IngestedDocument, DocumentMetadata, DocumentSegment, IngestError};
+pub use mongodb_ingestor::{MongoDocumentIngestor, MongoConfig};
+pub use frontmatter::FrontmatterFields;
\ No newline at end of file
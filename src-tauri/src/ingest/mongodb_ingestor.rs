@@ -245,10 +245,13 @@ impl MongoDocumentIngestor {
                 source_type: "MongoDB".to_string(),
                 source_path: format!("{}/{}", self.config.augie_bot_db_name, self.config.edgar_bob_db_name),
                 author: None,
+                authors: Vec::new(),
                 created_date: None,
                 modified_date: None,
                 frontmatter,
+                tags: Vec::new(),
             },
+            segments: Vec::new(),
         })
     }
     
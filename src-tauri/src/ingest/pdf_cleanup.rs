@@ -0,0 +1,472 @@
+// src/ingest/pdf_cleanup.rs
+//
+// Tidies text extracted from PDF pages before it is chunked. Running heads and footers
+// that repeat across most pages are dropped, as is a page number on a page's first or
+// last line. Words hyphenated at line ends are joined, and hard-wrapped lines are
+// reflowed into paragraphs. Headings, list items, verse and other runs of short lines
+// keep their line breaks. Two-column pages are put back into reading order from the
+// positions PDFium reports.
+
+use std::collections::{HashMap, HashSet};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// Lines at the top and bottom of a page that may be running heads or footers
+const EDGE_LINES: usize = 3;
+/// A document needs this many pages before its repeated lines can be told from text
+const MIN_PAGES_FOR_REPEATS: usize = 3;
+/// Each column needs this many runs of text before a page counts as two-column
+const MIN_COLUMN_RUNS: usize = 5;
+/// Consecutive short lines needed before they are kept line for line
+const MIN_SET_LINES: usize = 3;
+/// Longest roman numeral taken for a page number, "xxxviii"
+const MAX_ROMAN_PAGE_NUMBER: usize = 8;
+/// Most words in a line that can be taken for a heading
+const MAX_HEADING_WORDS: usize = 8;
+
+lazy_static! {
+    // "12", "- 12 -", "Page 12", "Page 12 of 300", "xiv". Only well-formed numerals, so
+    // "mid" or "civil" isn't one; the roman part also matches nothing, which is checked
+    // in `is_page_number`.
+    static ref PAGE_NUMBER: Regex = Regex::new(
+        r"^[\s\-–—]*(?:(?i:page)\s+)?(?P<number>\d{1,5}|(?i:m{0,3}(?:cm|cd|d?c{0,3})(?:xc|xl|l?x{0,3})(?:ix|iv|v?i{0,3})))(?:\s+(?i:of)\s+\d+)?[\s\-–—]*$"
+    ).unwrap();
+    // "• ", "- ", "1. ", "12) ", "b. ", "(iv) "
+    static ref LIST_MARKER: Regex = Regex::new(r"^(?:[•◦▪‣⁃∙*\-–]|\(?(?:\d{1,3}|[a-zA-Z]|(?i:[ivxlcdm]{1,5}))[.)])\s").unwrap();
+}
+
+/// A run of text on one line of a page, in PDF points from the bottom left
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRun {
+    pub text: String,
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+/// Cleans every page of a document. Page numbers are kept so segments still cite them.
+pub fn clean_pages(pages: Vec<(u32, String)>) -> Vec<(u32, String)> {
+    let repeated = repeated_edge_lines(pages.iter().map(|(_, text)| text.as_str()));
+    let stripped: Vec<(u32, String)> = pages
+    .into_iter()
+    .map(|(page_number, text)| (page_number, strip_edges(&text, &repeated)))
+    .collect();
+    let context = ReflowContext::from_pages(stripped.iter().map(|(_, text)| text.as_str()));
+    stripped
+    .into_iter()
+    .map(|(page_number, text)| (page_number, reflow(&text, &context)))
+    .collect()
+}
+
+/// What reflowing a page needs to know about the whole document
+#[derive(Debug, Default)]
+pub struct ReflowContext {
+    /// Length of a full line: the upper quartile, so headings, lists and verse don't
+    /// drag it down
+    full_line: usize,
+    /// Hyphenated words the document writes mid-line, lower-cased, such as "well-known"
+    compounds: HashSet<String>,
+}
+
+impl ReflowContext {
+    pub fn from_pages<'a>(pages: impl Iterator<Item = &'a str>) -> Self {
+        let mut lengths = Vec::new();
+        let mut compounds = HashSet::new();
+        for line in pages.flat_map(str::lines).map(str::trim).filter(|line| !line.is_empty()) {
+            lengths.push(line.chars().count());
+            compounds.extend(
+                line.split_whitespace()
+                .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+                .filter(|word| word.contains('-') && word.split('-').all(|part| !part.is_empty() && part.chars().all(char::is_alphabetic)))
+                .map(str::to_lowercase),
+            );
+        }
+        lengths.sort_unstable();
+        let full_line = lengths.get(lengths.len() * 3 / 4).copied().unwrap_or(0);
+        Self { full_line, compounds }
+    }
+
+    fn is_short(&self, line: &str) -> bool {
+        line.chars().count() * 4 < self.full_line * 3
+    }
+
+    /// Which lines are in runs of consistently short lines, such as verse, a list or a
+    /// table, and so are kept line for line. The first short line after an unfinished
+    /// sentence is the end of that paragraph, not part of the run.
+    fn set_lines(&self, lines: &[&str]) -> Vec<bool> {
+        let short: Vec<bool> = lines.iter().map(|line| !line.is_empty() && self.is_short(line)).collect();
+        let mut set = vec![false; lines.len()];
+        let mut start = 0;
+        while start < lines.len() {
+            if !short[start] {
+                start += 1;
+                continue;
+            }
+            let mut end = start;
+            while end < lines.len() && short[end] {
+                end += 1;
+            }
+            let first = if start > 0 && !lines[start - 1].is_empty() && !ends_sentence(lines[start - 1]) { start + 1 } else { start };
+            if end >= first + MIN_SET_LINES {
+                set[first..end].fill(true);
+            }
+            start = end;
+        }
+        set
+    }
+}
+
+/// A line as compared across pages: case and spacing ignored, and numbers treated
+/// alike so "The Big Sleep 41" matches "The Big Sleep 42"
+fn edge_key(line: &str) -> String {
+    let mut key = String::new();
+    let mut in_number = false;
+    for c in line.trim().to_lowercase().chars() {
+        if c.is_ascii_digit() {
+            if !in_number {
+                key.push('#');
+            }
+            in_number = true;
+            continue;
+        }
+        in_number = false;
+        if c.is_whitespace() {
+            if !key.ends_with(' ') {
+                key.push(' ');
+            }
+        } else {
+            key.push(c);
+        }
+    }
+    key
+}
+
+/// The indices of a page's first and last few non-empty lines
+fn edge_line_indices(lines: &[&str]) -> Vec<usize> {
+    let filled: Vec<usize> = (0..lines.len()).filter(|&i| !lines[i].trim().is_empty()).collect();
+    let mut edges: Vec<usize> = filled.iter().take(EDGE_LINES).copied().collect();
+    edges.extend(filled.iter().rev().take(EDGE_LINES).copied());
+    edges.sort_unstable();
+    edges.dedup();
+    edges
+}
+
+fn is_page_number(line: &str) -> bool {
+    PAGE_NUMBER
+    .captures(line)
+    .and_then(|captures| captures.name("number"))
+    .map_or(false, |number| !number.as_str().is_empty() && number.as_str().len() <= MAX_ROMAN_PAGE_NUMBER)
+}
+
+/// Keys of the edge lines found on most pages. Bare page numbers are left out: they all
+/// share one key, which would match any lone number near a page's edge.
+fn repeated_edge_lines<'a>(pages: impl Iterator<Item = &'a str>) -> HashSet<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut page_count = 0;
+    for page in pages {
+        page_count += 1;
+        let lines: Vec<&str> = page.lines().collect();
+        let keys: HashSet<String> = edge_line_indices(&lines)
+        .into_iter()
+        .filter(|&i| !is_page_number(lines[i]))
+        .map(|i| edge_key(lines[i]))
+        .collect();
+        for key in keys {
+            *counts.entry(key).or_default() += 1;
+        }
+    }
+    if page_count < MIN_PAGES_FOR_REPEATS {
+        return HashSet::new();
+    }
+    counts.into_iter().filter(|(_, count)| count * 2 > page_count).map(|(key, _)| key).collect()
+}
+
+/// The page without its repeated heads and footers or a bare page number
+fn strip_edges(page: &str, repeated: &HashSet<String>) -> String {
+    let lines: Vec<&str> = page.lines().collect();
+    let mut dropped: HashSet<usize> = edge_line_indices(&lines)
+    .into_iter()
+    .filter(|&i| repeated.contains(&edge_key(lines[i])))
+    .collect();
+    // A page number is only ever the outermost line left at the top or bottom
+    let remaining: Vec<usize> = (0..lines.len()).filter(|i| !dropped.contains(i) && !lines[*i].trim().is_empty()).collect();
+    for i in [remaining.first(), remaining.last()].into_iter().flatten() {
+        if is_page_number(lines[*i]) {
+            dropped.insert(*i);
+        }
+    }
+    lines
+    .iter()
+    .enumerate()
+    .filter(|(i, _)| !dropped.contains(i))
+    .map(|(_, line)| *line)
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+fn ends_sentence(line: &str) -> bool {
+    line.trim_end().ends_with(['.', '!', '?', ':', '"', '”', '’'])
+}
+
+fn is_list_item(line: &str) -> bool {
+    LIST_MARKER.is_match(line)
+}
+
+/// A short title-case line with no punctuation after its last word, such as
+/// "Chapter 2" or "The Sternwood Place"
+fn looks_like_heading(line: &str, context: &ReflowContext) -> bool {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    context.is_short(line)
+        && words.len() <= MAX_HEADING_WORDS
+        && line.chars().last().map_or(false, char::is_alphanumeric)
+        && words.iter().enumerate().all(|(i, word)| {
+            // Short words such as "of" and "the" may stay lower case after the first
+            word.chars().next().map_or(false, |c| c.is_uppercase() || c.is_ascii_digit()) || (i > 0 && word.chars().count() <= 3)
+        })
+}
+
+/// Joins hard-wrapped lines into paragraphs. A blank line always ends a paragraph;
+/// so does a sentence ending on a short line. A list item starts a new one, a heading
+/// starting a page or paragraph stands alone, and runs of short lines are kept as they
+/// are set. A word split by a hyphen at the end of a line,
+/// with the next line starting in lower case, is joined without the hyphen unless the
+/// document writes it hyphenated elsewhere.
+pub fn reflow(page: &str, context: &ReflowContext) -> String {
+    let lines: Vec<&str> = page.lines().map(str::trim).collect();
+    let set = context.set_lines(&lines);
+
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for (i, &line) in lines.iter().enumerate() {
+        let in_set = set[i];
+        let after_set = i > 0 && set[i - 1];
+        if line.is_empty() || in_set != after_set || (!in_set && is_list_item(line)) {
+            if !current.is_empty() {
+                paragraphs.push(std::mem::take(&mut current));
+            }
+            if line.is_empty() {
+                continue;
+            }
+        }
+        let next_is_capitalised = lines.get(i + 1).and_then(|next| next.chars().next()).map_or(false, char::is_uppercase);
+        if current.is_empty() && !in_set && next_is_capitalised && looks_like_heading(line, context) {
+            paragraphs.push(line.to_string());
+            continue;
+        }
+        if current.is_empty() {
+            current.push_str(line);
+        } else if in_set {
+            current.push('\n');
+            current.push_str(line);
+        } else if current.ends_with('\u{ad}') {
+            current.pop();
+            current.push_str(line);
+        } else if current.ends_with('-')
+            && current.chars().rev().nth(1).map_or(false, char::is_alphabetic)
+            && line.chars().next().map_or(false, char::is_lowercase)
+        {
+            let head = current.rsplit(char::is_whitespace).next().unwrap_or_default().trim_start_matches(|c: char| !c.is_alphanumeric());
+            let tail = line.split(char::is_whitespace).next().unwrap_or_default().trim_end_matches(|c: char| !c.is_alphanumeric());
+            if !context.compounds.contains(&format!("{}{}", head, tail).to_lowercase()) {
+                current.pop();
+            }
+            current.push_str(line);
+        } else {
+            current.push(' ');
+            current.push_str(line);
+        }
+        if !in_set && ends_sentence(line) && context.is_short(line) {
+            paragraphs.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(current);
+    }
+    paragraphs.join("\n\n")
+}
+
+fn push_line(line: &mut Vec<&TextRun>, lines: &mut Vec<String>) {
+    line.sort_by(|a, b| a.left.total_cmp(&b.left));
+    let text = line.iter().map(|run| run.text.trim()).filter(|text| !text.is_empty()).collect::<Vec<_>>().join(" ");
+    if !text.is_empty() {
+        lines.push(text);
+    }
+    line.clear();
+}
+
+/// Lines of text from runs, top to bottom, with a blank line where the gap between
+/// lines is wide enough to be a paragraph break
+fn runs_to_lines(runs: &mut [&TextRun], lines: &mut Vec<String>) {
+    runs.sort_by(|a, b| b.top.total_cmp(&a.top).then(a.left.total_cmp(&b.left)));
+    let mut line: Vec<&TextRun> = Vec::new();
+    let mut line_top: Option<f32> = None;
+    for &run in runs.iter() {
+        let height = (run.top - run.bottom).abs().max(1.0);
+        if let Some(top) = line_top {
+            if (top - run.top).abs() > height / 2.0 {
+                push_line(&mut line, lines);
+                if top - run.top > height * 1.8 {
+                    lines.push(String::new());
+                }
+                line_top = None;
+            }
+        }
+        line_top.get_or_insert(run.top);
+        line.push(run);
+    }
+    push_line(&mut line, lines);
+}
+
+/// The page's text in reading order if it is laid out in two columns: each column
+/// read top to bottom, left before right, with anything spanning both (a title, a
+/// wide figure caption) read where it falls. `None` for single-column pages, whose
+/// text PDFium already orders.
+pub fn two_column_text(runs: &[TextRun], page_width: f32) -> Option<String> {
+    let gutter = page_width / 2.0;
+    let tolerance = page_width * 0.02;
+    let is_left = |run: &TextRun| run.right <= gutter + tolerance;
+    let is_right = |run: &TextRun| run.left >= gutter - tolerance;
+
+    let left_count = runs.iter().filter(|run| is_left(run)).count();
+    let right_count = runs.iter().filter(|run| is_right(run)).count();
+    if left_count < MIN_COLUMN_RUNS || right_count < MIN_COLUMN_RUNS || (left_count + right_count) * 4 < runs.len() * 3 {
+        return None;
+    }
+
+    let mut sorted: Vec<&TextRun> = runs.iter().collect();
+    sorted.sort_by(|a, b| b.top.total_cmp(&a.top));
+    let mut lines = Vec::new();
+    let mut left: Vec<&TextRun> = Vec::new();
+    let mut right: Vec<&TextRun> = Vec::new();
+    for run in sorted {
+        if is_left(run) {
+            left.push(run);
+        } else if is_right(run) {
+            right.push(run);
+        } else {
+            runs_to_lines(&mut left, &mut lines);
+            runs_to_lines(&mut right, &mut lines);
+            left.clear();
+            right.clear();
+            lines.push(String::new());
+            runs_to_lines(&mut [run], &mut lines);
+            lines.push(String::new());
+        }
+    }
+    runs_to_lines(&mut left, &mut lines);
+    runs_to_lines(&mut right, &mut lines);
+    Some(lines.join("\n").trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reflow_alone(page: &str) -> String {
+        reflow(page, &ReflowContext::from_pages(std::iter::once(page)))
+    }
+
+    #[test]
+    fn test_hyphens_joined_and_lines_reflowed() {
+        let page = "The General sat in the hot-\nhouse among the orchids, and the\nair was thick with the smell of\nthem.\n\nHe did not get up. It was a well-\nknown fact.";
+        // Another page writes "well-known" with its hyphen, so that one stays
+        let context = ReflowContext::from_pages([page, "Everyone in Los Angeles knew the well-known story."].into_iter());
+        assert_eq!(
+            reflow(page, &context),
+            "The General sat in the hothouse among the orchids, and the air was thick with the smell of them.\n\nHe did not get up. It was a well-known fact."
+        );
+    }
+
+    #[test]
+    fn test_list_items_and_verse_keep_their_lines() {
+        let page = "The orchids needed three things to live, and the General
+gave them every one of them without fail, day after day:
+• heat
+• water
+• patience beyond what any man could give them in a
+single lifetime.
+Then he would read aloud from the book of verse he kept:
+Roses are red,
+Violets are blue,
+Sugar is sweet.
+And that was all he ever read from it, night after night.";
+        assert_eq!(
+            reflow_alone(page),
+            "The orchids needed three things to live, and the General gave them every one of them without fail, day after day:\n\n\
+• heat\n\n• water\n\n• patience beyond what any man could give them in a single lifetime.\n\n\
+Then he would read aloud from the book of verse he kept:\n\n\
+Roses are red,\nViolets are blue,\nSugar is sweet.\n\n\
+And that was all he ever read from it, night after night."
+        );
+    }
+
+    #[test]
+    fn test_short_sentence_line_ends_paragraph() {
+        let page = "Rain fell on the canyon all night long and into the\nmorning.\nThe phone rang twice before anyone answered it at all.";
+        assert_eq!(
+            reflow_alone(page),
+            "Rain fell on the canyon all night long and into the morning.\n\nThe phone rang twice before anyone answered it at all."
+        );
+    }
+
+    #[test]
+    fn test_running_heads_and_page_numbers_removed() {
+        let pages = vec![
+            (1, "THE BIG SLEEP\nIt was about eleven o'clock.\n1".to_string()),
+            (2, "THE BIG SLEEP\nThe main hallway was two stories high.\n2".to_string()),
+            (3, "THE BIG SLEEP\nA girl stood in the doorway.\nPage 3 of 3".to_string()),
+            (4, "Chapter 2\nThe General liked orchids.\n- 4 -".to_string()),
+        ];
+        let cleaned = clean_pages(pages);
+        assert_eq!(cleaned[0], (1, "It was about eleven o'clock.".to_string()));
+        assert_eq!(cleaned[2], (3, "A girl stood in the doorway.".to_string()));
+        assert_eq!(cleaned[3], (4, "Chapter 2\n\nThe General liked orchids.".to_string()));
+    }
+
+    #[test]
+    fn test_lone_numbers_and_numeral_like_words_near_edges_survive() {
+        let pages = vec![
+            (1, "The ledger for that year was short.\n1".to_string()),
+            (2, "He wrote down the year the house was built:\n1949\nand closed the book.\n2".to_string()),
+            (3, "mid\nwinter, the orchids died.\niii".to_string()),
+        ];
+        let cleaned = clean_pages(pages);
+        assert_eq!(cleaned[0], (1, "The ledger for that year was short.".to_string()));
+        assert_eq!(cleaned[1], (2, "He wrote down the year the house was built: 1949 and closed the book.".to_string()));
+        assert_eq!(cleaned[2], (3, "mid winter, the orchids died.".to_string()));
+
+        for word in ["mid", "did", "civil", "mild", "vivid"] {
+            assert!(!is_page_number(word), "{}", word);
+        }
+        for number in ["12", "- 12 -", "Page 12 of 300", "xiv", "XXXVIII"] {
+            assert!(is_page_number(number), "{}", number);
+        }
+    }
+
+    #[test]
+    fn test_two_columns_read_left_then_right() {
+        let run = |text: &str, left: f32, top: f32| TextRun {
+            text: text.to_string(),
+            left,
+            right: left + 200.0,
+            top,
+            bottom: top - 10.0,
+        };
+        let mut runs = vec![TextRun { text: "A Title Across The Page".to_string(), left: 100.0, right: 500.0, top: 760.0, bottom: 740.0 }];
+        for (i, top) in [700.0, 688.0, 676.0, 664.0, 652.0].iter().enumerate() {
+            // PDFium may hand runs over row by row, across both columns
+            runs.push(run(&format!("left{}", i), 50.0, *top));
+            runs.push(run(&format!("right{}", i), 320.0, *top));
+        }
+        let text = two_column_text(&runs, 612.0).unwrap();
+        assert_eq!(
+            text,
+            "A Title Across The Page\n\nleft0\nleft1\nleft2\nleft3\nleft4\nright0\nright1\nright2\nright3\nright4"
+        );
+
+        let single: Vec<TextRun> = (0..8).map(|i| TextRun { right: 560.0, ..run("line", 50.0, 700.0 - i as f32 * 12.0) }).collect();
+        assert!(two_column_text(&single, 612.0).is_none());
+    }
+}
@@ -7,15 +7,50 @@ use super::document_ingestor::{
     DocumentIngestor,
     IngestedDocument,
     DocumentMetadata,
+    DocumentSegment,
     IngestError,
     Resource  // Add this import
 };
-use crate::get_resource_dir_path;
+use super::pdf_cleanup::{clean_pages, two_column_text, TextRun};
+use gray_matter::Pod;
 
-#[derive(Debug)]
-pub struct PdfIngestor;
+/// 2: pages separated by a line break, running heads dropped and lines reflowed
+const EXTRACTION_VERSION: u32 = 2;
 
+/// How text is pulled out of a PDF
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PdfEngine {
+    /// PDFium when the library can be loaded, pure Rust otherwise
+    #[default]
+    Auto,
+    Pdfium,
+    /// pdf-extract, falling back to lopdf page by page; needs no native library
+    PureRust,
+}
+
+impl PdfEngine {
+    /// Parses the `pdf_engine` preference; anything unrecognised is `Auto`
+    pub fn from_name(name: &str) -> Self {
+        match name.trim().to_lowercase().as_str() {
+            "pdfium" => PdfEngine::Pdfium,
+            "pure_rust" | "rust" | "pdf-extract" | "pdf_extract" => PdfEngine::PureRust,
+            _ => PdfEngine::Auto,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PdfEngine::Auto => "auto",
+            PdfEngine::Pdfium => "pdfium",
+            PdfEngine::PureRust => "pure_rust",
+        }
+    }
+}
 
+#[derive(Debug, Default)]
+pub struct PdfIngestor {
+    engine: PdfEngine,
+}
 
 #[async_trait]
 impl DocumentIngestor for PdfIngestor {
@@ -42,6 +77,9 @@ impl DocumentIngestor for PdfIngestor {
             )),
         }
     }
+    fn extraction_version(&self) -> u32 {
+        EXTRACTION_VERSION
+    }
     fn as_any(&self) -> &dyn Any {
         self // This returns a reference to self as a type-erased &dyn Any
     }
@@ -49,106 +87,245 @@ impl DocumentIngestor for PdfIngestor {
 
 // Move existing implementation to a helper method in a separate impl block
 impl PdfIngestor {
-    // Keep the existing ingest_file implementation
-    async fn ingest_file(&self, path: &Path) -> Result<IngestedDocument, IngestError> {
-        // Try loading the PDF library, with proper error handling
-        crate::get_resource_dir_path();
-
-        let pdfium_dir = if let Some(resource_dir_path) = crate::get_resource_dir_path() {
-            log::debug!("Using globally stored resource directory for libpdfium: {:?}", resource_dir_path);
-            resource_dir_path.join("resources")
-        } else {
-            log::warn!("Resource directory not found, attempting to load PDFium from system library");
-            PathBuf::from("./Resources/resources")
-        };
+    pub fn new(engine: PdfEngine) -> Self {
+        Self { engine }
+    }
 
-        let pdfium = Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path(&pdfium_dir))
-        .or_else(|err| {
-            // Log the first failure
-            log::warn!("Failed to load PDFium from resources/: {}", err);
-            log::info!("Attempting to load PDFium from system library...");
-            
-            // Try the system library
-            Pdfium::bind_to_system_library()
-            .map_err(|sys_err| {
-                // Log the second failure too
-                log::error!("Failed to load PDFium from system library: {}", sys_err);
-                log::error!("Could not initialize PDF processing engine");
-                
-                // Return the second error
-                sys_err
-            })
-        })
-        .map(Pdfium::new)
-        .map_err(|e| log::error!("Failed to initialize PDF library: {}", e));
-        
-        // log::debug!("PDFium library successfully loaded");
-        
-        // Now try to load the PDF file
-        let pdfium = match pdfium {
-            Ok(pdfium) => pdfium,
-            Err(_) => return Err(IngestError::Parse("Failed to initialize PDFium".to_string())),
-        };
+    pub fn engine(&self) -> PdfEngine {
+        self.engine
+    }
 
-        let document = match pdfium.load_pdf_from_file(path, None) {
-            Ok(doc) => doc,
-            Err(e) => {
-                log::error!("Failed to load PDF file {}: {}", path.display(), e);
-                return Err(IngestError::Parse(format!("Failed to load PDF file: {}", e)));
-            }
+    async fn ingest_file(&self, path: &Path) -> Result<IngestedDocument, IngestError> {
+        let pages = match self.engine {
+            PdfEngine::PureRust => extract_pages_pure_rust(path)?,
+            PdfEngine::Pdfium => match bind_pdfium() {
+                Some(pdfium) => extract_pages_pdfium(&pdfium, path)?,
+                None => {
+                    return Err(IngestError::Parse(
+                        "the PDF engine is set to PDFium, but its library couldn't be loaded from the app resources or the system".to_string(),
+                    ))
+                }
+            },
+            PdfEngine::Auto => match bind_pdfium() {
+                Some(pdfium) => extract_pages_pdfium(&pdfium, path)?,
+                None => {
+                    log::warn!("PDFium is unavailable, extracting {} with the pure Rust reader", path.display());
+                    extract_pages_pure_rust(path).map_err(|e| match e {
+                        IngestError::Parse(reason) => IngestError::Parse(format!("{} (PDFium unavailable)", reason)),
+                        other => other,
+                    })?
+                }
+            },
         };
-        
         log::info!("Successfully loaded PDF file: {}", path.display());
-        
+        let pages = clean_pages(pages);
+
         let mut extracted_text = String::new();
-        
-        for (index, page) in document.pages().iter().enumerate() {
-            if let Ok(text) = page.text() {
-                //extracted_text.push_str(&format!("\n=============== Page {} ===============\n", index + 1));
-                extracted_text.push_str(&text.all());
+        let mut segments = Vec::new();
+        let mut offset = 0;
+        for (page_number, page_text) in pages {
+            // Keep the last word of one page from running into the first of the next
+            if !extracted_text.is_empty() && !extracted_text.ends_with(char::is_whitespace) {
+                extracted_text.push('\n');
+                offset += 1;
             }
+            let length = page_text.chars().count();
+            segments.push(DocumentSegment::page(offset, offset + length, page_number));
+            extracted_text.push_str(&page_text);
+            offset += length;
         }
-        
+
+        let info = read_info(path);
+        let field = |key: &str| info.get(key).cloned().filter(|value| !value.is_empty());
+        let authors: Vec<String> = field("Author")
+        .map(|author| author.split(';').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect())
+        .unwrap_or_default();
+        let tags: Vec<String> = field("Keywords")
+        .map(|keywords| keywords.split([',', ';']).map(|tag| tag.trim().to_string()).filter(|tag| !tag.is_empty()).collect())
+        .unwrap_or_default();
+        let frontmatter: HashMap<String, Pod> = info.iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| (key.clone(), Pod::String(value.clone())))
+        .collect();
+
         Ok(IngestedDocument {
-            title: path.file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string(),
+            title: field("Title").unwrap_or_else(|| {
+                path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+            }),
             content: extracted_text,
             metadata: DocumentMetadata {
                 source_type: "pdf".to_string(),
                 source_path: path.to_string_lossy().to_string(),
-                author: None,
-                created_date: None,
-                modified_date: None,
-                frontmatter: HashMap::new(),
-            }
+                author: Some(authors.join(", ")).filter(|author| !author.is_empty()),
+                authors,
+                created_date: field("CreationDate").map(|date| pdf_date(&date)),
+                modified_date: field("ModDate").map(|date| pdf_date(&date)),
+                frontmatter,
+                tags,
+            },
+            segments,
         })
     }
 }
 
+/// Loads PDFium from the app's resources, then from the system; `None` when neither has it
+fn bind_pdfium() -> Option<Pdfium> {
+    let pdfium_dir = if let Some(resource_dir_path) = crate::get_resource_dir_path() {
+        log::debug!("Using globally stored resource directory for libpdfium: {:?}", resource_dir_path);
+        resource_dir_path.join("resources")
+    } else {
+        log::warn!("Resource directory not found, attempting to load PDFium from system library");
+        PathBuf::from("./Resources/resources")
+    };
+
+    Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path(&pdfium_dir))
+    .or_else(|err| {
+        // Log the first failure
+        log::warn!("Failed to load PDFium from resources/: {}", err);
+        log::info!("Attempting to load PDFium from system library...");
+
+        // Try the system library
+        Pdfium::bind_to_system_library()
+        .map_err(|sys_err| {
+            log::warn!("Failed to load PDFium from system library: {}", sys_err);
+            sys_err
+        })
+    })
+    .map(Pdfium::new)
+    .ok()
+}
+
+/// Each page's text with its 1-based number, as PDFium reads it. Two-column pages are
+/// put in reading order from the positions of their runs of text.
+fn extract_pages_pdfium(pdfium: &Pdfium, path: &Path) -> Result<Vec<(u32, String)>, IngestError> {
+    let document = match pdfium.load_pdf_from_file(path, None) {
+        Ok(doc) => doc,
+        Err(e) => {
+            log::error!("PDFium failed to load PDF file {}: {}", path.display(), e);
+            return Err(IngestError::Parse(format!("PDFium couldn't read the file: {}", e)));
+        }
+    };
+    let mut pages = Vec::new();
+    for (index, page) in document.pages().iter().enumerate() {
+        if let Ok(text) = page.text() {
+            let runs: Vec<TextRun> = text
+            .segments()
+            .iter()
+            .map(|segment| {
+                let bounds = segment.bounds();
+                TextRun {
+                    text: segment.text(),
+                    left: bounds.left().value,
+                    right: bounds.right().value,
+                    top: bounds.top().value,
+                    bottom: bounds.bottom().value,
+                }
+            })
+            .collect();
+            let page_text = two_column_text(&runs, page.width().value).unwrap_or_else(|| text.all());
+            pages.push((index as u32 + 1, page_text));
+        }
+    }
+    Ok(pages)
+}
+
+/// Each page's text with its 1-based number, read without PDFium: pdf-extract lays the
+/// text out better, and lopdf reads some files it rejects
+fn extract_pages_pure_rust(path: &Path) -> Result<Vec<(u32, String)>, IngestError> {
+    // pdf-extract panics on some malformed files rather than returning an error
+    let pdf_extract_error = match std::panic::catch_unwind(|| pdf_extract::extract_text_by_pages(path)) {
+        Ok(Ok(pages)) => return Ok(pages.into_iter().enumerate().map(|(index, text)| (index as u32 + 1, text)).collect()),
+        Ok(Err(e)) => e.to_string(),
+        Err(_) => "it panicked".to_string(),
+    };
+    log::warn!("pdf-extract couldn't read {}, trying lopdf: {}", path.display(), pdf_extract_error);
+    let document = lopdf::Document::load(path).map_err(|e| {
+        log::error!("lopdf failed to load PDF file {}: {}", path.display(), e);
+        IngestError::Parse(format!(
+            "the pure Rust reader couldn't read the file (pdf-extract: {}; lopdf: {})",
+            pdf_extract_error, e
+        ))
+    })?;
+    Ok(document
+    .get_pages()
+    .keys()
+    .map(|&page_number| (page_number, document.extract_text(&[page_number]).unwrap_or_default()))
+    .collect())
+}
+
+/// The document information dictionary (Title, Author, CreationDate and the like) as
+/// text. Unreadable or encrypted files just have none.
+fn read_info(path: &Path) -> HashMap<String, String> {
+    let document = match lopdf::Document::load(path) {
+        Ok(document) => document,
+        Err(e) => {
+            log::debug!("Couldn't read the info dictionary of {}: {}", path.display(), e);
+            return HashMap::new();
+        }
+    };
+    let info = match document.trailer.get(b"Info") {
+        Ok(lopdf::Object::Reference(id)) => document.get_dictionary(*id).ok(),
+        Ok(lopdf::Object::Dictionary(info)) => Some(info),
+        _ => None,
+    };
+    info.map(|info| {
+        info.iter()
+        .filter_map(|(key, value)| match value {
+            lopdf::Object::String(bytes, _) => Some((String::from_utf8_lossy(key).to_string(), pdf_text_string(bytes))),
+            _ => None,
+        })
+        .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// A PDF text string: UTF-16BE behind a byte order mark, otherwise PDFDocEncoding,
+/// which is close enough to Latin-1 for names and titles
+fn pdf_text_string(bytes: &[u8]) -> String {
+    let text = match bytes {
+        [0xFE, 0xFF, rest @ ..] => {
+            let units: Vec<u16> = rest.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).to_string(),
+        _ => bytes.iter().map(|&byte| byte as char).collect(),
+    };
+    text.trim_matches(|c: char| c.is_whitespace() || c == '\0').to_string()
+}
+
+/// "D:19390206120000+01'00'" as "1939-02-06"; dates not in PDF form are kept as written
+fn pdf_date(date: &str) -> String {
+    let digits: String = date.trim().trim_start_matches("D:").chars().take_while(|c| c.is_ascii_digit()).collect();
+    match digits.len() {
+        n if n >= 8 => format!("{}-{}-{}", &digits[..4], &digits[4..6], &digits[6..8]),
+        6 | 7 => format!("{}-{}", &digits[..4], &digits[4..6]),
+        4 | 5 => digits[..4].to_string(),
+        _ => date.trim().to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
-    
-    fn get_test_pdf_path() -> PathBuf {
-        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("tests")
-        .join("fixtures")
-        .join("test.pdf")
+
+    #[test]
+    fn test_info_strings_and_dates() {
+        assert_eq!(pdf_text_string(b"Raymond Chandler"), "Raymond Chandler");
+        assert_eq!(pdf_text_string(&[0xFE, 0xFF, 0x00, 0x4C, 0x00, 0xE9, 0x00, 0x6F]), "Léo");
+        assert_eq!(pdf_text_string(&[0x43, 0x61, 0x66, 0xE9]), "Café");
+        assert_eq!(pdf_date("D:19390206120000+01'00'"), "1939-02-06");
+        assert_eq!(pdf_date("D:1939"), "1939");
+        assert_eq!(pdf_date("Feb 1939"), "Feb 1939");
+    }
+
+    #[test]
+    fn test_engine_names() {
+        assert_eq!(PdfEngine::from_name("PDFium"), PdfEngine::Pdfium);
+        assert_eq!(PdfEngine::from_name("pure_rust"), PdfEngine::PureRust);
+        assert_eq!(PdfEngine::from_name(""), PdfEngine::Auto);
+        assert_eq!(PdfEngine::from_name(PdfEngine::PureRust.as_str()), PdfEngine::PureRust);
     }
-    
-    // #[tokio::test]
-    // async fn test_pdf_ingestion() {
-    //     let ingestor = PdfIngestor;
-    //     let pdf_path = get_test_pdf_path();
-    
-    //     let result = ingestor.ingest_file(&pdf_path).await;
-    //     assert!(result.is_ok(), "PDF ingestion failed: {:?}", result.err());
-    
-    //     let doc = result.unwrap();
-    //     println!("Content length: {}", doc.content.len());
-    //     println!("First 100 chars: {}", &doc.content[..100.min(doc.content.len())]);
-    // }
 }
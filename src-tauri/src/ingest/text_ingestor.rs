@@ -62,10 +62,13 @@ impl TextIngestor {
                 source_type: "text".to_string(),
                 source_path: path.to_string_lossy().to_string(),
                 author: None,
+                authors: Vec::new(),
                 created_date: None,
                 modified_date: None,
                 frontmatter: HashMap::new(),
-            }
+                tags: Vec::new(),
+            },
+            segments: Vec::new(),
         })
     }
 }
@@ -93,10 +93,13 @@ impl UrlDocumentIngestor {
                 source_type: "URL".to_string(),
                 source_path: url_root.to_string(),
                 author: None,
+                authors: Vec::new(),
                 created_date: Some(current_time.clone()),  // Add current time as creation date
                 modified_date: Some(current_time),         // Same for modification date    
                 frontmatter,
+                tags: Vec::new(),
             },
+            segments: Vec::new(),
         })
     }
     
@@ -0,0 +1,573 @@
+#![allow(unused_imports)]
+#![allow(dead_code)]
+// src/ingestion_queue.rs
+//
+// Ingestion runs as jobs persisted in the canon's `jobs` table rather than inline
+// inside a Tauri command. A single background worker takes queued jobs in order,
+// checkpoints embedding progress after every committed batch, and resumes from that
+// checkpoint when a job is retried or the app restarts mid-ingestion.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chrono::Local;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Notify;
+
+use crate::ai::providers::Provider;
+use crate::ai::traits::PreferredEmbeddingModel;
+use crate::app_state::AppState;
+use crate::document_store::{content_hash, Document, DocumentStore};
+use crate::ingest::Resource;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Failed,
+    Cancelled,
+    Done,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::Done => "done",
+        }
+    }
+}
+
+/// What a job ingests. URL jobs carry the fetched text so a resume doesn't refetch a page that may have changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobSourceKind {
+    File,
+    Url,
+}
+
+impl JobSourceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobSourceKind::File => "file",
+            JobSourceKind::Url => "url",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IngestionJob {
+    pub id: i64,
+    pub source: String,
+    pub source_kind: String,
+    pub title: Option<String>,
+    #[serde(skip)]
+    pub content: Option<String>,
+    pub doc_id: Option<i64>,
+    pub status: String,
+    pub total_chunks: usize,
+    pub completed_chunks: usize,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl IngestionJob {
+    pub fn resource(&self) -> Resource {
+        match self.source_kind.as_str() {
+            "url" => Resource::Url(self.source.clone()),
+            _ => Resource::FilePath(PathBuf::from(&self.source)),
+        }
+    }
+
+    /// When a file source was last modified, for spotting files that haven't changed
+    /// since they were ingested. URLs have no such time.
+    pub fn source_modified_at(&self) -> Option<String> {
+        match self.source_kind.as_str() {
+            "url" => None,
+            _ => std::fs::metadata(&self.source)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .map(|modified| chrono::DateTime::<Local>::from(modified).to_rfc3339()),
+        }
+    }
+
+    /// Short name used in progress events and log messages
+    pub fn display_name(&self) -> String {
+        match self.source_kind.as_str() {
+            "url" => self.source.clone(),
+            _ => Path::new(&self.source)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.source.clone()),
+        }
+    }
+}
+
+/// Passed to `process_embeddings` so a job can skip already-embedded chunks,
+/// record progress as batches commit, and stop when cancelled.
+#[derive(Debug, Clone)]
+pub struct JobCheckpoint {
+    pub job_id: i64,
+    pub completed_chunks: usize,
+    pub cancel_flag: Arc<AtomicBool>,
+}
+
+impl JobCheckpoint {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_flag.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IngestionJobError {
+    #[error("Ingestion job {0} was cancelled")]
+    Cancelled(i64),
+    #[error("Ingestion job {0} not found")]
+    NotFound(i64),
+}
+
+const JOB_COLUMNS: &str = "id, source, source_kind, title, content, doc_id, status, total_chunks, completed_chunks, error, created_at, updated_at";
+
+fn job_from_row(row: &rusqlite::Row) -> rusqlite::Result<IngestionJob> {
+    Ok(IngestionJob {
+        id: row.get(0)?,
+        source: row.get(1)?,
+        source_kind: row.get(2)?,
+        title: row.get(3)?,
+        content: row.get(4)?,
+        doc_id: row.get(5)?,
+        status: row.get(6)?,
+        total_chunks: row.get::<_, i64>(7)? as usize,
+        completed_chunks: row.get::<_, i64>(8)? as usize,
+        error: row.get(9)?,
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+    })
+}
+
+/// Creates the jobs table
+pub(crate) fn initialize_jobs_table(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS jobs
+        (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        source TEXT NOT NULL,
+        source_kind TEXT NOT NULL,
+        title TEXT,
+        content TEXT,
+        doc_id INTEGER,
+        status TEXT NOT NULL DEFAULT 'queued',
+        total_chunks INTEGER NOT NULL DEFAULT 0,
+        completed_chunks INTEGER NOT NULL DEFAULT 0,
+        error TEXT,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Puts jobs interrupted by a quit or crash back in the queue
+pub(crate) fn requeue_interrupted_jobs(conn: &Connection) -> Result<(), rusqlite::Error> {
+    let requeued = conn.execute(
+        "UPDATE jobs SET status = 'queued', updated_at = ?1 WHERE status = 'running'",
+        params![Local::now().to_rfc3339()],
+    )?;
+    if requeued > 0 {
+        log::info!("Re-queued {} interrupted ingestion jobs", requeued);
+    }
+    Ok(())
+}
+
+impl DocumentStore {
+    pub async fn enqueue_ingestion_job(
+        &self,
+        source: &str,
+        source_kind: JobSourceKind,
+        title: Option<String>,
+        content: Option<String>,
+    ) -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        let now = Local::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO jobs (source, source_kind, title, content, status, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, 'queued', ?5, ?5)",
+            params![source, source_kind.as_str(), title, content, now],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub async fn list_ingestion_jobs(&self) -> Result<Vec<IngestionJob>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM jobs ORDER BY id DESC", JOB_COLUMNS))?;
+        let jobs = stmt.query_map([], job_from_row)?.collect::<Result<Vec<_>, _>>()?;
+        Ok(jobs)
+    }
+
+    pub async fn get_ingestion_job(&self, job_id: i64) -> Result<IngestionJob, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        conn.query_row(
+            &format!("SELECT {} FROM jobs WHERE id = ?1", JOB_COLUMNS),
+            params![job_id],
+            job_from_row,
+        )
+        .optional()?
+        .ok_or_else(|| IngestionJobError::NotFound(job_id).into())
+    }
+
+    pub async fn next_queued_job(&self) -> Result<Option<IngestionJob>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        let job = conn.query_row(
+            &format!("SELECT {} FROM jobs WHERE status = 'queued' ORDER BY id LIMIT 1", JOB_COLUMNS),
+            [],
+            job_from_row,
+        )
+        .optional()?;
+        Ok(job)
+    }
+
+    /// Moves a job from queued to running. Returns false if someone else got there first
+    /// (for example the job was cancelled while it waited).
+    pub async fn claim_ingestion_job(&self, job_id: i64) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        let updated = conn.execute(
+            "UPDATE jobs SET status = 'running', error = NULL, updated_at = ?1 WHERE id = ?2 AND status = 'queued'",
+            params![Local::now().to_rfc3339(), job_id],
+        )?;
+        Ok(updated == 1)
+    }
+
+    pub async fn set_ingestion_job_status(
+        &self,
+        job_id: i64,
+        status: JobStatus,
+        error: Option<String>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "UPDATE jobs SET status = ?1, error = ?2, updated_at = ?3 WHERE id = ?4",
+            params![status.as_str(), error, Local::now().to_rfc3339(), job_id],
+        )?;
+        Ok(())
+    }
+
+    /// Cancels a job that hasn't started yet. Running jobs are stopped through their cancel flag.
+    pub async fn cancel_queued_ingestion_job(&self, job_id: i64) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        let updated = conn.execute(
+            "UPDATE jobs SET status = 'cancelled', updated_at = ?1 WHERE id = ?2 AND status = 'queued'",
+            params![Local::now().to_rfc3339(), job_id],
+        )?;
+        Ok(updated == 1)
+    }
+
+    /// Puts a failed or cancelled job back in the queue. It resumes from its last checkpoint.
+    pub async fn retry_ingestion_job(&self, job_id: i64) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        let updated = conn.execute(
+            "UPDATE jobs SET status = 'queued', error = NULL, updated_at = ?1 WHERE id = ?2 AND status IN ('failed', 'cancelled')",
+            params![Local::now().to_rfc3339(), job_id],
+        )?;
+        Ok(updated == 1)
+    }
+
+    async fn link_job_to_document(&self, job_id: i64, doc_id: i64) -> Result<(), rusqlite::Error> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "UPDATE jobs SET doc_id = ?1, updated_at = ?2 WHERE id = ?3",
+            params![doc_id, Local::now().to_rfc3339(), job_id],
+        )?;
+        Ok(())
+    }
+
+    /// Ingests the job's source (unless the job already carries its text), creates the
+    /// document row on first run, then embeds whatever isn't already in the canon.
+    ///
+    /// A source that was ingested before is updated in place: an untouched file is skipped
+    /// without being read, and an edited one only has its changed chunks embedded. Content
+    /// identical to a document from another path is reported as a duplicate and not added.
+    pub(crate) async fn run_ingestion_job(
+        &self,
+        job: &IngestionJob,
+        provider: &Provider,
+        cancel_flag: Arc<AtomicBool>,
+        app_handle: AppHandle,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let embedding_model = provider.get_preferred_embedding_model();
+        let source_modified_at = job.source_modified_at();
+        let extraction_version = match &job.content {
+            Some(_) => None,
+            None => self.extraction_version(&job.resource()),
+        };
+        let existing = match job.doc_id {
+            Some(_) => None,
+            None => self.find_document_by_source(&job.source).await?,
+        };
+        // Read by an ingestor whose text has since changed, so its content must be read again
+        let extracted_by_older_version = existing.as_ref().map_or(false, |existing| {
+            extraction_version.map_or(false, |version| existing.extraction_version.unwrap_or(1) < version)
+        });
+
+        // An unchanged source isn't read again, but a model it was never embedded with
+        // is added to its stored chunks
+        if let (Some(existing), Some(modified_at)) = (&existing, &source_modified_at) {
+            if existing.content_hash.is_some()
+                && existing.source_modified_at.as_ref() == Some(modified_at)
+                && !extracted_by_older_version
+            {
+                self.link_job_to_document(job.id, existing.id).await?;
+                self.embed_missing_vectors(existing.id, provider).await?;
+                log::info!("{} hasn't changed since it was ingested", job.display_name());
+                return Ok(());
+            }
+        }
+
+        let (title, content, segments, metadata) = match &job.content {
+            Some(content) => (job.title.clone().unwrap_or_else(|| job.source.clone()), content.clone(), Vec::new(), None),
+            None => {
+                let ingested = self.ingest_resource(&job.resource()).await?;
+                (ingested.title, ingested.content, ingested.segments, Some(ingested.metadata))
+            }
+        };
+        let hash = content_hash(&content);
+
+        let doc_id = match (job.doc_id, existing) {
+            (Some(doc_id), _) => doc_id,
+            (None, Some(existing)) => {
+                self.link_job_to_document(job.id, existing.id).await?;
+                if existing.content_hash.as_deref() == Some(hash.as_str()) {
+                    // Touched but not edited
+                    self.embed_missing_vectors(existing.id, provider).await?;
+                    self.record_document_source(existing.id, &hash, source_modified_at.as_deref(), extraction_version).await?;
+                    log::info!("{} is unchanged since it was ingested", job.display_name());
+                    return Ok(());
+                }
+                if extracted_by_older_version {
+                    let message = format!(
+                        "{} was read by an older version of its text extraction and will be re-embedded",
+                        job.display_name()
+                    );
+                    log::info!("{}", message);
+                    let _ = app_handle.emit("simple-log-message", json!({
+                        "message": message,
+                        "timestamp": chrono::Local::now().to_rfc3339(),
+                        "level": "info"
+                    }));
+                }
+                existing.id
+            }
+            (None, None) => {
+                if let Some(duplicate) = self.find_document_by_hash(&hash).await? {
+                    self.link_job_to_document(job.id, duplicate.id).await?;
+                    self.embed_missing_vectors(duplicate.id, provider).await?;
+                    let _ = app_handle.emit("simple-log-message", json!({
+                        "message": format!("{} is identical to {}, which is already in the canon", job.display_name(), duplicate.file_path),
+                        "timestamp": chrono::Local::now().to_rfc3339(),
+                        "level": "info"
+                    }));
+                    return Ok(());
+                }
+
+                let document = Document {
+                    id: 0,
+                    name: title.clone(),
+                    created_at: Local::now().to_rfc3339(),
+                    file_path: job.source.clone(),
+                    embedding_model_name: embedding_model.clone(),
+                    notes: "".to_string(),
+                };
+                let doc_id = {
+                    let conn = self.conn.lock().await;
+                    self.add_document_internal(&conn, document)?
+                };
+                self.link_job_to_document(job.id, doc_id).await?;
+                doc_id
+            }
+        };
+
+        if let Some(metadata) = &metadata {
+            let conn = self.conn.lock().await;
+            Self::record_document_metadata(&conn, doc_id, &title, metadata)?;
+        }
+
+        let checkpoint = JobCheckpoint {
+            job_id: job.id,
+            completed_chunks: job.completed_chunks,
+            cancel_flag,
+        };
+        self.process_embeddings(doc_id, content, &segments, job.display_name(), provider, app_handle, Some(&checkpoint))
+        .await?;
+        self.record_document_source(doc_id, &hash, source_modified_at.as_deref(), extraction_version).await
+    }
+}
+
+/// Handle to the background ingestion worker, kept in `AppState`
+#[derive(Debug, Clone)]
+pub struct IngestionQueue {
+    wake: Arc<Notify>,
+    cancel_flags: Arc<std::sync::Mutex<HashMap<i64, Arc<AtomicBool>>>>,
+}
+
+impl IngestionQueue {
+    pub fn new() -> Self {
+        Self {
+            wake: Arc::new(Notify::new()),
+            cancel_flags: Arc::new(std::sync::Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Wakes the worker to look for queued jobs
+    pub fn notify(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Signals a running job to stop after its in-flight batch. Returns false if the job isn't running.
+    pub fn cancel_running(&self, job_id: i64) -> bool {
+        match self.cancel_flags.lock().unwrap().get(&job_id) {
+            Some(flag) => {
+                flag.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Spawns the worker. It always works against whichever canon is currently loaded.
+    pub fn start(&self, app_handle: AppHandle) {
+        let queue = self.clone();
+        tauri::async_runtime::spawn(async move {
+            log::info!("Ingestion queue worker started");
+            loop {
+                let state = app_handle.state::<AppState>();
+                let store = state.doc_store.lock().await.clone();
+
+                let job = match store.next_queued_job().await.map_err(|e| e.to_string()) {
+                    Ok(Some(job)) => job,
+                    Ok(None) => {
+                        queue.wake.notified().await;
+                        continue;
+                    }
+                    Err(e) => {
+                        log::error!("Failed to read ingestion queue: {}", e);
+                        queue.wake.notified().await;
+                        continue;
+                    }
+                };
+
+                // Register the cancel flag before claiming so a cancel can't slip in between
+                let cancel_flag = Arc::new(AtomicBool::new(false));
+                queue.cancel_flags.lock().unwrap().insert(job.id, cancel_flag.clone());
+
+                if !store.claim_ingestion_job(job.id).await.unwrap_or(false) {
+                    queue.cancel_flags.lock().unwrap().remove(&job.id);
+                    continue;
+                }
+                emit_job_update(&app_handle, &store, job.id).await;
+
+                let provider = {
+                    let preferences = state.preferences.lock().await;
+                    crate::get_preferred_llm_provider(&app_handle, &preferences)
+                };
+
+                let outcome: Result<(), (JobStatus, String)> = match provider {
+                    Ok(provider) => store
+                    .run_ingestion_job(&job, &provider, cancel_flag, app_handle.clone())
+                    .await
+                    .map_err(|e| match e.downcast_ref::<IngestionJobError>() {
+                        Some(IngestionJobError::Cancelled(_)) => (JobStatus::Cancelled, e.to_string()),
+                        _ => (JobStatus::Failed, e.to_string()),
+                    }),
+                    Err(e) => Err((JobStatus::Failed, e)),
+                };
+                queue.cancel_flags.lock().unwrap().remove(&job.id);
+
+                let (status, error) = match outcome {
+                    Ok(()) => {
+                        log::info!("Ingestion job {} done: {}", job.id, job.source);
+                        (JobStatus::Done, None)
+                    }
+                    Err((status, error)) => {
+                        log::warn!("Ingestion job {} {}: {}", job.id, status.as_str(), error);
+                        let level = if status == JobStatus::Cancelled { "info" } else { "error" };
+                        let _ = app_handle.emit("simple-log-message", json!({
+                            "message": format!("Ingestion of {} {}: {}", job.display_name(), status.as_str(), error),
+                            "timestamp": chrono::Local::now().to_rfc3339(),
+                            "level": level
+                        }));
+                        (status, Some(error))
+                    }
+                };
+                if let Err(e) = store.set_ingestion_job_status(job.id, status, error).await {
+                    log::error!("Failed to record status for ingestion job {}: {}", job.id, e);
+                }
+                emit_job_update(&app_handle, &store, job.id).await;
+            }
+        });
+    }
+}
+
+/// Lets the UI refresh a job row without polling
+pub(crate) async fn emit_job_update(app_handle: &AppHandle, store: &DocumentStore, job_id: i64) {
+    if let Ok(job) = store.get_ingestion_job(job_id).await {
+        let _ = app_handle.emit("ingestion-job-updated", &job);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_job_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DocumentStore::new(dir.path().to_path_buf()).unwrap();
+
+        let job_id = store
+        .enqueue_ingestion_job("/tmp/big-sleep.pdf", JobSourceKind::File, None, None)
+        .await
+        .unwrap();
+        let job = store.next_queued_job().await.unwrap().unwrap();
+        assert_eq!(job.id, job_id);
+        assert_eq!(job.status, "queued");
+
+        assert!(store.claim_ingestion_job(job_id).await.unwrap());
+        // A running job can't be claimed twice or cancelled through the queued path
+        assert!(!store.claim_ingestion_job(job_id).await.unwrap());
+        assert!(!store.cancel_queued_ingestion_job(job_id).await.unwrap());
+
+        store.set_ingestion_job_status(job_id, JobStatus::Failed, Some("boom".to_string())).await.unwrap();
+        assert!(store.next_queued_job().await.unwrap().is_none());
+
+        assert!(store.retry_ingestion_job(job_id).await.unwrap());
+        let job = store.get_ingestion_job(job_id).await.unwrap();
+        assert_eq!(job.status, "queued");
+        assert!(job.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_running_jobs_are_requeued_on_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let job_id = {
+            let store = DocumentStore::new(dir.path().to_path_buf()).unwrap();
+            let job_id = store
+            .enqueue_ingestion_job("/tmp/long-goodbye.epub", JobSourceKind::File, None, None)
+            .await
+            .unwrap();
+            store.claim_ingestion_job(job_id).await.unwrap();
+            job_id
+        };
+
+        let reopened = DocumentStore::new(dir.path().to_path_buf()).unwrap();
+        let job = reopened.get_ingestion_job(job_id).await.unwrap();
+        assert_eq!(job.status, "queued");
+    }
+}
@@ -34,7 +34,18 @@ use crate::ai::AIProviderError;
 use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
 use window_vibrancy::{apply_blur, apply_vibrancy, NSVisualEffectMaterial};
 use embeddings::EmbeddingGenerator;
-use document_store::DocumentStore;
+use document_store::{DocumentStore, EmbeddingBatchConfig, EmbeddingCoverage, EmbeddingModelMismatch, SearchHit, SearchMode, SearchOptions};
+use ingestion_queue::{IngestionJob, JobSourceKind};
+use linked_folders::{FolderSyncReport, LinkedFolder, LinkedFolderStatus};
+use canon_archive::{ArchiveImportReport, ArchiveManifest};
+use canon_ops::{CanonTransferReport, DocumentSelection, PathConflict};
+use attached_canons::AttachedCanonInfo;
+use collections::{PauseChanges, RetrievalPreset, TagInfo};
+use document_metadata::MetadataFilter;
+use chunking::{ChunkingConfig, ChunkingStrategy};
+use ingest::PdfEngine;
+use chunk_editor::{ChunkPage, ChunkView};
+use canon_hygiene::{HygieneCleanup, HygieneCleanupReport, HygieneOptions, HygieneReport};
 
 use serde::Deserialize;
 
@@ -43,6 +54,18 @@ pub mod ingest;
 pub mod document_store;
 pub mod menu;
 pub mod embeddings;
+pub mod ingestion_queue;
+pub mod document_scanner;
+pub mod linked_folders;
+pub mod migrations;
+pub mod canon_archive;
+pub mod canon_ops;
+pub mod attached_canons;
+pub mod collections;
+pub mod document_metadata;
+pub mod chunking;
+pub mod chunk_editor;
+pub mod canon_hygiene;
 
 mod conversations; // Add this line
 use conversations::Conversation;
@@ -50,7 +73,7 @@ use conversations::Conversation;
 mod app_state; // Add this line
 use app_state::AppState;
 use crate::ai::providers::{self, ProviderType, Provider};
-use crate::ai::models::{ChatCompletionRequest, ChatMessage, MessageRole, EmbeddingRequest};
+use crate::ai::models::{AIModel, ChatCompletionRequest, ChatMessage, MessageRole, EmbeddingRequest};
 use crate::ai::traits::{EmbeddingProvider, ChatCompletionProvider, PreferredEmbeddingModel};
 
 // Define log levels as constants
@@ -177,7 +200,7 @@ pub fn get_resource_dir_path() -> Option<PathBuf> {
 pub fn get_preferred_llm_provider(app_handle: &AppHandle, preferences: &Preferences) -> Result<Provider, String> {
     // Create provider based on preferences
     let mut new_logger = NewLogger::new(app_handle.clone());
-    let provider = match preferences.ai_provider.to_lowercase().as_str() {
+    let mut provider = match preferences.ai_provider.to_lowercase().as_str() {
         "ollama" => {
             new_logger.simple_log_message(
                 format!("Using Ollama provider at: {}", preferences.ollama_url),
@@ -213,9 +236,50 @@ pub fn get_preferred_llm_provider(app_handle: &AppHandle, preferences: &Preferen
             }
         }
     };
+    if let Some(embedding_model) = preferences.embedding_model_for_provider() {
+        provider.set_preferred_embedding_model(embedding_model.to_string());
+    }
     Ok(provider)
 }
 
+/// The embedding model preferences select for the current provider, worked out
+/// without contacting the provider
+pub fn preferred_embedding_model_name(preferences: &Preferences) -> String {
+    if let Some(embedding_model) = preferences.embedding_model_for_provider() {
+        return embedding_model.to_string();
+    }
+    match preferences.ai_provider.to_lowercase().as_str() {
+        "ollama" => providers::OllamaProvider::DEFAULT_EMBEDDING_MODEL,
+        "lmstudio" => providers::LMStudioProvider::DEFAULT_EMBEDDING_MODEL,
+        _ => providers::OpenAIProvider::DEFAULT_EMBEDDING_MODEL,
+    }
+    .to_string()
+}
+
+/// Warns the writer when the open canon was embedded with a different model than
+/// preferences select, since searching it would find nothing
+pub async fn warn_on_embedding_model_mismatch<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    doc_store: &DocumentStore,
+    preferred_model: &str,
+) -> Option<EmbeddingModelMismatch> {
+    let mismatch = match doc_store.check_embedding_model(preferred_model).await {
+        Ok(mismatch) => mismatch?,
+        Err(e) => {
+            log::warn!("Couldn't check the canon's embedding model: {}", e);
+            return None;
+        }
+    };
+    log::warn!("{}", mismatch);
+    let _ = app_handle.emit("simple-log-message", json!({
+        "message": mismatch.to_string(),
+        "timestamp": chrono::Local::now().to_rfc3339(),
+        "level": LOG_WARN
+    }));
+    let _ = app_handle.emit("embedding-model-mismatch", &mismatch);
+    Some(mismatch)
+}
+
 #[derive(Serialize)]
 struct CompletionTiming {
     embedding_generation_ms: u128,
@@ -284,12 +348,7 @@ async fn streaming_completion_from_context(
             let cached_results = rag_cache.similarity_documents.clone();
             let cached_vector_search_results: Vec<VectorSearchResult> = cached_results
             .iter()
-            .map(|(_doc_id, doc_name, chunk_id, chunk_text, similarity)| VectorSearchResult {
-                name: doc_name.clone(),
-                similarity: *similarity,
-                content: chunk_text.clone(),
-                chunk_id: *chunk_id,
-            })
+            .map(VectorSearchResult::from)
             .collect();
             (cached_results, cached_vector_search_results)
         } else {
@@ -324,32 +383,37 @@ async fn streaming_completion_from_context(
             let similarity_count = preferences.similarity_count;
             let similarity_threshold = preferences.similarity_threshold;
             let shuffle_similars = preferences.shuffle_similars;
+            let search_options = SearchOptions::from_preferences(&preferences);
             
-            let mut results = store.search(
+            let attached = state.attached_canons.lock().await;
+            let mut results = attached.search(
+                &store,
+                &context,
                 &embedding_result, 
                 &provider, 
                 similarity_count, 
-                similarity_threshold
+                similarity_threshold,
+                &search_options
             ).await.map_err(|e| format!("Search failed: {}", e))?;
             
             
             // Convert to VectorSearchResult
             let vector_search_results: Vec<VectorSearchResult> = results
             .iter()
-            .map(|(_doc_id, doc_name, chunk_id, chunk_text, similarity)| VectorSearchResult {
-                name: doc_name.clone(),
-                similarity: *similarity,
-                content: chunk_text.clone(),
-                chunk_id: *chunk_id,
-            })
+            .map(VectorSearchResult::from)
             .collect();
             
             // Log each result
-            for (doc_id, doc_name, chunk_id, chunk_text, similarity) in &results {
+            for hit in &results {
                 new_logger.simple_log_message(
                     format!(
-                        "Search result: doc_id={}, doc_name={}, chunk_id={}, similarity={:.4}\n{}",
-                        doc_id, doc_name, chunk_id, similarity, truncate(chunk_text, 120)
+                        "Search result: doc_id={}, doc_name={}, chunk_id={}, similarity={:.4}, lexical={}\n{}",
+                        hit.doc_id,
+                        hit.doc_name,
+                        hit.chunk_id,
+                        hit.similarity,
+                        hit.lexical_score.map(|score| format!("{:.4}", score)).unwrap_or_else(|| "-".to_string()),
+                        truncate(&hit.chunk, 120)
                     ),
                     "rag_search_result".to_string(),
                     "debug".to_string()
@@ -397,8 +461,8 @@ async fn streaming_completion_from_context(
     
     // Build context from similar documents
     let mut rag_context = String::new();
-    for (_, doc_name, _, chunk_text, similarity) in &similar_docs {
-        rag_context.push_str(&format!("{}\n", chunk_text));
+    for hit in &similar_docs {
+        rag_context.push_str(&format!("{}\n", hit.chunk));
     }
     
     // Create combined system prompt
@@ -788,23 +852,30 @@ async fn ingest_from_url(
 ) -> Result<(), String> {
     
     let store = state.doc_store.lock().await;
-    let store_clone = Arc::new(store.clone());
-    let preferences = state.preferences.lock().await;
     
-    let provider = match get_preferred_llm_provider(&app_handle, &preferences) {
-        Ok(p) => p,
-        Err(e) => {
-            let line = line!();
-            log_message!(app_handle, LOG_ERROR, "Line {} - Provider initialization failed: {}", line, e);
-            return Err(format!("Line {} — Could not initialize AI provider: {}", line, e));
-        }
-    };
-    
-    match store_clone.ingest_url_async(&url, &provider, app_handle.clone()).await {
+    // Fetch the page now so the save dialog has its text; embedding runs on the queue
+    // from the fetched copy, so a retry doesn't depend on the page staying the same
+    let ingested = store
+    .ingest_resource(&ingest::Resource::Url(url.clone()))
+    .await
+    .map_err(|e| e.to_string());
+    match ingested {
         Ok(ingested_document) => {
             log::info!("Ingested URL: {}", url);
             log_message!(app_handle, LOG_INFO, "Ingested URL: {}", url);
             
+            let job_id = store
+            .enqueue_ingestion_job(
+                &url,
+                JobSourceKind::Url,
+                Some(ingested_document.title.clone()),
+                Some(ingested_document.content.clone()),
+            )
+            .await
+            .map_err(|e| format!("Couldn't queue {} for embedding: {}", url, e))?;
+            ingestion_queue::emit_job_update(&app_handle, &store, job_id).await;
+            state.ingestion_queue.notify();
+            
             // Get current date/time and format it as mmddyy_hhmmss
             let now = chrono::Local::now();
             let date_time_str = now.format("%m%d%y_%H%M%S").to_string();
@@ -1029,6 +1100,12 @@ async fn load_openai_api_key_from_keyring(
     async fn load_preferences(app_handle: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<(Preferences), String> {
         let preferences = Preferences::load_with_defaults(&state, app_handle.clone());
         *state.preferences.lock().await = preferences.clone();
+        let mut store = state.doc_store.lock().await;
+        store.set_chunking_config(ChunkingConfig::from_preferences(&preferences));
+        store.set_embedding_batch_config(EmbeddingBatchConfig::from_preferences(&preferences));
+        store.set_pdf_engine(PdfEngine::from_name(&preferences.pdf_engine));
+        warn_on_embedding_model_mismatch(&app_handle, &store, &preferred_embedding_model_name(&preferences)).await;
+        state.attached_canons.lock().await.restore(&store, &preferences.attached_canons);
         Ok((preferences))
     }
     
@@ -1087,12 +1164,22 @@ async fn load_openai_api_key_from_keyring(
         aimodelname: String,
         ollamaurl: String,
         lmstudiourl: String,
+        searchmode: Option<String>,
+        lexicalweight: Option<String>,
+        mmrlambda: Option<String>,
+        chunkingstrategy: Option<String>,
+        chunksize: Option<String>,
+        chunkoverlap: Option<String>,
+        embeddingbatchsize: Option<String>,
+        embeddingconcurrency: Option<String>,
+        embeddingmodel: Option<String>,
     ) -> Result<(Preferences), String> {
         
         // println!("update_preferences called with: {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}",
         // responselimit, mainprompt, finalpreamble, prosestyle, similaritythreshold, shufflesimilars, similaritycount, maxhistory, maxtokens, temperature, gametimerms);
         
         let mut preferences = state.preferences.lock().await;
+        let previous_preferences = preferences.clone();
         preferences.response_limit = responselimit;
         preferences.main_prompt = mainprompt;
         preferences.final_preamble = finalpreamble;
@@ -1120,6 +1207,82 @@ async fn load_openai_api_key_from_keyring(
         preferences.ai_model_name = aimodelname;
         preferences.ollama_url = ollamaurl;
         preferences.lm_studio_url = lmstudiourl;
+        // Older front ends don't send the retrieval settings; keep what's saved
+        if let Some(searchmode) = searchmode {
+            preferences.search_mode = SearchMode::from_preference(&searchmode).as_str().to_string();
+        }
+        if let Some(lexicalweight) = lexicalweight {
+            preferences.lexical_weight = Some(
+                lexicalweight
+                .parse::<f32>()
+                .map(|weight| weight.clamp(0.0, 1.0))
+                .unwrap_or(Preferences::LEXICAL_WEIGHT_DEFAULT),
+            );
+        }
+        if let Some(mmrlambda) = mmrlambda {
+            preferences.mmr_lambda = Some(
+                mmrlambda
+                .parse::<f32>()
+                .map(|lambda| lambda.clamp(0.0, 1.0))
+                .unwrap_or(Preferences::MMR_LAMBDA_DEFAULT),
+            );
+        }
+        if let Some(chunkingstrategy) = chunkingstrategy {
+            match ChunkingStrategy::from_name(&chunkingstrategy) {
+                Some(strategy) => preferences.chunking_strategy = strategy.as_str().to_string(),
+                None => {
+                    log_message!(app_handle, LOG_WARN, "Unknown chunking strategy {}, keeping {}", chunkingstrategy, preferences.chunking_strategy);
+                }
+            }
+        }
+        if let Some(chunksize) = chunksize {
+            preferences.chunk_size = chunksize.parse::<usize>().unwrap_or(Preferences::CHUNK_SIZE_DEFAULT);
+        }
+        if let Some(chunkoverlap) = chunkoverlap {
+            preferences.chunk_overlap = chunkoverlap.parse::<usize>().unwrap_or(Preferences::CHUNK_OVERLAP_DEFAULT);
+        }
+        if let Some(embeddingbatchsize) = embeddingbatchsize {
+            preferences.embedding_batch_size = embeddingbatchsize
+            .parse::<usize>()
+            .ok()
+            .filter(|size| *size > 0)
+            .unwrap_or(Preferences::EMBEDDING_BATCH_SIZE_DEFAULT);
+        }
+        if let Some(embeddingconcurrency) = embeddingconcurrency {
+            preferences.embedding_concurrency = embeddingconcurrency
+            .parse::<usize>()
+            .ok()
+            .filter(|concurrency| *concurrency > 0)
+            .unwrap_or(Preferences::EMBEDDING_CONCURRENCY_DEFAULT);
+        }
+        // The embedding model belongs to the provider chosen above; an empty name means its default.
+        // A new choice has to be a model the provider serves and can embed with.
+        if let Some(embeddingmodel) = embeddingmodel {
+            let embeddingmodel = embeddingmodel.trim().to_string();
+            if !embeddingmodel.is_empty() && preferences.embedding_model_for_provider() != Some(embeddingmodel.as_str()) {
+                let validation = match get_preferred_llm_provider(&app_handle, &preferences) {
+                    Ok(provider) => providers::validate_embedding_model(&provider, &embeddingmodel)
+                    .await
+                    .map_err(|e| e.to_string()),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = validation {
+                    *preferences = previous_preferences;
+                    return Err(log_message!(app_handle, LOG_ERROR, "Can't use {} for embeddings: {}", embeddingmodel, e));
+                }
+            }
+            preferences.set_embedding_model_for_provider(&embeddingmodel);
+        }
+        let embedding_model_changed = preferred_embedding_model_name(&preferences) != preferred_embedding_model_name(&previous_preferences);
+        {
+            let mut store = state.doc_store.lock().await;
+            store.set_chunking_config(ChunkingConfig::from_preferences(&preferences));
+            store.set_embedding_batch_config(EmbeddingBatchConfig::from_preferences(&preferences));
+            store.set_pdf_engine(PdfEngine::from_name(&preferences.pdf_engine));
+            if embedding_model_changed {
+                warn_on_embedding_model_mismatch(&app_handle, &store, &preferred_embedding_model_name(&preferences)).await;
+            }
+        }
         
         let prefs_clone = preferences.clone();
         // Attempt to save preferences and handle any errors
@@ -1139,6 +1302,12 @@ async fn load_openai_api_key_from_keyring(
     async fn reset_preferences(state: tauri::State<'_, AppState>) -> Result<(Preferences), String> {
         let mut preferences = state.preferences.lock().await;
         preferences.reset_to_defaults();
+        {
+            let mut store = state.doc_store.lock().await;
+            store.set_chunking_config(ChunkingConfig::from_preferences(&preferences));
+            store.set_embedding_batch_config(EmbeddingBatchConfig::from_preferences(&preferences));
+            store.set_pdf_engine(PdfEngine::from_name(&preferences.pdf_engine));
+        }
         preferences.save().map_err(|e| e.to_string()); 
         Ok(preferences.clone())
     }
@@ -1209,13 +1378,599 @@ async fn load_openai_api_key_from_keyring(
         let file_path_buf = PathBuf::from(file_path);
         let file_name = file_path_buf.clone().as_path().file_name().unwrap().to_str().unwrap().to_string();
         
-        let preferences = state.preferences.lock().await;
-        let provider = get_preferred_llm_provider(&app_handle, &preferences)
-        .map_err(|e| format!("Couldn't get a preferred LLM provider: {}", e))?;        let store = state.doc_store.lock().await;
-        let store_clone = Arc::new(store.clone());
-        store_clone.process_document_async(&provider, &file_path_buf, app_handle).await;
+        let store = state.doc_store.lock().await;
+        if store.find_ingestor(&file_path_buf).is_none() {
+            log_message!(app_handle, LOG_WARN, "No ingestor found for {}", file_name);
+            return Err(format!("No ingestor found for {}", file_name));
+        }
+        
+        // Ingestion runs on the queue worker so it survives failures and restarts
+        let job_id = store
+        .enqueue_ingestion_job(&file_path_buf.to_string_lossy(), JobSourceKind::File, None, None)
+        .await
+        .map_err(|e| format!("Couldn't queue {} for ingestion: {}", file_name, e))?;
+        ingestion_queue::emit_job_update(&app_handle, &store, job_id).await;
+        state.ingestion_queue.notify();
+        
+        log_message!(app_handle, LOG_INFO, "Queued {} for ingestion (job {})", file_name, job_id);
+        Ok(format!("Queued {} for ingestion", file_name))
+    }
+    
+    #[tauri::command]
+    async fn list_ingestion_jobs(
+        state: tauri::State<'_, AppState>,
+    ) -> Result<Vec<IngestionJob>, String> {
+        let store = state.doc_store.lock().await;
+        store.list_ingestion_jobs().await.map_err(|e| format!("Failed to list ingestion jobs: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn cancel_ingestion_job(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        job_id: i64,
+    ) -> Result<String, String> {
+        // A running job stops after its in-flight batch and records itself as cancelled
+        if state.ingestion_queue.cancel_running(job_id) {
+            log_message!(app_handle, LOG_INFO, "Cancelling ingestion job {}", job_id);
+            return Ok(format!("Cancelling ingestion job {}", job_id));
+        }
+        
+        let store = state.doc_store.lock().await;
+        let cancelled = store.cancel_queued_ingestion_job(job_id).await
+        .map_err(|e| format!("Failed to cancel ingestion job {}: {}", job_id, e))?;
+        if !cancelled {
+            return Err(format!("Ingestion job {} is not queued or running", job_id));
+        }
+        ingestion_queue::emit_job_update(&app_handle, &store, job_id).await;
+        log_message!(app_handle, LOG_INFO, "Cancelled ingestion job {}", job_id);
+        Ok(format!("Cancelled ingestion job {}", job_id))
+    }
+    
+    #[tauri::command]
+    async fn retry_ingestion_job(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        job_id: i64,
+    ) -> Result<String, String> {
+        let store = state.doc_store.lock().await;
+        let requeued = store.retry_ingestion_job(job_id).await
+        .map_err(|e| format!("Failed to retry ingestion job {}: {}", job_id, e))?;
+        if !requeued {
+            return Err(format!("Ingestion job {} has not failed or been cancelled", job_id));
+        }
+        ingestion_queue::emit_job_update(&app_handle, &store, job_id).await;
+        state.ingestion_queue.notify();
+        log_message!(app_handle, LOG_INFO, "Retrying ingestion job {}", job_id);
+        Ok(format!("Retrying ingestion job {}", job_id))
+    }
+    
+    #[tauri::command]
+    async fn export_canon_archive(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        path: String,
+        includesources: Option<bool>,
+    ) -> Result<ArchiveManifest, String> {
+        let store = state.doc_store.lock().await;
+        let manifest = store.export_archive(std::path::Path::new(&path), includesources.unwrap_or(false)).await
+        .map_err(|e| format!("Failed to export canon to {}: {}", path, e))?;
+        log_message!(app_handle, LOG_INFO, "Exported {} documents to {}", manifest.documents, path);
+        Ok(manifest)
+    }
+    
+    #[tauri::command]
+    async fn import_canon_archive(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        path: String,
+        pathroot: Option<String>,
+        sourcesdir: Option<String>,
+    ) -> Result<ArchiveImportReport, String> {
+        let store = state.doc_store.lock().await;
+        let report = store
+        .import_archive(
+            std::path::Path::new(&path),
+            pathroot.as_deref().map(std::path::Path::new),
+            sourcesdir.as_deref().map(std::path::Path::new),
+        )
+        .await
+        .map_err(|e| format!("Failed to import canon archive {}: {}", path, e))?;
+        log_message!(
+            app_handle,
+            LOG_INFO,
+            "Imported {} documents from {} ({} already in the canon, {} path conflicts)",
+            report.documents_imported,
+            path,
+            report.duplicates_skipped,
+            report.conflicts_skipped
+        );
+        Ok(report)
+    }
+    
+    #[tauri::command]
+    async fn merge_canon(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        sourcepath: String,
+        onconflict: Option<PathConflict>,
+    ) -> Result<CanonTransferReport, String> {
+        let store = state.doc_store.lock().await;
+        let report = store.merge_canon(std::path::Path::new(&sourcepath), onconflict.unwrap_or_default()).await
+        .map_err(|e| format!("Failed to merge {} into the canon: {}", sourcepath, e))?;
+        log_message!(app_handle, LOG_INFO, "Merged {} documents from {}", report.documents_copied, sourcepath);
+        Ok(report)
+    }
+    
+    #[tauri::command]
+    async fn split_canon(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        targetpath: String,
+        selection: DocumentSelection,
+        removefromsource: Option<bool>,
+    ) -> Result<CanonTransferReport, String> {
+        let store = state.doc_store.lock().await;
+        let report = store
+        .split_canon(&selection, std::path::Path::new(&targetpath), removefromsource.unwrap_or(false))
+        .await
+        .map_err(|e| format!("Failed to split documents into {}: {}", targetpath, e))?;
+        log_message!(app_handle, LOG_INFO, "Split {} documents into {}", report.documents_copied, targetpath);
+        Ok(report)
+    }
+    
+    #[tauri::command]
+    async fn copy_documents_to_canon(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        docids: Vec<i64>,
+        targetpath: String,
+        onconflict: Option<PathConflict>,
+    ) -> Result<CanonTransferReport, String> {
+        let store = state.doc_store.lock().await;
+        let report = store
+        .copy_documents_to(&docids, std::path::Path::new(&targetpath), onconflict.unwrap_or_default())
+        .await
+        .map_err(|e| format!("Failed to copy documents to {}: {}", targetpath, e))?;
+        log_message!(app_handle, LOG_INFO, "Copied {} documents to {}", report.documents_copied, targetpath);
+        Ok(report)
+    }
+    
+    /// Remembers the current attachments in preferences so they come back next launch
+    async fn remember_attached_canons(state: &tauri::State<'_, AppState>) -> Result<(), String> {
+        let mut preferences = state.preferences.lock().await;
+        let remembered = state.attached_canons.lock().await.preferences();
+        preferences.attached_canons = remembered;
+        preferences.save().map_err(|e| format!("Failed to save preferences: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn attach_canon(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        path: String,
+        weight: Option<f32>,
+        label: Option<String>,
+    ) -> Result<Vec<AttachedCanonInfo>, String> {
+        let preferred_model = preferred_embedding_model_name(&*state.preferences.lock().await);
+        let store = state.doc_store.lock().await;
+        let mut attached = state.attached_canons.lock().await;
+        attached
+        .attach(&store, std::path::Path::new(&path), weight.unwrap_or(1.0), label)
+        .map_err(|e| format!("Failed to attach {}: {}", path, e))?;
+        let infos = attached.list().await;
+        drop(attached);
+        drop(store);
+        
+        // The canon just attached is listed last
+        if let Some(info) = infos.last() {
+            if let Some(model) = info.embedding_model_name.as_deref().filter(|model| *model != preferred_model) {
+                log_message!(app_handle, LOG_WARN, "{} was embedded with {}, not {}; it won't turn up in searches", info.label, model, preferred_model);
+            }
+        }
+        remember_attached_canons(&state).await?;
+        log_message!(app_handle, LOG_INFO, "Attached canon {}", path);
+        Ok(infos)
+    }
+    
+    #[tauri::command]
+    async fn detach_canon(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        path: String,
+    ) -> Result<Vec<AttachedCanonInfo>, String> {
+        let infos = {
+            let mut attached = state.attached_canons.lock().await;
+            attached.detach(&path).map_err(|e| e.to_string())?;
+            attached.list().await
+        };
+        remember_attached_canons(&state).await?;
+        log_message!(app_handle, LOG_INFO, "Detached canon {}", path);
+        Ok(infos)
+    }
+    
+    #[tauri::command]
+    async fn set_attached_canon_weight(
+        state: tauri::State<'_, AppState>,
+        path: String,
+        weight: f32,
+    ) -> Result<Vec<AttachedCanonInfo>, String> {
+        let infos = {
+            let mut attached = state.attached_canons.lock().await;
+            attached.set_weight(&path, weight).map_err(|e| e.to_string())?;
+            attached.list().await
+        };
+        remember_attached_canons(&state).await?;
+        Ok(infos)
+    }
+    
+    #[tauri::command]
+    async fn list_attached_canons(state: tauri::State<'_, AppState>) -> Result<Vec<AttachedCanonInfo>, String> {
+        Ok(state.attached_canons.lock().await.list().await)
+    }
+    
+    #[tauri::command]
+    async fn tag_documents(
+        state: tauri::State<'_, AppState>,
+        docids: Vec<i64>,
+        tags: Vec<String>,
+    ) -> Result<usize, String> {
+        let store = state.doc_store.lock().await;
+        store.tag_documents(&docids, &tags).await
+        .map_err(|e| format!("Failed to tag documents: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn untag_documents(
+        state: tauri::State<'_, AppState>,
+        docids: Vec<i64>,
+        tags: Vec<String>,
+    ) -> Result<usize, String> {
+        let store = state.doc_store.lock().await;
+        store.untag_documents(&docids, &tags).await
+        .map_err(|e| format!("Failed to untag documents: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn list_tags(state: tauri::State<'_, AppState>) -> Result<Vec<TagInfo>, String> {
+        let store = state.doc_store.lock().await;
+        store.list_tags().await.map_err(|e| format!("Failed to list tags: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn rename_tag(
+        state: tauri::State<'_, AppState>,
+        name: String,
+        newname: String,
+    ) -> Result<String, String> {
+        let store = state.doc_store.lock().await;
+        store.rename_tag(&name, &newname).await
+        .map_err(|e| format!("Failed to rename tag {}: {}", name, e))?;
+        Ok(format!("Renamed tag {} to {}", name, newname))
+    }
+    
+    #[tauri::command]
+    async fn delete_tag(state: tauri::State<'_, AppState>, name: String) -> Result<String, String> {
+        let store = state.doc_store.lock().await;
+        store.delete_tag(&name).await
+        .map_err(|e| format!("Failed to delete tag {}: {}", name, e))?;
+        Ok(format!("Deleted tag {}", name))
+    }
+    
+    /// Tells the canon list which documents were paused or unpaused in bulk
+    fn emit_pause_changes(app_handle: &tauri::AppHandle, changes: &PauseChanges) {
+        for (doc_ids, paused) in [(&changes.paused, true), (&changes.unpaused, false)] {
+            for doc_id in doc_ids {
+                let _ = app_handle.emit("rag-pause-state-changed", json!({
+                    "id": doc_id,
+                    "paused": paused
+                }));
+            }
+        }
+    }
+    
+    #[tauri::command]
+    async fn set_collection_paused(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        tag: String,
+        paused: bool,
+    ) -> Result<PauseChanges, String> {
+        let store = state.doc_store.lock().await;
+        let changes = store.set_collection_paused(&tag, paused).await
+        .map_err(|e| format!("Failed to update collection {}: {}", tag, e))?;
+        emit_pause_changes(&app_handle, &changes);
+        Ok(changes)
+    }
+    
+    #[tauri::command]
+    async fn save_retrieval_preset(
+        state: tauri::State<'_, AppState>,
+        preset: RetrievalPreset,
+    ) -> Result<RetrievalPreset, String> {
+        let store = state.doc_store.lock().await;
+        store.save_retrieval_preset(&preset).await
+        .map_err(|e| format!("Failed to save preset {}: {}", preset.name, e))
+    }
+    
+    #[tauri::command]
+    async fn list_retrieval_presets(state: tauri::State<'_, AppState>) -> Result<Vec<RetrievalPreset>, String> {
+        let store = state.doc_store.lock().await;
+        store.list_retrieval_presets().await.map_err(|e| format!("Failed to list presets: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn delete_retrieval_preset(state: tauri::State<'_, AppState>, name: String) -> Result<String, String> {
+        let store = state.doc_store.lock().await;
+        store.delete_retrieval_preset(&name).await
+        .map_err(|e| format!("Failed to delete preset {}: {}", name, e))?;
+        Ok(format!("Deleted preset {}", name))
+    }
+    
+    #[tauri::command]
+    async fn apply_retrieval_preset(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        name: String,
+    ) -> Result<PauseChanges, String> {
+        let store = state.doc_store.lock().await;
+        let changes = store.apply_retrieval_preset(&name).await
+        .map_err(|e| format!("Failed to apply preset {}: {}", name, e))?;
+        emit_pause_changes(&app_handle, &changes);
+        log_message!(app_handle, LOG_INFO, "Applied retrieval preset {}: {} paused, {} unpaused", name, changes.paused.len(), changes.unpaused.len());
+        Ok(changes)
+    }
+    
+    #[tauri::command]
+    async fn list_document_chunks(
+        state: tauri::State<'_, AppState>,
+        docid: i64,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<ChunkPage, String> {
+        let store = state.doc_store.lock().await;
+        store.list_document_chunks(docid, offset.unwrap_or(0), limit.unwrap_or(50)).await
+        .map_err(|e| format!("Failed to list chunks of document {}: {}", docid, e))
+    }
+    
+    #[tauri::command]
+    async fn search_document_chunks(
+        state: tauri::State<'_, AppState>,
+        docid: i64,
+        query: String,
+    ) -> Result<Vec<ChunkView>, String> {
+        let store = state.doc_store.lock().await;
+        store.search_document_chunks(docid, &query).await
+        .map_err(|e| format!("Failed to search chunks of document {}: {}", docid, e))
+    }
+    
+    #[tauri::command]
+    async fn edit_chunk(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        chunkid: i64,
+        text: String,
+    ) -> Result<ChunkView, String> {
+        let provider = get_current_provider(state.clone()).await?;
+        let store = state.doc_store.lock().await;
+        let chunk = store.edit_chunk(chunkid, &text, &provider).await
+        .map_err(|e| format!("Failed to edit chunk {}: {}", chunkid, e))?;
+        log_message!(app_handle, LOG_INFO, "Edited and re-embedded chunk {}", chunkid);
+        Ok(chunk)
+    }
+    
+    #[tauri::command]
+    async fn delete_chunks(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        chunkids: Vec<i64>,
+    ) -> Result<usize, String> {
+        let store = state.doc_store.lock().await;
+        let deleted = store.remove_chunks(&chunkids).await
+        .map_err(|e| format!("Failed to delete chunks: {}", e))?;
+        log_message!(app_handle, LOG_INFO, "Deleted {} chunks", deleted);
+        Ok(deleted)
+    }
+    
+    #[tauri::command]
+    async fn split_chunk(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        chunkid: i64,
+        at: usize,
+    ) -> Result<Vec<ChunkView>, String> {
+        let provider = get_current_provider(state.clone()).await?;
+        let store = state.doc_store.lock().await;
+        let halves = store.split_chunk(chunkid, at, &provider).await
+        .map_err(|e| format!("Failed to split chunk {}: {}", chunkid, e))?;
+        log_message!(app_handle, LOG_INFO, "Split chunk {} in two", chunkid);
+        Ok(halves)
+    }
+    
+    #[tauri::command]
+    async fn merge_chunks(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        firstid: i64,
+        secondid: i64,
+    ) -> Result<ChunkView, String> {
+        let provider = get_current_provider(state.clone()).await?;
+        let store = state.doc_store.lock().await;
+        let merged = store.merge_chunks(firstid, secondid, &provider).await
+        .map_err(|e| format!("Failed to merge chunks {} and {}: {}", firstid, secondid, e))?;
+        log_message!(app_handle, LOG_INFO, "Merged chunk {} into {}", secondid, firstid);
+        Ok(merged)
+    }
+    
+    #[tauri::command]
+    async fn canon_hygiene_report(
+        state: tauri::State<'_, AppState>,
+        options: Option<HygieneOptions>,
+    ) -> Result<HygieneReport, String> {
+        let store = state.doc_store.lock().await;
+        store.hygiene_report(&options.unwrap_or_default()).await
+        .map_err(|e| format!("Failed to analyse the canon: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn clean_canon(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        options: Option<HygieneOptions>,
+        cleanup: Option<HygieneCleanup>,
+    ) -> Result<HygieneCleanupReport, String> {
+        let store = state.doc_store.lock().await;
+        let removed = store.clean_canon(&options.unwrap_or_default(), &cleanup.unwrap_or_default()).await
+        .map_err(|e| format!("Failed to clean up the canon: {}", e))?;
+        log_message!(
+            app_handle,
+            LOG_INFO,
+            "Canon cleanup removed {} duplicate, {} empty and {} boilerplate chunks and {} empty documents",
+            removed.duplicates_removed,
+            removed.empty_chunks_removed,
+            removed.boilerplate_removed,
+            removed.documents_removed
+        );
+        Ok(removed)
+    }
+    
+    #[tauri::command]
+    async fn add_linked_folder(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        path: String,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+    ) -> Result<LinkedFolder, String> {
+        let store = state.doc_store.lock().await;
+        let folder = store
+        .add_linked_folder(std::path::Path::new(&path), include.unwrap_or_default(), exclude.unwrap_or_default())
+        .await
+        .map_err(|e| format!("Failed to link folder {}: {}", path, e))?;
+        // The first sync runs in the background so large folders don't hold up the UI
+        state.linked_folder_sync.notify();
+        log_message!(app_handle, LOG_INFO, "Linked folder {} to the canon", path);
+        Ok(folder)
+    }
+    
+    #[tauri::command]
+    async fn update_linked_folder_rules(
+        state: tauri::State<'_, AppState>,
+        folder_id: i64,
+        include: Vec<String>,
+        exclude: Vec<String>,
+    ) -> Result<String, String> {
+        let store = state.doc_store.lock().await;
+        store.update_linked_folder_rules(folder_id, include, exclude).await
+        .map_err(|e| format!("Failed to update rules for linked folder {}: {}", folder_id, e))?;
+        state.linked_folder_sync.notify();
+        Ok(format!("Updated rules for linked folder {}", folder_id))
+    }
+    
+    #[tauri::command]
+    async fn remove_linked_folder(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+        folder_id: i64,
+        removedocuments: Option<bool>,
+    ) -> Result<String, String> {
+        let store = state.doc_store.lock().await;
+        let removed = store.remove_linked_folder(folder_id, removedocuments.unwrap_or(false)).await
+        .map_err(|e| format!("Failed to remove linked folder {}: {}", folder_id, e))?;
+        log_message!(app_handle, LOG_INFO, "Unlinked folder {} and removed {} documents", folder_id, removed);
+        Ok(format!("Unlinked folder {} and removed {} documents", folder_id, removed))
+    }
+    
+    #[tauri::command]
+    async fn list_linked_folders(
+        state: tauri::State<'_, AppState>,
+    ) -> Result<Vec<LinkedFolderStatus>, String> {
+        let store = state.doc_store.lock().await;
+        store.linked_folder_status().await.map_err(|e| format!("Failed to list linked folders: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn sync_linked_folders(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+    ) -> Result<Vec<FolderSyncReport>, String> {
+        let store = state.doc_store.lock().await.clone();
+        let reports = store.sync_linked_folders().await
+        .map_err(|e| format!("Failed to sync linked folders: {}", e))?;
+        if reports.iter().any(|report| report.queued() > 0) {
+            state.ingestion_queue.notify();
+        }
+        let _ = app_handle.emit("linked-folders-synced", &reports);
+        Ok(reports)
+    }
+    
+    #[tauri::command]
+    async fn reembed_canon(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+    ) -> Result<Vec<EmbeddingCoverage>, String> {
+        let provider = {
+            let preferences = state.preferences.lock().await;
+            get_preferred_llm_provider(&app_handle, &preferences)
+            .map_err(|e| format!("Couldn't get a preferred LLM provider: {}", e))?
+        };
+        let embedding_model = provider.get_preferred_embedding_model();
+        // Work on a clone so searches and other commands aren't blocked on the store for the whole run
+        let store = state.doc_store.lock().await.clone();
         
-        Ok("Ingested file".to_string())
+        log_message!(app_handle, LOG_INFO, "Re-embedding canon {} with {}", store.get_database_name(), embedding_model);
+        let coverage = store
+        .reembed_canon(&provider, app_handle.clone())
+        .await
+        .map_err(|e| e.to_string());
+        match coverage {
+            Ok(coverage) => {
+                for model in &coverage {
+                    log_message!(
+                        app_handle,
+                        LOG_INFO,
+                        "{}: {}/{} chunks embedded ({} dimensions)",
+                        model.embedding_model_name, model.embedded_chunks, model.total_chunks, model.dimension
+                    );
+                }
+                Ok(coverage)
+            }
+            Err(e) => {
+                log_message!(app_handle, LOG_ERROR, "Re-embedding with {} failed: {}", embedding_model, e);
+                Err(format!("Re-embedding with {} failed: {}", embedding_model, e))
+            }
+        }
+    }
+    
+    #[tauri::command]
+    async fn get_embedding_coverage(
+        state: tauri::State<'_, AppState>,
+    ) -> Result<Vec<EmbeddingCoverage>, String> {
+        let store = state.doc_store.lock().await;
+        store.embedding_coverage().await.map_err(|e| format!("Failed to read embedding coverage: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn list_embedding_models(
+        state: tauri::State<'_, AppState>,
+        app_handle: tauri::AppHandle,
+    ) -> Result<Vec<AIModel>, String> {
+        let provider = {
+            let preferences = state.preferences.lock().await;
+            get_preferred_llm_provider(&app_handle, &preferences)?
+        };
+        providers::list_embedding_models(&provider)
+        .await
+        .map_err(|e| format!("Failed to list embedding models: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn check_canon_embedding_model(
+        state: tauri::State<'_, AppState>,
+    ) -> Result<Option<EmbeddingModelMismatch>, String> {
+        let preferred_model = preferred_embedding_model_name(&*state.preferences.lock().await);
+        let store = state.doc_store.lock().await;
+        store.check_embedding_model(&preferred_model).await.map_err(|e| e.to_string())
     }
     
     #[tauri::command]
@@ -1251,6 +2006,7 @@ async fn load_openai_api_key_from_keyring(
         let similarity_count = preferences.similarity_count;
         let max_history = preferences.max_history;
         let similarity_threshold = preferences.similarity_threshold;
+        let search_options = SearchOptions::from_preferences(&preferences);
         let mut new_logger = NewLogger::new(app_handle.clone());
         
         //let openai_api_key = get_api_key(&app_handle).map_err(|e| e.to_string())?;
@@ -1329,14 +2085,15 @@ async fn load_openai_api_key_from_keyring(
         
         // Time similarity search
         let start_search = Instant::now();
-        let mut similar_docs: Vec<(i64, String, usize, String, f32)> = Vec::new();
+        let mut similar_docs: Vec<SearchHit> = Vec::new();
         let database_name: String;
         let database_path: String;
         // similar docs get the top 4, which may all be from the same source
         // fence this off so we can release the lock on the store
         {
             let store = state.doc_store.lock().await;
-            similar_docs = store.search(&embedding_result, &provider, similarity_count, similarity_threshold).await.map_err(|e| e.to_string())?;
+            let attached = state.attached_canons.lock().await;
+            similar_docs = attached.search(&store, &input, &embedding_result, &provider, similarity_count, similarity_threshold, &search_options).await.map_err(|e| e.to_string())?;
             database_name = (store.get_database_name().to_string()); // Just convert &str to String
             database_path = store.get_database_path().to_string(); // Just convert &str to String
         }
@@ -1376,34 +2133,36 @@ async fn load_openai_api_key_from_keyring(
         let mut context = String::new();
         context.push_str("This is the context:");
         context.push_str("\n");
-        for (i, (doc_id, doc_name, chunk_id, chunk_text, similarity)) in similar_docs.iter().enumerate() {
-            context.push_str(&format!("{}\n", chunk_text));
+        for hit in &similar_docs {
+            context.push_str(&format!("{}\n", hit.chunk));
         }
         
         let mut vector_search_results_for_log: Vec<VectorSearchResult> = Vec::new();
-        let mut seen_chunk_ids: HashSet<usize> = HashSet::new();
+        // Chunk ids are only unique within one canon
+        let mut seen_chunk_ids: HashSet<(String, usize)> = HashSet::new();
         
-        for (i, (doc_id, doc_name, chunk_id, chunk_text, similarity)) in similar_docs.iter().enumerate() {
-            if seen_chunk_ids.contains(chunk_id) {
+        for hit in &similar_docs {
+            if seen_chunk_ids.contains(&(hit.canon_name.clone(), hit.chunk_id)) {
                 // Skip this item if the chunk_id is already in the HashSet
                 continue;
             }
+            let label = if hit.citation.is_empty() { &hit.doc_name } else { &hit.citation };
+            let source_name = if hit.canon_name.is_empty() || hit.canon_name == database_name {
+                label.clone()
+            } else {
+                format!("{} ({})", label, hit.canon_name)
+            };
             let msg = format!("<div>
             <div class='border-l-[4px] border-amber-300 pl-2 pr-8 text-pretty leading-tight font-[InputMono]'>{}</div>
             <div class='mt-2 px-2 py-1 rounded-sm bg-gray-700 w-fit'>{}</div>
             <span class='mt-2 font-bold'>{}</span>
-          </div>", chunk_text, similarity, doc_name);
+          </div>", hit.chunk, hit.similarity, source_name);
             
-            new_logger.simple_log_message(msg, chunk_id.to_string(), "info".to_string());
+            new_logger.simple_log_message(msg, hit.chunk_id.to_string(), "info".to_string());
             
-            vector_search_results_for_log.push(VectorSearchResult {
-                similarity: *similarity,
-                name: doc_name.clone(),
-                content: chunk_text.clone(),
-                chunk_id: *chunk_id,
-            });
+            vector_search_results_for_log.push(VectorSearchResult::from(hit));
             // Add the chunk_id to the HashSet
-            seen_chunk_ids.insert(*chunk_id);
+            seen_chunk_ids.insert((hit.canon_name.clone(), hit.chunk_id));
         }
         
         let conversation_context = state.conversation.lock().await.get_context();
@@ -1873,13 +2632,19 @@ async fn load_openai_api_key_from_keyring(
         chunk_id: usize,
         chunk_text: String,
         similarity_score: f32,
+        lexical_score: Option<f32>,
+        fused_score: Option<f32>,
+        canon_name: String,
+        citation: String,
+        page: Option<u32>,
+        section: Option<String>,
     }
     
     async fn get_current_provider(state: tauri::State<'_, AppState>) -> Result<Provider, String> {
         let state_clone = state.clone();
         let preferences = state.preferences.lock().await;
         let _app_handle = state_clone.app_handle.clone();
-        let provider = match preferences.ai_provider.to_lowercase().as_str() {
+        let mut provider = match preferences.ai_provider.to_lowercase().as_str() {
             "ollama" => {
                 let ollama_url = preferences.ollama_url.clone();
                 providers::create_provider(ProviderType::Ollama, &ollama_url)
@@ -1901,6 +2666,9 @@ async fn load_openai_api_key_from_keyring(
                 }
             }
         };
+        if let Some(embedding_model) = preferences.embedding_model_for_provider() {
+            provider.set_preferred_embedding_model(embedding_model.to_string());
+        }
         Ok(provider)
     }
     
@@ -1910,6 +2678,8 @@ async fn load_openai_api_key_from_keyring(
         app_handle: tauri::AppHandle,
         query: String,
         limit: Option<usize>,  
+        tags: Option<Vec<String>>,
+        metadata: Option<MetadataFilter>,
     ) -> Result<Vec<SearchResult>, String> {  // Changed return type
         let limit = limit.unwrap_or(3);
         let state_clone = state.clone();
@@ -1935,9 +2705,17 @@ async fn load_openai_api_key_from_keyring(
             }
         };
         let similarity_threshold = preferences.similarity_threshold;
+        let mut search_options = SearchOptions::from_preferences(&preferences);
+        if let Some(tags) = tags {
+            search_options.tags = tags;
+        }
+        if let Some(metadata) = metadata {
+            search_options.metadata = metadata;
+        }
         let store = state.doc_store.lock().await;
-        let results = store
-        .search(&query_embedding, &provider, limit, similarity_threshold)
+        let attached = state.attached_canons.lock().await;
+        let results = attached
+        .search(&store, &query, &query_embedding, &provider, limit, similarity_threshold, &search_options)
         .await // ✅ Now correctly awaiting the async function
         .map_err(|e| format!("Search failed: {}", e))?;
         
@@ -1945,11 +2723,17 @@ async fn load_openai_api_key_from_keyring(
         // Transform results into SearchResult structs
         Ok(results
             .into_iter()
-            .map(|(doc_id, doc, index, chunk, similarity)| SearchResult {
-                document_name: doc,
-                chunk_id: index,
-                chunk_text: chunk,
-                similarity_score: similarity,
+            .map(|hit| SearchResult {
+                document_name: hit.doc_name,
+                chunk_id: hit.chunk_id,
+                chunk_text: hit.chunk,
+                similarity_score: hit.similarity,
+                lexical_score: hit.lexical_score,
+                fused_score: hit.fused_score,
+                canon_name: hit.canon_name,
+                citation: hit.citation,
+                page: hit.provenance.page,
+                section: hit.provenance.section,
             })
             .collect())
         }
@@ -2397,6 +3181,8 @@ async fn load_openai_api_key_from_keyring(
                 name: String,
                 notes: String,
                 authors: Vec<String>,
+                weight: Option<f32>,
+                pinned: Option<bool>,
             ) -> Result<String, String> {
                 let doc_store = Arc::clone(&app_state.doc_store);
                 let store = doc_store.lock().await;
@@ -2413,7 +3199,7 @@ async fn load_openai_api_key_from_keyring(
                 let authors_json = serde_json::to_string(&authors)
                 .map_err(|e| format!("Failed to serialize authors: {}", e))?;
                 
-                match store.update_document_details(doc_id_int, name.clone(), notes.clone(), authors_json).await {
+                match store.update_document_details(doc_id_int, name.clone(), notes.clone(), authors_json, weight, pinned).await {
                     Ok(_) => {
                         log_message!(app_handle, LOG_INFO, "Updated document details for ID {}: name={}, authors={}", 
                         doc_id, name, authors.join(", "));
@@ -2573,6 +3359,8 @@ async fn load_openai_api_key_from_keyring(
                     
                     
                     app.manage(app_state);
+                    app.state::<AppState>().ingestion_queue.start(app_handle.clone());
+                    app.state::<AppState>().linked_folder_sync.start(app_handle.clone());
                     //let foo = app.state::<AppState>();
                     
                     //log::debug!("AppState managed? {:?}", foo);
@@ -2626,6 +3414,45 @@ async fn load_openai_api_key_from_keyring(
                 get_vibe_genre_context,
                 streaming_completion_from_context,
                 simplify_text,
+                list_ingestion_jobs,
+                cancel_ingestion_job,
+                retry_ingestion_job,
+                reembed_canon,
+                get_embedding_coverage,
+                list_embedding_models,
+                check_canon_embedding_model,
+                add_linked_folder,
+                update_linked_folder_rules,
+                remove_linked_folder,
+                list_linked_folders,
+                sync_linked_folders,
+                export_canon_archive,
+                import_canon_archive,
+                merge_canon,
+                split_canon,
+                copy_documents_to_canon,
+                attach_canon,
+                detach_canon,
+                set_attached_canon_weight,
+                list_attached_canons,
+                tag_documents,
+                untag_documents,
+                list_tags,
+                rename_tag,
+                delete_tag,
+                set_collection_paused,
+                save_retrieval_preset,
+                list_retrieval_presets,
+                delete_retrieval_preset,
+                apply_retrieval_preset,
+                list_document_chunks,
+                search_document_chunks,
+                edit_chunk,
+                delete_chunks,
+                split_chunk,
+                merge_chunks,
+                canon_hygiene_report,
+                clean_canon,
                 ])
                 .run(tauri::generate_context!())
                 .expect("error while running tauri application");
@@ -0,0 +1,461 @@
+#![allow(unused_imports)]
+#![allow(dead_code)]
+// src/linked_folders.rs
+//
+// A canon can subscribe to folders on disk. Each sync walks a folder with the
+// DocumentScanner, queues ingestion jobs for files that are new or changed since they
+// were last ingested, and deletes documents whose files have gone. Embedding still
+// happens in the ingestion queue, which only re-embeds the chunks that changed.
+// There's no filesystem notification here: a background task syncs on an interval
+// and whenever the folder list changes.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Notify;
+use std::sync::Arc;
+
+use crate::app_state::AppState;
+use crate::document_scanner::DocumentScanner;
+use crate::document_store::DocumentStore;
+use crate::ingestion_queue::JobSourceKind;
+
+/// How often linked folders are re-scanned when nothing prompts a sync sooner
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkedFolder {
+    pub id: i64,
+    pub path: String,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub created_at: String,
+    pub last_synced_at: Option<String>,
+    pub last_error: Option<String>,
+}
+
+/// What one sync of a folder did
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FolderSyncReport {
+    pub folder_id: i64,
+    pub path: String,
+    /// Files with no document yet, queued for ingestion
+    pub queued_new: usize,
+    /// Files modified since they were ingested, queued for re-embedding
+    pub queued_changed: usize,
+    /// Documents deleted because their file is gone
+    pub removed: usize,
+    /// Files already up to date, or already queued
+    pub unchanged: usize,
+    pub error: Option<String>,
+}
+
+impl FolderSyncReport {
+    pub fn queued(&self) -> usize {
+        self.queued_new + self.queued_changed
+    }
+}
+
+/// A linked folder with its current state, for the sync status view
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkedFolderStatus {
+    #[serde(flatten)]
+    pub folder: LinkedFolder,
+    /// Documents in the canon from files under the folder
+    pub documents: usize,
+    /// Ingestion jobs for the folder's files that are queued or running
+    pub pending_jobs: usize,
+    /// Ingestion jobs for the folder's files that failed
+    pub failed_jobs: usize,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LinkedFolderError {
+    #[error("{0} is not a directory")]
+    NotADirectory(String),
+    #[error("Invalid glob rule: {0}")]
+    InvalidRule(#[from] glob::PatternError),
+    #[error("Linked folder {0} not found")]
+    NotFound(i64),
+}
+
+const FOLDER_COLUMNS: &str = "id, path, include_globs, exclude_globs, created_at, last_synced_at, last_error";
+
+fn folder_from_row(row: &rusqlite::Row) -> rusqlite::Result<LinkedFolder> {
+    let include: Option<String> = row.get(2)?;
+    let exclude: Option<String> = row.get(3)?;
+    Ok(LinkedFolder {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        include: include.and_then(|rules| serde_json::from_str(&rules).ok()).unwrap_or_default(),
+        exclude: exclude.and_then(|rules| serde_json::from_str(&rules).ok()).unwrap_or_default(),
+        created_at: row.get(4)?,
+        last_synced_at: row.get(5)?,
+        last_error: row.get(6)?,
+    })
+}
+
+pub(crate) fn initialize_linked_folders_table(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS linked_folders
+        (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        path TEXT NOT NULL UNIQUE,
+        include_globs JSON,
+        exclude_globs JSON,
+        created_at TEXT NOT NULL,
+        last_synced_at TEXT,
+        last_error TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Whether a file was modified after `since`. Unreadable times count as modified so
+/// the file gets another look rather than being ignored.
+fn modified_after(modified_at: &str, since: Option<&str>) -> bool {
+    let since = match since {
+        Some(since) => since,
+        None => return true,
+    };
+    match (DateTime::parse_from_rfc3339(modified_at), DateTime::parse_from_rfc3339(since)) {
+        (Ok(modified_at), Ok(since)) => modified_at > since,
+        _ => true,
+    }
+}
+
+impl DocumentStore {
+    /// Subscribes the canon to a folder. Rules are checked before anything is stored.
+    pub async fn add_linked_folder(
+        &self,
+        path: &Path,
+        include: Vec<String>,
+        exclude: Vec<String>,
+    ) -> Result<LinkedFolder, Box<dyn std::error::Error + Send + Sync>> {
+        if !path.is_dir() {
+            return Err(LinkedFolderError::NotADirectory(path.display().to_string()).into());
+        }
+        DocumentScanner::new().with_rules(&include, &exclude).map_err(LinkedFolderError::from)?;
+
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO linked_folders (path, include_globs, exclude_globs, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                path.to_string_lossy(),
+                serde_json::to_string(&include)?,
+                serde_json::to_string(&exclude)?,
+                Local::now().to_rfc3339()
+            ],
+        )?;
+        let folder_id = conn.last_insert_rowid();
+        Ok(conn.query_row(
+            &format!("SELECT {} FROM linked_folders WHERE id = ?1", FOLDER_COLUMNS),
+            params![folder_id],
+            folder_from_row,
+        )?)
+    }
+
+    pub async fn update_linked_folder_rules(
+        &self,
+        folder_id: i64,
+        include: Vec<String>,
+        exclude: Vec<String>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        DocumentScanner::new().with_rules(&include, &exclude).map_err(LinkedFolderError::from)?;
+        let conn = self.conn.lock().await;
+        let updated = conn.execute(
+            "UPDATE linked_folders SET include_globs = ?1, exclude_globs = ?2 WHERE id = ?3",
+            params![serde_json::to_string(&include)?, serde_json::to_string(&exclude)?, folder_id],
+        )?;
+        if updated == 0 {
+            return Err(LinkedFolderError::NotFound(folder_id).into());
+        }
+        Ok(())
+    }
+
+    /// Unsubscribes from a folder. Its documents stay in the canon unless `remove_documents` is set.
+    pub async fn remove_linked_folder(
+        &self,
+        folder_id: i64,
+        remove_documents: bool,
+    ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let folder = self.get_linked_folder(folder_id).await?;
+        let mut removed = 0;
+        if remove_documents {
+            for (doc_id, _) in self.documents_under(Path::new(&folder.path)).await? {
+                self.delete_document(doc_id).await.map_err(|e| e.to_string())?;
+                removed += 1;
+            }
+        }
+        let conn = self.conn.lock().await;
+        conn.execute("DELETE FROM linked_folders WHERE id = ?1", params![folder_id])?;
+        Ok(removed)
+    }
+
+    pub async fn list_linked_folders(&self) -> Result<Vec<LinkedFolder>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM linked_folders ORDER BY path", FOLDER_COLUMNS))?;
+        let folders = stmt.query_map([], folder_from_row)?.collect::<Result<Vec<_>, _>>()?;
+        Ok(folders)
+    }
+
+    pub async fn get_linked_folder(&self, folder_id: i64) -> Result<LinkedFolder, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        conn.query_row(
+            &format!("SELECT {} FROM linked_folders WHERE id = ?1", FOLDER_COLUMNS),
+            params![folder_id],
+            folder_from_row,
+        )
+        .optional()?
+        .ok_or_else(|| LinkedFolderError::NotFound(folder_id).into())
+    }
+
+    /// Documents (id, file path) whose source file sits under `folder`
+    async fn documents_under(&self, folder: &Path) -> Result<Vec<(i64, String)>, Box<dyn std::error::Error + Send + Sync>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT id, file_path FROM documents")?;
+        let documents = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+        Ok(documents
+        .into_iter()
+        .filter(|(_, file_path)| Path::new(file_path).starts_with(folder))
+        .collect())
+    }
+
+    /// Brings the canon in line with one folder. New and modified files are queued for
+    /// ingestion; a file counts as handled once a job for it was created after its last
+    /// modification, which also covers files skipped as duplicates or still in the queue.
+    /// Documents whose files no longer exist are deleted.
+    pub async fn sync_linked_folder(&self, folder: &LinkedFolder) -> Result<FolderSyncReport, Box<dyn std::error::Error + Send + Sync>> {
+        let mut report = FolderSyncReport {
+            folder_id: folder.id,
+            path: folder.path.clone(),
+            ..Default::default()
+        };
+        let folder_path = PathBuf::from(&folder.path);
+        let scanner = DocumentScanner::with_ingestors(self.ingestors())
+        .with_rules(&folder.include, &folder.exclude)
+        .map_err(LinkedFolderError::from)?;
+        let files = scanner.scan_directory(&folder_path)?;
+
+        // What the canon already knows about each path under the folder
+        let (modified_at_by_path, last_job_by_path) = {
+            let conn = self.conn.lock().await;
+            let mut stmt = conn.prepare("SELECT file_path, source_modified_at FROM documents")?;
+            let modified_at_by_path: HashMap<String, Option<String>> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+            let mut stmt = conn.prepare("SELECT source, MAX(created_at) FROM jobs WHERE source_kind = 'file' GROUP BY source")?;
+            let last_job_by_path: HashMap<String, String> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+            (modified_at_by_path, last_job_by_path)
+        };
+
+        for file in &files {
+            let source = file.to_string_lossy().to_string();
+            let modified_at = match std::fs::metadata(file).and_then(|metadata| metadata.modified()) {
+                Ok(modified) => DateTime::<Local>::from(modified).to_rfc3339(),
+                Err(e) => {
+                    log::warn!("Skipping {} during folder sync: {}", source, e);
+                    continue;
+                }
+            };
+            if !modified_after(&modified_at, last_job_by_path.get(&source).map(String::as_str)) {
+                report.unchanged += 1;
+                continue;
+            }
+            match modified_at_by_path.get(&source) {
+                Some(recorded) if recorded.as_deref() == Some(modified_at.as_str()) => report.unchanged += 1,
+                Some(_) => {
+                    self.enqueue_ingestion_job(&source, JobSourceKind::File, None, None).await?;
+                    report.queued_changed += 1;
+                }
+                None => {
+                    self.enqueue_ingestion_job(&source, JobSourceKind::File, None, None).await?;
+                    report.queued_new += 1;
+                }
+            }
+        }
+
+        for (doc_id, file_path) in self.documents_under(&folder_path).await? {
+            if !Path::new(&file_path).exists() {
+                self.delete_document(doc_id).await.map_err(|e| e.to_string())?;
+                log::info!("Removed {} from the canon; its file is gone", file_path);
+                report.removed += 1;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Syncs every linked folder, recording the outcome on each. One folder failing
+    /// (say, an unplugged drive) doesn't stop the others.
+    pub async fn sync_linked_folders(&self) -> Result<Vec<FolderSyncReport>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut reports = Vec::new();
+        for folder in self.list_linked_folders().await? {
+            let report = match self.sync_linked_folder(&folder).await {
+                Ok(report) => report,
+                Err(e) => FolderSyncReport {
+                    folder_id: folder.id,
+                    path: folder.path.clone(),
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                },
+            };
+            let conn = self.conn.lock().await;
+            conn.execute(
+                "UPDATE linked_folders SET last_synced_at = ?1, last_error = ?2 WHERE id = ?3",
+                params![Local::now().to_rfc3339(), report.error, folder.id],
+            )?;
+            reports.push(report);
+        }
+        Ok(reports)
+    }
+
+    pub async fn linked_folder_status(&self) -> Result<Vec<LinkedFolderStatus>, Box<dyn std::error::Error + Send + Sync>> {
+        let folders = self.list_linked_folders().await?;
+        let jobs: Vec<(String, String)> = {
+            let conn = self.conn.lock().await;
+            let mut stmt = conn.prepare(
+                "SELECT source, status FROM jobs WHERE source_kind = 'file' AND status IN ('queued', 'running', 'failed')"
+            )?;
+            let jobs = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+            jobs
+        };
+
+        let mut statuses = Vec::with_capacity(folders.len());
+        for folder in folders {
+            let folder_path = PathBuf::from(&folder.path);
+            let documents = self.documents_under(&folder_path).await?.len();
+            let folder_jobs = jobs.iter().filter(|(source, _)| Path::new(source).starts_with(&folder_path));
+            let (mut pending_jobs, mut failed_jobs) = (0, 0);
+            for (_, status) in folder_jobs {
+                if status == "failed" {
+                    failed_jobs += 1;
+                } else {
+                    pending_jobs += 1;
+                }
+            }
+            statuses.push(LinkedFolderStatus {
+                folder,
+                documents,
+                pending_jobs,
+                failed_jobs,
+            });
+        }
+        Ok(statuses)
+    }
+}
+
+/// Handle to the background folder sync, kept in `AppState`
+#[derive(Debug, Clone)]
+pub struct LinkedFolderSync {
+    wake: Arc<Notify>,
+}
+
+impl LinkedFolderSync {
+    pub fn new() -> Self {
+        Self { wake: Arc::new(Notify::new()) }
+    }
+
+    /// Asks for a sync now rather than at the next interval
+    pub fn notify(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Spawns the sync loop. Like the ingestion queue it works against whichever
+    /// canon is loaded, and hands any new jobs to the queue.
+    pub fn start(&self, app_handle: AppHandle) {
+        let sync = self.clone();
+        tauri::async_runtime::spawn(async move {
+            log::info!("Linked folder sync started");
+            loop {
+                let state = app_handle.state::<AppState>();
+                let store = state.doc_store.lock().await.clone();
+
+                match store.sync_linked_folders().await.map_err(|e| e.to_string()) {
+                    Ok(reports) => {
+                        if reports.iter().any(|report| report.queued() > 0) {
+                            state.ingestion_queue.notify();
+                        }
+                        for report in reports.iter().filter(|report| report.queued() > 0 || report.removed > 0) {
+                            let _ = app_handle.emit("simple-log-message", json!({
+                                "message": format!(
+                                    "Synced {}: {} new, {} changed, {} removed",
+                                    report.path, report.queued_new, report.queued_changed, report.removed
+                                ),
+                                "timestamp": chrono::Local::now().to_rfc3339(),
+                                "level": "info"
+                            }));
+                        }
+                        if !reports.is_empty() {
+                            let _ = app_handle.emit("linked-folders-synced", &reports);
+                        }
+                    }
+                    Err(e) => log::error!("Linked folder sync failed: {}", e),
+                }
+
+                let _ = tokio::time::timeout(SYNC_INTERVAL, sync.wake.notified()).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document_store::insert_test_document;
+
+    #[tokio::test]
+    async fn test_folder_sync_queues_changes_and_removes_deleted_files() {
+        let canon_dir = tempfile::tempdir().unwrap();
+        let folder_dir = tempfile::tempdir().unwrap();
+        let store = DocumentStore::new(canon_dir.path().to_path_buf()).unwrap();
+        std::fs::write(folder_dir.path().join("chapter-1.md"), "# One").unwrap();
+        std::fs::write(folder_dir.path().join("scratch.md"), "# Scratch").unwrap();
+
+        let folder = store
+        .add_linked_folder(folder_dir.path(), vec!["*.md".to_string()], vec!["scratch*".to_string()])
+        .await
+        .unwrap();
+        assert!(store.add_linked_folder(&folder_dir.path().join("missing"), vec![], vec![]).await.is_err());
+
+        let report = store.sync_linked_folder(&folder).await.unwrap();
+        assert_eq!((report.queued_new, report.queued_changed, report.unchanged), (1, 0, 0));
+
+        // A job created after the file's last change means it's already handled
+        let report = store.sync_linked_folder(&folder).await.unwrap();
+        assert_eq!((report.queued_new, report.unchanged), (0, 1));
+
+        // A document whose file disappears is removed
+        let gone = folder_dir.path().join("chapter-2.md");
+        {
+            let conn = store.conn.lock().await;
+            insert_test_document(&conn, 1, &gone.to_string_lossy(), "test-model");
+        }
+        let report = store.sync_linked_folder(&folder).await.unwrap();
+        assert_eq!(report.removed, 1);
+
+        let status = store.linked_folder_status().await.unwrap();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].pending_jobs, 1);
+    }
+
+    #[test]
+    fn test_modified_after_compares_times() {
+        assert!(modified_after("2026-03-01T10:00:00+00:00", None));
+        assert!(modified_after("2026-03-01T10:00:00+00:00", Some("2026-03-01T09:00:00+00:00")));
+        assert!(!modified_after("2026-03-01T10:00:00+00:00", Some("2026-03-01T11:00:00+01:00")));
+    }
+}
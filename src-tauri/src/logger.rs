@@ -9,6 +9,7 @@ use std::io::{self, BufReader, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use chrono::Local;
 use crate::preferences::Preferences;
+use crate::document_store::SearchHit;
 use crate::NewLogger;
 use crate::ai::*;
 
@@ -20,6 +21,33 @@ pub struct VectorSearchResult {
     pub similarity: f32,
     pub content: String,
     pub chunk_id: usize,
+    /// BM25 keyword relevance, present when hybrid search matched the chunk's words
+    #[serde(default)]
+    pub lexical_score: Option<f32>,
+    /// Reciprocal rank fusion score the chunk was ranked by in hybrid search
+    #[serde(default)]
+    pub fused_score: Option<f32>,
+    /// The canon the chunk came from
+    #[serde(default)]
+    pub canon_name: String,
+    /// Author, title, section and page of the chunk, as far as they're known
+    #[serde(default)]
+    pub citation: String,
+}
+
+impl From<&SearchHit> for VectorSearchResult {
+    fn from(hit: &SearchHit) -> Self {
+        VectorSearchResult {
+            name: hit.doc_name.clone(),
+            similarity: hit.similarity,
+            content: hit.chunk.clone(),
+            chunk_id: hit.chunk_id,
+            lexical_score: hit.lexical_score,
+            fused_score: hit.fused_score,
+            canon_name: hit.canon_name.clone(),
+            citation: hit.citation.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
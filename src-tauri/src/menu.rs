@@ -313,11 +313,19 @@ async fn set_database_path_async<R: Runtime>(
     file_path: String,
     app_handle: AppHandle<R>,
 ) {
+    // Preferences are locked before the store, as everywhere else
+    let preferred_model = {
+        let app_state = app_handle.state::<AppState>();
+        let preferences = app_state.preferences.lock().await;
+        crate::preferred_embedding_model_name(&preferences)
+    };
     let mut store = doc_store.lock().await; // ✅ Correct async lock
     let path = file_path.clone();
     let path_buf = PathBuf::from(file_path);
     match store.set_database_path(path_buf).await {
         Ok(_) => {
+            // The new canon may have jobs left over from a previous session
+            app_handle.state::<AppState>().ingestion_queue.notify();
             let simple_log_data = SimpleLog {
                 message: format!("New canon set: {}", path),
                 level: "info".to_string(),
@@ -325,6 +333,7 @@ async fn set_database_path_async<R: Runtime>(
                 id: None,
             };
             let _ = app_handle.emit("simple-log-message", simple_log_data);
+            crate::warn_on_embedding_model_mismatch(&app_handle, &store, &preferred_model).await;
         }
         Err(e) => {
             let simple_log_data = SimpleLog {
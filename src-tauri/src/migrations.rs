@@ -0,0 +1,520 @@
+#![allow(dead_code)]
+// src/migrations.rs
+//
+// The canon schema is versioned. Each migration below is applied once, in order, in
+// its own transaction, and recorded in `schema_version`. Canons from before versioning
+// have no `schema_version` table and may sit anywhere along the history, so every
+// migration tolerates finding its change already made (IF NOT EXISTS, adding columns
+// only when missing); such canons replay the whole list. A canon recording a version
+// newer than this build knows is refused rather than opened and half understood.
+//
+// Add new schema changes as a new migration at the end. Never edit one that has shipped.
+
+use chrono::Local;
+use rusqlite::{params, Connection};
+
+use crate::document_store::{add_column_if_missing, DocumentStore};
+use crate::ingestion_queue;
+use crate::linked_folders;
+use crate::collections;
+use crate::document_metadata;
+
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    apply: fn(&Connection) -> Result<(), Box<dyn std::error::Error>>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Create the documents, embeddings and canon tables",
+        apply: create_original_tables,
+    },
+    Migration {
+        version: 2,
+        description: "Add the paused flag to documents",
+        apply: add_paused_flag,
+    },
+    Migration {
+        version: 3,
+        description: "Move JSON embeddings into per-model sqlite-vec tables",
+        apply: move_to_sqlite_vec,
+    },
+    Migration {
+        version: 4,
+        description: "Add the keyword index over chunk text",
+        apply: add_keyword_index,
+    },
+    Migration {
+        version: 5,
+        description: "Add the ingestion jobs table",
+        apply: add_jobs_table,
+    },
+    Migration {
+        version: 6,
+        description: "Record the chunking strategy per document",
+        apply: add_chunking_columns,
+    },
+    Migration {
+        version: 7,
+        description: "Record the embedding model per canon and index metadata per model",
+        apply: add_embedding_model_metadata,
+    },
+    Migration {
+        version: 8,
+        description: "Hash documents and chunks for incremental re-ingestion",
+        apply: add_content_hashes,
+    },
+    Migration {
+        version: 9,
+        description: "Add the linked folders table",
+        apply: add_linked_folders_table,
+    },
+    Migration {
+        version: 10,
+        description: "Give documents.notes the empty default it was always meant to have",
+        apply: fix_notes_default,
+    },
+    Migration {
+        version: 11,
+        description: "Add document tags and retrieval presets",
+        apply: add_collection_tables,
+    },
+    Migration {
+        version: 12,
+        description: "Add per-document retrieval weight and pinning",
+        apply: add_retrieval_weighting,
+    },
+    Migration {
+        version: 13,
+        description: "Record where each chunk sits in its source",
+        apply: add_chunk_provenance,
+    },
+    Migration {
+        version: 14,
+        description: "Keep each document's source metadata",
+        apply: add_document_metadata_table,
+    },
+    Migration {
+        version: 15,
+        description: "Record which version of text extraction read each document",
+        apply: add_extraction_version,
+    },
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum SchemaError {
+    #[error("This canon uses schema version {found}, but this version of Ghostwriter only understands up to version {supported}. Open it with a newer release.")]
+    NewerThanSupported { found: i64, supported: i64 },
+    #[error("This canon uses schema version {found} and needs upgrading to version {supported}. Open it as the main canon once to upgrade it.")]
+    NeedsUpgrade { found: i64, supported: i64 },
+}
+
+/// The schema version this build writes
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|migration| migration.version).unwrap_or(0)
+}
+
+/// The version recorded in a canon; 0 for a fresh file or one from before versioning
+pub fn current_version(conn: &Connection) -> Result<i64, rusqlite::Error> {
+    let versioned: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'schema_version')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !versioned {
+        return Ok(0);
+    }
+    conn.query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", [], |row| row.get(0))
+}
+
+/// The oldest schema a read-only connection can search: per-model sqlite-vec indexes
+/// with their metadata, and the keyword index
+pub const OLDEST_READABLE_VERSION: i64 = 7;
+/// Versions that added what searches use beyond those. Read-only canons from before
+/// one of these are searched as if that feature were empty.
+pub const TAGS_VERSION: i64 = 11;
+pub const RETRIEVAL_WEIGHT_VERSION: i64 = 12;
+pub const CHUNK_PROVENANCE_VERSION: i64 = 13;
+pub const DOCUMENT_METADATA_VERSION: i64 = 14;
+
+/// Checks that a canon can be read without migrating it, and returns its version.
+/// Canons older than this build are fine down to `OLDEST_READABLE_VERSION`.
+pub fn check_readable(conn: &Connection) -> Result<i64, Box<dyn std::error::Error>> {
+    let found = current_version(conn)?;
+    let supported = latest_version();
+    if found > supported {
+        return Err(Box::new(SchemaError::NewerThanSupported { found, supported }));
+    }
+    if found < OLDEST_READABLE_VERSION {
+        return Err(Box::new(SchemaError::NeedsUpgrade { found, supported }));
+    }
+    Ok(found)
+}
+
+/// Checks that a canon is exactly at this build's version, for connections that can't migrate it
+pub fn check_current(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    let found = current_version(conn)?;
+    let supported = latest_version();
+    if found > supported {
+        return Err(Box::new(SchemaError::NewerThanSupported { found, supported }));
+    }
+    if found < supported {
+        return Err(Box::new(SchemaError::NeedsUpgrade { found, supported }));
+    }
+    Ok(())
+}
+
+/// Applies every migration the canon hasn't had yet
+pub fn migrate(conn: &mut Connection) -> Result<(), Box<dyn std::error::Error>> {
+    migrate_to(conn, latest_version())
+}
+
+/// Applies the migrations the canon hasn't had yet, up to and including `target`.
+/// Anything short of the latest version is for building older canons in tests.
+pub(crate) fn migrate_to(conn: &mut Connection, target: i64) -> Result<(), Box<dyn std::error::Error>> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version
+        (
+        version INTEGER PRIMARY KEY,
+        description TEXT NOT NULL,
+        applied_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    let found = current_version(conn)?;
+    let supported = latest_version();
+    if found > supported {
+        return Err(Box::new(SchemaError::NewerThanSupported { found, supported }));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > found && migration.version <= target) {
+        log::info!("Applying canon migration {}: {}", migration.version, migration.description);
+        let tx = conn.transaction()?;
+        (migration.apply)(&tx).map_err(|e| {
+            format!("Canon migration {} ({}) failed: {}", migration.version, migration.description, e)
+        })?;
+        tx.execute(
+            "INSERT INTO schema_version (version, description, applied_at) VALUES (?1, ?2, ?3)",
+            params![migration.version, migration.description, Local::now().to_rfc3339()],
+        )?;
+        tx.commit()?;
+    }
+
+    if found < target {
+        log::info!("Canon schema is now at version {} (was {})", target.min(supported), found);
+    }
+    Ok(())
+}
+
+fn create_original_tables(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS documents
+        (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        title TEXT,
+        authors JSON,
+        created_at TEXT NOT NULL,
+        file_path TEXT NOT NULL,
+        embedding_model_name TEXT DEFAULT 'unknown',
+        notes TEXT DEFAULT '',
+        UNIQUE(file_path, embedding_model_name)
+        );
+        CREATE TABLE IF NOT EXISTS embeddings
+        (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        doc_id INTEGER NOT NULL,
+        chunk TEXT NOT NULL,
+        embedding_model_name TEXT DEFAULT 'unknown',
+        FOREIGN KEY(doc_id) REFERENCES documents(id)
+        );
+        CREATE TABLE IF NOT EXISTS canon
+        (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        owner TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        modified_at TEXT NOT NULL,
+        notes TEXT NOT NULL
+        );",
+    )?;
+    Ok(())
+}
+
+fn add_paused_flag(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    add_column_if_missing(conn, "documents", "paused", "BOOLEAN DEFAULT 0")?;
+    Ok(())
+}
+
+fn move_to_sqlite_vec(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    // Created in its current shape: the JSON migration registers indexes through the
+    // same code that writes them today
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS vector_indexes
+        (
+        embedding_model_name TEXT PRIMARY KEY,
+        table_name TEXT NOT NULL UNIQUE,
+        dimension INTEGER NOT NULL,
+        normalized BOOLEAN,
+        provider TEXT,
+        created_at TEXT
+        )",
+        [],
+    )?;
+    DocumentStore::migrate_json_embeddings(conn)
+}
+
+fn add_keyword_index(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    DocumentStore::initialize_chunk_fts(conn)?;
+    Ok(())
+}
+
+fn add_jobs_table(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    ingestion_queue::initialize_jobs_table(conn)?;
+    Ok(())
+}
+
+fn add_chunking_columns(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    add_column_if_missing(conn, "documents", "chunking_strategy", "TEXT")?;
+    add_column_if_missing(conn, "documents", "chunking_params", "JSON")?;
+    Ok(())
+}
+
+fn add_embedding_model_metadata(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    add_column_if_missing(conn, "canon", "embedding_model_name", "TEXT")?;
+    add_column_if_missing(conn, "vector_indexes", "normalized", "BOOLEAN")?;
+    add_column_if_missing(conn, "vector_indexes", "provider", "TEXT")?;
+    add_column_if_missing(conn, "vector_indexes", "created_at", "TEXT")?;
+    DocumentStore::backfill_normalization(conn)
+}
+
+fn add_content_hashes(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    add_column_if_missing(conn, "documents", "content_hash", "TEXT")?;
+    add_column_if_missing(conn, "documents", "source_modified_at", "TEXT")?;
+    add_column_if_missing(conn, "embeddings", "content_hash", "TEXT")?;
+    add_column_if_missing(conn, "embeddings", "chunk_index", "INTEGER")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_documents_content_hash ON documents(content_hash)", [])?;
+    Ok(())
+}
+
+fn add_linked_folders_table(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    linked_folders::initialize_linked_folders_table(conn)?;
+    Ok(())
+}
+
+fn add_collection_tables(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    collections::initialize_collection_tables(conn)?;
+    Ok(())
+}
+
+fn add_retrieval_weighting(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    add_column_if_missing(conn, "documents", "retrieval_weight", "REAL DEFAULT 1.0")?;
+    add_column_if_missing(conn, "documents", "pinned", "BOOLEAN DEFAULT 0")?;
+    Ok(())
+}
+
+/// Documents ingested before this migration get their source type from their path;
+/// the rest of their metadata comes with the next re-ingestion
+fn add_document_metadata_table(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    document_metadata::initialize_metadata_table(conn)?;
+    let documents: Vec<(i64, String)> = {
+        let mut stmt = conn.prepare("SELECT id, file_path FROM documents")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    for (doc_id, file_path) in documents {
+        if let Some(source_type) = document_metadata::source_type_for_path(&file_path) {
+            conn.execute(
+                "INSERT OR IGNORE INTO document_metadata (doc_id, source_type) VALUES (?1, ?2)",
+                params![doc_id, source_type],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// NULL for documents read before this migration, which counts as each ingestor's
+/// first version
+fn add_extraction_version(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    add_column_if_missing(conn, "documents", "extraction_version", "INTEGER")?;
+    Ok(())
+}
+
+/// Character offsets, page and section of each chunk; NULL for chunks embedded before
+/// this migration until their document is re-ingested
+fn add_chunk_provenance(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    add_column_if_missing(conn, "embeddings", "start_offset", "INTEGER")?;
+    add_column_if_missing(conn, "embeddings", "end_offset", "INTEGER")?;
+    add_column_if_missing(conn, "embeddings", "page", "INTEGER")?;
+    add_column_if_missing(conn, "embeddings", "section", "TEXT")?;
+    Ok(())
+}
+
+/// Canons created before this migration declared `notes TEXT DEFFAULT ''`, which SQLite
+/// reads as a column of type "TEXT DEFFAULT ''" with no default, so notes came back NULL.
+/// A column default can't be altered in place, so the table is rebuilt.
+fn fix_notes_default(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    let notes_default: Option<String> = conn.query_row(
+        "SELECT dflt_value FROM pragma_table_info('documents') WHERE name = 'notes'",
+        [],
+        |row| row.get(0),
+    )?;
+    if notes_default.is_none() {
+        conn.execute_batch(
+            "CREATE TABLE documents_rebuilt
+            (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            title TEXT,
+            authors JSON,
+            created_at TEXT NOT NULL,
+            file_path TEXT NOT NULL,
+            paused BOOLEAN DEFAULT 0,
+            embedding_model_name TEXT DEFAULT 'unknown',
+            notes TEXT DEFAULT '',
+            chunking_strategy TEXT,
+            chunking_params JSON,
+            content_hash TEXT,
+            source_modified_at TEXT,
+            UNIQUE(file_path, embedding_model_name)
+            );
+            INSERT INTO documents_rebuilt (id, name, title, authors, created_at, file_path, paused,
+                embedding_model_name, notes, chunking_strategy, chunking_params, content_hash, source_modified_at)
+            SELECT id, name, title, authors, created_at, file_path, paused,
+                embedding_model_name, notes, chunking_strategy, chunking_params, content_hash, source_modified_at
+            FROM documents;
+            DROP TABLE documents;
+            ALTER TABLE documents_rebuilt RENAME TO documents;
+            CREATE INDEX IF NOT EXISTS idx_documents_content_hash ON documents(content_hash);",
+        )?;
+    }
+    conn.execute("UPDATE documents SET notes = '' WHERE notes IS NULL", [])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// A canon as written by 0.3.5, before sqlite-vec: vectors as JSON text, notes
+    /// without a default
+    const CANON_0_3_5: &str = include_str!("../tests/fixtures/canons/canon_0_3_5.sql");
+    /// A canon from before documents could be paused
+    const CANON_BEFORE_PAUSE: &str = include_str!("../tests/fixtures/canons/canon_before_pause.sql");
+
+    fn write_fixture(path: &Path, sql: &str) {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(sql).unwrap();
+    }
+
+    fn applied_versions(conn: &Connection) -> Vec<i64> {
+        let mut stmt = conn.prepare("SELECT version FROM schema_version ORDER BY version").unwrap();
+        let versions = stmt.query_map([], |row| row.get(0)).unwrap();
+        versions.collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn test_migrations_are_numbered_in_order() {
+        for (position, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, position as i64 + 1);
+        }
+    }
+
+    #[test]
+    fn test_fresh_canon_is_created_at_the_latest_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let canon_path = dir.path().join("fresh.canon");
+        {
+            let store = DocumentStore::new(canon_path.clone()).unwrap();
+            let conn = store.conn.blocking_lock();
+            assert_eq!(applied_versions(&conn), (1..=latest_version()).collect::<Vec<_>>());
+
+            crate::document_store::insert_test_document(&conn, 1, "/tmp/a.md", "test-model");
+            let notes: Option<String> = conn.query_row("SELECT notes FROM documents", [], |row| row.get(0)).unwrap();
+            assert_eq!(notes.as_deref(), Some(""));
+        }
+
+        // Reopening applies nothing again
+        let store = DocumentStore::new(canon_path).unwrap();
+        let conn = store.conn.blocking_lock();
+        assert_eq!(applied_versions(&conn).len(), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn test_canon_from_0_3_5_is_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let canon_path = dir.path().join("release.canon");
+        write_fixture(&canon_path, CANON_0_3_5);
+
+        let store = DocumentStore::new(canon_path).unwrap();
+        let conn = store.conn.blocking_lock();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+
+        let (name, notes, paused): (String, Option<String>, bool) = conn.query_row(
+            "SELECT name, notes, paused FROM documents WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).unwrap();
+        assert_eq!(name, "the-big-sleep.md");
+        assert_eq!(notes.as_deref(), Some(""));
+        assert!(!paused);
+
+        let results = DocumentStore::knn_search(&conn, "text-embedding-ada-002", &[1.0, 0.0, 0.0], 2).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].3, "The General's orchids");
+
+        let keyword_hits: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM embeddings_fts WHERE embeddings_fts MATCH 'orchids'",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+        assert_eq!(keyword_hits, 1);
+
+        let canon_model: Option<String> = conn.query_row("SELECT embedding_model_name FROM canon", [], |row| row.get(0)).unwrap();
+        assert!(canon_model.is_none());
+        for table in ["jobs", "linked_folders", "vector_indexes", "document_tags", "retrieval_presets"] {
+            let exists: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+                params![table],
+                |row| row.get(0),
+            ).unwrap();
+            assert!(exists, "{} should exist after migrating", table);
+        }
+    }
+
+    #[test]
+    fn test_canon_from_before_pausing_gains_the_paused_flag() {
+        let dir = tempfile::tempdir().unwrap();
+        let canon_path = dir.path().join("older.canon");
+        write_fixture(&canon_path, CANON_BEFORE_PAUSE);
+
+        let store = DocumentStore::new(canon_path).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(!runtime.block_on(store.is_document_paused(1)).unwrap());
+        runtime.block_on(store.update_document_pause_state(1, true)).unwrap();
+        assert!(runtime.block_on(store.is_document_paused(1)).unwrap());
+    }
+
+    #[test]
+    fn test_canon_from_a_newer_release_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let canon_path = dir.path().join("future.canon");
+        {
+            let store = DocumentStore::new(canon_path.clone()).unwrap();
+            let conn = store.conn.blocking_lock();
+            conn.execute(
+                "INSERT INTO schema_version (version, description, applied_at) VALUES (?1, 'From the future', 'later')",
+                params![latest_version() + 1],
+            ).unwrap();
+        }
+
+        let error = DocumentStore::new(canon_path).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<SchemaError>(),
+            Some(SchemaError::NewerThanSupported { .. })
+        ));
+    }
+}
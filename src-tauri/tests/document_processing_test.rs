@@ -37,7 +37,7 @@ async fn setup_store() -> (DocumentStore, EmbeddingGenerator) {
     
     // Register ingestors
     store.register_ingestor(Box::new(MdxIngestor));
-    store.register_ingestor(Box::new(PdfIngestor));
+    store.register_ingestor(Box::new(PdfIngestor::default()));
     
     
     (store, cloned_embedding_generator)
@@ -0,0 +1,48 @@
+-- A canon as written by Ghostwriter 0.3.5, before schema versioning and sqlite-vec.
+-- Note the original `notes TEXT DEFFAULT ''` typo, kept as shipped.
+CREATE TABLE documents
+(
+id INTEGER PRIMARY KEY AUTOINCREMENT,
+name TEXT NOT NULL,
+title TEXT,
+authors JSON,
+created_at TEXT NOT NULL,
+file_path TEXT NOT NULL,
+paused BOOLEAN DEFAULT 0,
+embedding_model_name TEXT DEFAULT 'unknown',
+notes TEXT DEFFAULT '',
+UNIQUE(file_path, embedding_model_name)
+);
+CREATE TABLE embeddings
+(
+id INTEGER PRIMARY KEY AUTOINCREMENT,
+doc_id INTEGER NOT NULL,
+chunk TEXT NOT NULL,
+embedding JSON NOT NULL,
+embedding_model_name TEXT DEFAULT 'unknown',
+FOREIGN KEY(doc_id) REFERENCES documents(id)
+);
+CREATE TABLE canon
+(
+id INTEGER PRIMARY KEY AUTOINCREMENT,
+name TEXT NOT NULL,
+owner TEXT NOT NULL,
+created_at TEXT NOT NULL,
+modified_at TEXT NOT NULL,
+notes TEXT NOT NULL
+);
+
+INSERT INTO canon (name, owner, created_at, modified_at, notes)
+VALUES ('Chandler', 'ghostwriter', '2025-01-12T09:30:00+00:00', '2025-01-12T09:30:00+00:00', '');
+
+INSERT INTO documents (id, name, created_at, file_path, embedding_model_name)
+VALUES (1, 'the-big-sleep.md', '2025-01-12T09:31:00+00:00', '/Users/writer/canon/the-big-sleep.md', 'text-embedding-ada-002');
+INSERT INTO documents (id, name, created_at, file_path, embedding_model_name, notes)
+VALUES (2, 'farewell-my-lovely.md', '2025-01-12T09:32:00+00:00', '/Users/writer/canon/farewell-my-lovely.md', 'text-embedding-ada-002', 'Moose Malloy chapters');
+
+INSERT INTO embeddings (doc_id, chunk, embedding, embedding_model_name)
+VALUES (1, 'The General''s orchids', '[1.0, 0.0, 0.0]', 'text-embedding-ada-002');
+INSERT INTO embeddings (doc_id, chunk, embedding, embedding_model_name)
+VALUES (1, 'Rain on the Sternwood house', '[0.0, 1.0, 0.0]', 'text-embedding-ada-002');
+INSERT INTO embeddings (doc_id, chunk, embedding, embedding_model_name)
+VALUES (2, 'A big man in a loud suit', '[0.0, 0.0, 1.0]', 'text-embedding-ada-002');
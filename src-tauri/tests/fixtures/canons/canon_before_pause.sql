@@ -0,0 +1,29 @@
+-- A canon from before documents could be paused. The paused column used to be
+-- patched in the first time a document was paused.
+CREATE TABLE documents
+(
+id INTEGER PRIMARY KEY AUTOINCREMENT,
+name TEXT NOT NULL,
+title TEXT,
+authors JSON,
+created_at TEXT NOT NULL,
+file_path TEXT NOT NULL,
+embedding_model_name TEXT DEFAULT 'unknown',
+notes TEXT DEFFAULT '',
+UNIQUE(file_path, embedding_model_name)
+);
+CREATE TABLE embeddings
+(
+id INTEGER PRIMARY KEY AUTOINCREMENT,
+doc_id INTEGER NOT NULL,
+chunk TEXT NOT NULL,
+embedding JSON NOT NULL,
+embedding_model_name TEXT DEFAULT 'unknown',
+FOREIGN KEY(doc_id) REFERENCES documents(id)
+);
+
+INSERT INTO documents (id, name, created_at, file_path, embedding_model_name)
+VALUES (1, 'the-long-goodbye.md', '2024-11-02T20:15:00+00:00', '/Users/writer/canon/the-long-goodbye.md', 'nomic-embed-text');
+
+INSERT INTO embeddings (doc_id, chunk, embedding, embedding_model_name)
+VALUES (1, 'Terry Lennox at the Dancers', '[0.6, 0.8]', 'nomic-embed-text');
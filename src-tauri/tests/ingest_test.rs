@@ -9,7 +9,7 @@ use std::path::{Path, PathBuf};
 #[tokio::test]
 async fn test_ingestors_handle_correct_files() {
     let mdx = MdxIngestor;
-    let pdf = PdfIngestor;
+    let pdf = PdfIngestor::default();
     let md = MarkdownIngestor;
 
     let test_files = vec![
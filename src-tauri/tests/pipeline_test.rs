@@ -32,7 +32,7 @@ async fn test_full_pdf_pipeline() {
         .expect("Failed to create test document store");
 
     // Register the PDF ingestor
-    doc_store.register_ingestor(Box::new(PdfIngestor));
+    doc_store.register_ingestor(Box::new(PdfIngestor::default()));
 
     // Process document
     let result = doc_store.process_document(&test_pdf_path).await;